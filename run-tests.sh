@@ -12,6 +12,7 @@ unset NO_PROXY
 
 cargo test --all-features
 cargo test --no-default-features
+cargo clippy --no-default-features --all-targets -- --deny warnings
 cargo test --no-default-features --features charsets
 cargo test --no-default-features --features compress
 cargo test --no-default-features --features form
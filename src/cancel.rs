@@ -0,0 +1,59 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::error::{Error, ErrorKind};
+
+/// A flag used to cancel requests from another thread.
+///
+/// Clones of a `CancelToken` share the same flag, once one of them is cancelled, the requests using any
+/// of them fail with [`ErrorKind::Cancelled`]. The flag is checked before connecting and before every
+/// read or write on the connection: a read waiting for data from the server is only interrupted once data
+/// arrives or the read timeout expires.
+///
+/// # Example
+/// ```no_run
+/// # fn main() -> attohttpc::Result {
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// let token = attohttpc::CancelToken::new();
+/// let canceller = token.clone();
+/// thread::spawn(move || {
+///     thread::sleep(Duration::from_secs(10));
+///     canceller.cancel();
+/// });
+///
+/// let resp = attohttpc::get("http://example.com/large_file").cancel_token(token).send()?;
+/// let body = resp.bytes()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Create a new `CancelToken` which is not cancelled.
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Cancel the requests using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Check if this token was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Fail with an I/O error wrapping [`ErrorKind::Cancelled`] if this token was cancelled.
+    pub(crate) fn check_io(&self) -> io::Result<()> {
+        if self.is_cancelled() {
+            Err(Error::from(ErrorKind::Cancelled).into())
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -1,10 +1,14 @@
 //! This module is a clean re-export of the `encoding_rs` crate.
 //! You can probably find the charset you need in here.
 
-use encoding_rs::Encoding;
+use encoding_rs::{EncoderResult, Encoding};
+
+use crate::{ErrorKind, Result};
 
 /// This type is an alias to the `encoding_rs::Encoding` type, used
 /// to normalize the name across the crate.
+///
+/// The canonical name of a charset, such as `windows-1252`, is returned by its `name` method.
 pub type Charset = &'static Encoding;
 
 pub use encoding_rs::{
@@ -13,3 +17,90 @@ pub use encoding_rs::{
     KOI8_R, KOI8_U, MACINTOSH, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1250, WINDOWS_1251, WINDOWS_1252,
     WINDOWS_1253, WINDOWS_1254, WINDOWS_1255, WINDOWS_1256, WINDOWS_1257, WINDOWS_1258, WINDOWS_874, X_MAC_CYRILLIC,
 };
+
+/// Get the charset with the given label, such as `"iso-8859-2"` or `"utf8"`.
+///
+/// Labels are matched case-insensitively against those of the Encoding Standard, surrounding whitespace is
+/// ignored. An unknown label fails with [`ErrorKind::InvalidCharset`].
+pub fn from_label(label: &str) -> Result<Charset> {
+    Encoding::for_label(label.as_bytes()).ok_or_else(|| ErrorKind::InvalidCharset(label.to_string()).into())
+}
+
+/// Encode `text` in the output encoding of `charset`, UTF-8 for the UTF-16 charsets.
+///
+/// Characters which cannot be represented are replaced by HTML numeric character references if `escape_unmappable`
+/// is set, otherwise they fail with `ErrorKind::Encoding`.
+pub(crate) fn encode(text: &str, charset: Charset, escape_unmappable: bool) -> Result<Vec<u8>> {
+    if escape_unmappable {
+        let (bytes, _, _) = charset.encode(text);
+        return Ok(bytes.into_owned());
+    }
+
+    let mut encoder = charset.new_encoder();
+    let mut buf = Vec::with_capacity(text.len());
+    let mut input = text;
+    loop {
+        let (result, read) = encoder.encode_from_utf8_to_vec_without_replacement(input, &mut buf, true);
+        input = &input[read..];
+        match result {
+            EncoderResult::InputEmpty => return Ok(buf),
+            EncoderResult::OutputFull => buf.reserve(input.len() + 16),
+            EncoderResult::Unmappable(character) => {
+                return Err(ErrorKind::Encoding {
+                    charset: encoder.encoding().name(),
+                    character,
+                }
+                .into())
+            }
+        }
+    }
+}
+
+#[test]
+fn test_from_label() {
+    assert_eq!(from_label("iso-8859-2").unwrap(), ISO_8859_2);
+    assert_eq!(from_label("UTF-8").unwrap(), UTF_8);
+    assert_eq!(from_label("utf8").unwrap(), UTF_8);
+    assert_eq!(from_label(" Shift_JIS ").unwrap(), SHIFT_JIS);
+    // Latin-1 labels map to windows-1252, as browsers do.
+    assert_eq!(from_label("latin1").unwrap(), WINDOWS_1252);
+
+    let err = from_label("klingon").unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidCharset(label) if label == "klingon"));
+    assert_eq!(err.to_string(), "Unknown charset: klingon");
+}
+
+#[test]
+fn test_name() {
+    assert_eq!(WINDOWS_1252.name(), "windows-1252");
+    assert_eq!(from_label("latin1").unwrap().name(), "windows-1252");
+    assert_eq!(from_label("utf8").unwrap().name(), "UTF-8");
+    assert_eq!(ISO_8859_2.name(), "ISO-8859-2");
+}
+
+#[test]
+fn test_encode() {
+    assert_eq!(
+        encode("caf\u{e9} \u{20ac}5", WINDOWS_1252, false).unwrap(),
+        b"caf\xe9 \x805"
+    );
+    assert_eq!(
+        encode("\u{65e5}\u{672c}", SHIFT_JIS, false).unwrap(),
+        b"\x93\xfa\x96\x7b"
+    );
+    // The UTF-16 charsets are sent as UTF-8.
+    assert_eq!(encode("\u{e9}", UTF_16LE, false).unwrap(), "\u{e9}".as_bytes());
+
+    let err = encode("na\u{ef}ve \u{2603}", WINDOWS_1252, false).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::Encoding {
+            charset: "windows-1252",
+            character: '\u{2603}'
+        }
+    ));
+    assert_eq!(
+        encode("na\u{ef}ve \u{2603}", WINDOWS_1252, true).unwrap(),
+        b"na\xefve &#9731;"
+    );
+}
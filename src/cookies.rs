@@ -0,0 +1,446 @@
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http::header::{HeaderMap, SET_COOKIE};
+use url::{Host, Url};
+
+use crate::tls::days_since_epoch;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Cookie {
+    name: String,
+    value: String,
+    // Host which set the cookie, or the domain given by its `Domain` attribute.
+    domain: String,
+    host_only: bool,
+    path: String,
+    secure: bool,
+    expires: Option<SystemTime>,
+}
+
+impl Cookie {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn matches(&self, url: &Url, now: SystemTime) -> bool {
+        let host = match url.host_str() {
+            Some(host) => host,
+            None => return false,
+        };
+        let domain_matches = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(url, &self.domain)
+        };
+        domain_matches
+            && path_match(url.path(), &self.path)
+            && (!self.secure || url.scheme() == "https")
+            && !self.is_expired(now)
+    }
+}
+
+/// Check if the host of a URL is the given domain or one of its subdomains.
+fn domain_match(url: &Url, domain: &str) -> bool {
+    match url.host() {
+        Some(Host::Domain(host)) => {
+            host == domain || host.strip_suffix(domain).is_some_and(|prefix| prefix.ends_with('.'))
+        }
+        // Cookies are never shared between the subdomains of an IP address.
+        Some(_) => url.host_str() == Some(domain),
+        None => false,
+    }
+}
+
+/// Public suffixes under which anyone can register a domain, in addition to the top-level domains.
+///
+/// This is a short list of the most used suffixes, not the full Public Suffix List.
+const PUBLIC_SUFFIXES: &[&str] = &[
+    "ac.uk",
+    "co.uk",
+    "gov.uk",
+    "ltd.uk",
+    "me.uk",
+    "net.uk",
+    "org.uk",
+    "plc.uk",
+    "sch.uk",
+    "com.au",
+    "edu.au",
+    "gov.au",
+    "net.au",
+    "org.au",
+    "co.nz",
+    "net.nz",
+    "org.nz",
+    "co.jp",
+    "ne.jp",
+    "or.jp",
+    "ac.jp",
+    "co.kr",
+    "or.kr",
+    "co.in",
+    "net.in",
+    "org.in",
+    "co.za",
+    "org.za",
+    "com.br",
+    "net.br",
+    "org.br",
+    "com.cn",
+    "net.cn",
+    "org.cn",
+    "com.hk",
+    "com.mx",
+    "com.tr",
+    "com.tw",
+    "com.sg",
+    "com.ar",
+    "co.il",
+    "co.id",
+    "github.io",
+    "gitlab.io",
+    "herokuapp.com",
+    "appspot.com",
+    "blogspot.com",
+    "netlify.app",
+    "vercel.app",
+    "pages.dev",
+    "workers.dev",
+    "azurewebsites.net",
+    "cloudfront.net",
+    "s3.amazonaws.com",
+];
+
+/// Check if a domain is a public suffix, which a cookie must not be shared under.
+fn is_public_suffix(domain: &str) -> bool {
+    !domain.contains('.') || PUBLIC_SUFFIXES.contains(&domain)
+}
+
+/// Check if a request path is within the path of a cookie.
+fn path_match(path: &str, cookie_path: &str) -> bool {
+    match path.strip_prefix(cookie_path) {
+        Some(rest) => cookie_path.ends_with('/') || rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// Get the path of a cookie which has no `Path` attribute, the directory of the request path.
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(end) => url.path()[..end].to_string(),
+    }
+}
+
+/// Parse a `Set-Cookie` header value received from the given URL.
+///
+/// Cookies whose `Domain` attribute does not match the URL are rejected. A `Domain` attribute which is a
+/// public suffix, such as `com` or `co.uk`, is only accepted from that exact host and makes a host-only
+/// cookie, so a site cannot set cookies for its sibling domains. Expired cookies are returned with
+/// their expiry, they remove the cookie they replace from the jar.
+fn parse_set_cookie(url: &Url, value: &str, now: SystemTime) -> Option<Cookie> {
+    let mut parts = value.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: value.trim().trim_matches('"').to_string(),
+        domain: url.host_str()?.to_string(),
+        host_only: true,
+        path: default_path(url),
+        secure: false,
+        expires: None,
+    };
+    let mut max_age = None;
+
+    for attr in parts {
+        let (key, val) = attr.split_once('=').unwrap_or((attr, ""));
+        let (key, val) = (key.trim(), val.trim());
+        if key.eq_ignore_ascii_case("secure") {
+            cookie.secure = true;
+        } else if key.eq_ignore_ascii_case("max-age") {
+            if let Ok(age) = val.parse::<i64>() {
+                max_age = Some(if age > 0 {
+                    now.checked_add(Duration::from_secs(age as u64))
+                } else {
+                    Some(UNIX_EPOCH)
+                });
+            }
+        } else if key.eq_ignore_ascii_case("expires") {
+            if let Some(expires) = parse_cookie_date(val) {
+                cookie.expires = Some(expires);
+            }
+        } else if key.eq_ignore_ascii_case("domain") {
+            let domain = val.trim_start_matches('.').to_ascii_lowercase();
+            if !domain.is_empty() {
+                if !domain_match(url, &domain) {
+                    debug!("rejecting cookie {} for domain {}", cookie.name, domain);
+                    return None;
+                }
+                if !is_public_suffix(&domain) {
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                } else if url.host_str() != Some(domain.as_str()) {
+                    debug!("rejecting cookie {} for public suffix {}", cookie.name, domain);
+                    return None;
+                }
+            }
+        } else if key.eq_ignore_ascii_case("path") && val.starts_with('/') {
+            cookie.path = val.to_string();
+        }
+    }
+
+    // `Max-Age` takes precedence over `Expires`, a cookie too far in the future never expires.
+    if let Some(expires) = max_age {
+        cookie.expires = expires;
+    }
+    Some(cookie)
+}
+
+/// Parse the date of an `Expires` attribute, such as `Wed, 21 Oct 2015 07:28:00 GMT`.
+///
+/// Like browsers, the parts of the date are recognized in any order, so the obsolete date formats are accepted.
+fn parse_cookie_date(value: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+
+    let (mut time, mut day, mut month, mut year) = (None, None, None, None);
+    let tokens = value
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != ':')
+        .filter(|token| !token.is_empty());
+    for token in tokens {
+        let number = token.parse::<u64>().ok();
+        if time.is_none() && token.contains(':') {
+            let parts: Vec<_> = token.split(':').map(|part| part.parse::<u64>().ok()).collect();
+            if let [Some(hours), Some(minutes), Some(seconds)] = parts[..] {
+                time = Some((hours, minutes, seconds));
+            }
+        } else if day.is_none() && token.len() <= 2 && number.is_some() {
+            day = number;
+        } else if month.is_none() && token.len() >= 3 {
+            let prefix = token[..3].to_ascii_lowercase();
+            month = MONTHS.iter().position(|&m| m == prefix).map(|i| i as u64 + 1);
+        } else if year.is_none() {
+            year = match (token.len(), number) {
+                (2, Some(year)) if year >= 70 => Some(1900 + year),
+                (2, Some(year)) => Some(2000 + year),
+                (4, Some(year)) => Some(year),
+                _ => None,
+            };
+        }
+    }
+
+    let (hours, minutes, seconds) = time?;
+    let (day, month, year) = (day?, month?, year?);
+    if !(1..=31).contains(&day) || hours > 23 || minutes > 59 || seconds > 59 {
+        return None;
+    }
+    if year < 1970 {
+        return Some(UNIX_EPOCH);
+    }
+    let secs = days_since_epoch(year, month, day) * 86400 + hours * 3600 + minutes * 60 + seconds;
+    UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+}
+
+/// Cookies received by the requests of a `Session`, sent back by the following requests.
+///
+/// Cookies are scoped to the host which set them, or to the domain given by their `Domain` attribute and its
+/// subdomains, and to their `Path`. They are removed once the time given by their `Max-Age` or `Expires`
+/// attribute has passed. Cookies with the `Secure` attribute are only sent over HTTPS. Public suffixes are not
+/// known, a `Domain` attribute only has to match the host which set the cookie.
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    cookies: RwLock<Vec<Cookie>>,
+}
+
+impl CookieJar {
+    /// Store the cookies set by the headers of a response to the given URL.
+    pub fn store(&self, url: &Url, headers: &HeaderMap) {
+        let now = SystemTime::now();
+        let received: Vec<_> = headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| parse_set_cookie(url, value, now))
+            .collect();
+        if received.is_empty() {
+            return;
+        }
+
+        if let Ok(mut cookies) = self.cookies.write() {
+            cookies.retain(|cookie| !cookie.is_expired(now));
+            for cookie in received {
+                cookies.retain(|c| c.name != cookie.name || c.domain != cookie.domain || c.path != cookie.path);
+                if !cookie.is_expired(now) {
+                    cookies.push(cookie);
+                }
+            }
+        }
+    }
+
+    /// Get the value of the `Cookie` header to send with a request to the given URL, if any.
+    ///
+    /// Cookies with longer paths are sent first.
+    pub fn header_value(&self, url: &Url) -> Option<String> {
+        let cookies = self.cookies.read().ok()?;
+        let now = SystemTime::now();
+
+        let mut matching: Vec<_> = cookies.iter().filter(|cookie| cookie.matches(url, now)).collect();
+        matching.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        let value = matching
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        if value.is_empty() {
+            None
+        } else {
+            Some(value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use http::header::{HeaderMap, HeaderValue, SET_COOKIE};
+    use url::Url;
+
+    use super::{parse_cookie_date, CookieJar};
+
+    fn set_cookies(values: &[&'static str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(SET_COOKIE, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn test_cookie_jar() {
+        let jar = CookieJar::default();
+        let url = Url::parse("http://example.com/login").unwrap();
+        jar.store(&url, &set_cookies(&["a=1; Path=/; HttpOnly", "b=\"2\"", "invalid"]));
+
+        assert_eq!(jar.header_value(&url).as_deref(), Some("a=1; b=2"));
+        assert_eq!(jar.header_value(&Url::parse("http://other.com").unwrap()), None);
+
+        jar.store(&url, &set_cookies(&["a=3", "b=; Max-Age=0"]));
+        assert_eq!(jar.header_value(&url).as_deref(), Some("a=3"));
+    }
+
+    #[test]
+    fn test_cookie_jar_secure() {
+        let jar = CookieJar::default();
+        let url = Url::parse("https://example.com").unwrap();
+        jar.store(&url, &set_cookies(&["a=1; Secure", "b=2"]));
+
+        assert_eq!(jar.header_value(&url).as_deref(), Some("a=1; b=2"));
+        assert_eq!(
+            jar.header_value(&Url::parse("http://example.com").unwrap()).as_deref(),
+            Some("b=2")
+        );
+    }
+
+    #[test]
+    fn test_cookie_jar_domain() {
+        let jar = CookieJar::default();
+        let url = Url::parse("http://www.example.com").unwrap();
+        jar.store(
+            &url,
+            &set_cookies(&[
+                "a=1; Domain=.Example.com",
+                "b=2",
+                "c=3; Domain=other.com",
+                "d=4; Domain=ple.com",
+            ]),
+        );
+
+        assert_eq!(jar.header_value(&url).as_deref(), Some("a=1; b=2"));
+        assert_eq!(
+            jar.header_value(&Url::parse("http://api.example.com").unwrap())
+                .as_deref(),
+            Some("a=1")
+        );
+        assert_eq!(jar.header_value(&Url::parse("http://other.com").unwrap()), None);
+        assert_eq!(jar.header_value(&Url::parse("http://anexample.com").unwrap()), None);
+    }
+
+    #[test]
+    fn test_cookie_jar_public_suffix() {
+        let jar = CookieJar::default();
+        let url = Url::parse("http://evil.co.uk").unwrap();
+        jar.store(&url, &set_cookies(&["a=1; Domain=co.uk", "b=2; Domain=.UK"]));
+        assert_eq!(jar.header_value(&url), None);
+        assert_eq!(jar.header_value(&Url::parse("http://victim.co.uk").unwrap()), None);
+
+        let url = Url::parse("http://localhost").unwrap();
+        jar.store(&url, &set_cookies(&["c=3; Domain=localhost"]));
+        assert_eq!(jar.header_value(&url).as_deref(), Some("c=3"));
+        assert_eq!(jar.header_value(&Url::parse("http://sub.localhost").unwrap()), None);
+
+        let url = Url::parse("http://site.github.io").unwrap();
+        jar.store(
+            &url,
+            &set_cookies(&["d=4; Domain=github.io", "e=5; Domain=site.github.io"]),
+        );
+        assert_eq!(jar.header_value(&url).as_deref(), Some("e=5"));
+        assert_eq!(jar.header_value(&Url::parse("http://other.github.io").unwrap()), None);
+    }
+
+    #[test]
+    fn test_cookie_jar_path() {
+        let jar = CookieJar::default();
+        let url = Url::parse("http://example.com/docs/index.html").unwrap();
+        jar.store(&url, &set_cookies(&["a=1", "b=2; Path=/docs/api", "c=3; Path=/"]));
+
+        assert_eq!(jar.header_value(&url).as_deref(), Some("a=1; c=3"));
+        assert_eq!(
+            jar.header_value(&Url::parse("http://example.com/docs/api/v1").unwrap())
+                .as_deref(),
+            Some("b=2; a=1; c=3")
+        );
+        assert_eq!(
+            jar.header_value(&Url::parse("http://example.com/docsets").unwrap())
+                .as_deref(),
+            Some("c=3")
+        );
+    }
+
+    #[test]
+    fn test_cookie_jar_expiry() {
+        let jar = CookieJar::default();
+        let url = Url::parse("http://example.com").unwrap();
+        jar.store(
+            &url,
+            &set_cookies(&[
+                "a=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+                "b=2; Expires=Fri, 01 Jan 9999 00:00:00 GMT",
+                "c=3; Max-Age=3600; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+                "d=4; Max-Age=-1",
+            ]),
+        );
+        assert_eq!(jar.header_value(&url).as_deref(), Some("b=2; c=3"));
+
+        jar.store(&url, &set_cookies(&["b=; Expires=Thu, 01 Jan 1970 00:00:00 GMT"]));
+        assert_eq!(jar.header_value(&url).as_deref(), Some("c=3"));
+    }
+
+    #[test]
+    fn test_parse_cookie_date() {
+        let date = UNIX_EPOCH + Duration::from_secs(1_445_412_480);
+        assert_eq!(parse_cookie_date("Wed, 21 Oct 2015 07:28:00 GMT"), Some(date));
+        assert_eq!(parse_cookie_date("Wednesday, 21-Oct-15 07:28:00 GMT"), Some(date));
+        assert_eq!(parse_cookie_date("Wed Oct 21 07:28:00 2015"), Some(date));
+        assert_eq!(parse_cookie_date("Wed, 32 Oct 2015 07:28:00 GMT"), None);
+        assert_eq!(parse_cookie_date("tomorrow"), None);
+    }
+}
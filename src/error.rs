@@ -23,6 +23,22 @@ pub enum InvalidResponseKind {
     Chunk,
     /// Invalid Content-Length header
     ContentLength,
+    /// Invalid or truncated multipart body
+    Multipart,
+    /// The body does not match the checksum from the headers
+    Checksum,
+    /// The headers are larger than the limit or there are too many of them
+    HeadersTooLarge,
+    /// A line of a line-based body is longer than the limit
+    LineTooLong,
+    /// Invalid URL in a Link header
+    LinkUrl,
+    /// Transfer-Encoding header which does not end with a single chunked coding
+    TransferEncoding,
+    /// A part of a multipart body is larger than the limit
+    PartTooLarge,
+    /// A partial response with a Content-Encoding, its range does not match the decoded body
+    EncodedRange,
 }
 
 impl Display for InvalidResponseKind {
@@ -38,6 +54,14 @@ impl Display for InvalidResponseKind {
             ChunkSize => write!(f, "invalid chunk size"),
             Chunk => write!(f, "invalid chunk"),
             ContentLength => write!(f, "invalid content length"),
+            Multipart => write!(f, "invalid multipart body"),
+            Checksum => write!(f, "checksum mismatch"),
+            HeadersTooLarge => write!(f, "headers too large"),
+            LineTooLong => write!(f, "line too long"),
+            LinkUrl => write!(f, "invalid link url"),
+            TransferEncoding => write!(f, "invalid transfer encoding"),
+            PartTooLarge => write!(f, "multipart part too large"),
+            EncodedRange => write!(f, "partial content with a content encoding"),
         }
     }
 }
@@ -73,6 +97,12 @@ pub enum ErrorKind {
     /// JSON decoding/encoding error.
     #[cfg(feature = "json")]
     Json(serde_json::Error),
+    /// XML decoding error.
+    #[cfg(feature = "xml")]
+    XmlDe(quick_xml::DeError),
+    /// XML encoding error.
+    #[cfg(feature = "xml")]
+    XmlSe(quick_xml::SeError),
     /// Form-URL encoding error.
     #[cfg(feature = "form")]
     UrlEncoded(serde_urlencoded::ser::Error),
@@ -89,6 +119,36 @@ pub enum ErrorKind {
     /// WebPKI error.
     #[cfg(feature = "tls-rustls")]
     WebPKI(webpki::Error),
+    /// The request body did not have the length it announced.
+    BodyLengthMismatch {
+        /// Length announced by the body.
+        expected: u64,
+        /// Number of bytes produced by the body. When the body is too long, it is not read past the first extra byte.
+        actual: u64,
+    },
+    /// The request was cancelled using its `CancelToken`.
+    Cancelled,
+    /// The response body is not valid text in the charset used to decode it.
+    Decoding {
+        /// Name of the charset used to decode the body.
+        charset: &'static str,
+        /// Offset in the body of the first byte which could not be decoded.
+        offset: u64,
+    },
+    /// The host or the address of the request is not allowed by the host filter.
+    HostBlocked(String),
+    /// The label given to `charsets::from_label` is not a known charset.
+    InvalidCharset(String),
+    /// The request body contains a character which cannot be represented in the charset used to encode it.
+    Encoding {
+        /// Name of the charset used to encode the body.
+        charset: &'static str,
+        /// The first character which could not be encoded.
+        character: char,
+    },
+    /// The local port set with `local_port` is already used by another socket.
+    #[cfg(feature = "socket2")]
+    LocalPortInUse(u16),
 }
 
 /// A type that contains all the errors that can possibly occur while accessing an HTTP server.
@@ -124,6 +184,10 @@ impl Display for Error {
             StatusCode(ref sc) => write!(w, "Status code {} indicates failure", sc),
             #[cfg(feature = "json")]
             Json(ref e) => write!(w, "Json Error: {}", e),
+            #[cfg(feature = "xml")]
+            XmlDe(ref e) => write!(w, "Xml Decoding Error: {}", e),
+            #[cfg(feature = "xml")]
+            XmlSe(ref e) => write!(w, "Xml Encoding Error: {}", e),
             #[cfg(feature = "form")]
             UrlEncoded(ref e) => write!(w, "URL Encoding Error: {}", e),
             #[cfg(feature = "tls")]
@@ -134,6 +198,27 @@ impl Display for Error {
             TlsDisabled => write!(w, "TLS is disabled, activate tls or tls-rustls feature"),
             #[cfg(feature = "tls-rustls")]
             WebPKI(ref e) => write!(w, "WebPKI error: {}", e),
+            BodyLengthMismatch { expected, actual } if actual > expected => {
+                write!(
+                    w,
+                    "Request body is longer than its announced length of {} bytes",
+                    expected
+                )
+            }
+            BodyLengthMismatch { expected, actual } => write!(
+                w,
+                "Request body is {} bytes long instead of its announced length of {} bytes",
+                actual, expected
+            ),
+            Cancelled => write!(w, "Request cancelled"),
+            Decoding { charset, offset } => {
+                write!(w, "Invalid {} text at byte {} of the response body", charset, offset)
+            }
+            HostBlocked(ref host) => write!(w, "Connection to {} is blocked", host),
+            InvalidCharset(ref label) => write!(w, "Unknown charset: {}", label),
+            Encoding { charset, character } => write!(w, "Cannot encode {:?} in {}", character, charset),
+            #[cfg(feature = "socket2")]
+            LocalPortInUse(port) => write!(w, "Local port {} is already in use", port),
         }
     }
 }
@@ -147,6 +232,10 @@ impl StdError for Error {
             Http(ref e) => Some(e),
             #[cfg(feature = "json")]
             Json(ref e) => Some(e),
+            #[cfg(feature = "xml")]
+            XmlDe(ref e) => Some(e),
+            #[cfg(feature = "xml")]
+            XmlSe(ref e) => Some(e),
             #[cfg(feature = "tls")]
             Tls(ref e) => Some(e),
             #[cfg(feature = "tls-rustls")]
@@ -168,6 +257,18 @@ impl From<io::Error> for Error {
     }
 }
 
+impl Error {
+    /// Convert an I/O error, getting back the `Error` it is wrapping if there is one.
+    ///
+    /// Request bodies can only return I/O errors, they use this to surface more specific errors.
+    pub(crate) fn from_io(err: io::Error) -> Error {
+        match err.downcast::<Error>() {
+            Ok(err) => err,
+            Err(err) => err.into(),
+        }
+    }
+}
+
 impl From<http::Error> for Error {
     fn from(err: http::Error) -> Error {
         Error(Box::new(ErrorKind::Http(err)))
@@ -180,6 +281,12 @@ impl From<http::header::InvalidHeaderValue> for Error {
     }
 }
 
+impl From<http::method::InvalidMethod> for Error {
+    fn from(err: http::method::InvalidMethod) -> Error {
+        Error(Box::new(ErrorKind::Http(http::Error::from(err))))
+    }
+}
+
 #[cfg(feature = "tls")]
 impl From<native_tls::Error> for Error {
     fn from(err: native_tls::Error) -> Error {
@@ -201,6 +308,20 @@ impl From<serde_json::Error> for Error {
     }
 }
 
+#[cfg(feature = "xml")]
+impl From<quick_xml::DeError> for Error {
+    fn from(err: quick_xml::DeError) -> Error {
+        Error(Box::new(ErrorKind::XmlDe(err)))
+    }
+}
+
+#[cfg(feature = "xml")]
+impl From<quick_xml::SeError> for Error {
+    fn from(err: quick_xml::SeError) -> Error {
+        Error(Box::new(ErrorKind::XmlSe(err)))
+    }
+}
+
 #[cfg(feature = "form")]
 impl From<serde_urlencoded::ser::Error> for Error {
     fn from(err: serde_urlencoded::ser::Error) -> Error {
@@ -222,13 +343,13 @@ impl From<InvalidResponseKind> for Error {
 
 impl From<Error> for io::Error {
     fn from(err: Error) -> io::Error {
-        io::Error::new(io::ErrorKind::Other, err)
+        io::Error::other(err)
     }
 }
 
 impl From<InvalidResponseKind> for io::Error {
     fn from(kind: InvalidResponseKind) -> io::Error {
-        io::Error::new(io::ErrorKind::Other, Error(Box::new(ErrorKind::InvalidResponse(kind))))
+        io::Error::other(Error(Box::new(ErrorKind::InvalidResponse(kind))))
     }
 }
 
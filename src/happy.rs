@@ -1,29 +1,50 @@
 use std::io;
 use std::iter::{self, FusedIterator};
-use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+#[cfg(feature = "socket2")]
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::sync::mpsc::channel;
 use std::thread;
 use std::time::{Duration, Instant};
 
+#[cfg(feature = "socket2")]
+use socket2::{Domain, Protocol, Socket, Type};
 use url::Host;
 
+#[cfg(feature = "socket2")]
+use crate::request::SocketCallback;
+
 const RACE_DELAY: Duration = Duration::from_millis(200);
 
+/// How the sockets are set up before they connect.
+#[derive(Clone, Default)]
+pub struct SocketOptions {
+    /// Local port the sockets are bound to.
+    #[cfg(feature = "socket2")]
+    pub local_port: Option<u16>,
+    /// Callback configuring the sockets.
+    #[cfg(feature = "socket2")]
+    pub configure: Option<SocketCallback>,
+}
+
+/// Resolve the addresses of a host.
+pub fn resolve(host: &Host<&str>, port: u16) -> io::Result<Vec<SocketAddr>> {
+    match *host {
+        Host::Domain(domain) => Ok((domain, port).to_socket_addrs()?.collect()),
+        Host::Ipv4(ip) => Ok(vec![(IpAddr::V4(ip), port).into()]),
+        Host::Ipv6(ip) => Ok(vec![(IpAddr::V6(ip), port).into()]),
+    }
+}
+
 /// This function implements a basic form of the happy eyeballs RFC to quickly connect
 /// to a domain which is available in both IPv4 and IPv6. Connection attempts are raced
 /// against each other and the first to connect successfully wins the race.
 ///
 /// If the timeout is not provided, a default timeout of 10 seconds is used.
-pub fn connect(host: &Host<&str>, port: u16, timeout: Duration) -> io::Result<TcpStream> {
-    let addrs: Vec<_> = match *host {
-        Host::Domain(domain) => (domain, port).to_socket_addrs()?.collect(),
-        Host::Ipv4(ip) => return TcpStream::connect_timeout(&(IpAddr::V4(ip), port).into(), timeout),
-        Host::Ipv6(ip) => return TcpStream::connect_timeout(&(IpAddr::V6(ip), port).into(), timeout),
-    };
-
-    if let [addr] = &addrs[..] {
+pub fn connect(addrs: &[SocketAddr], options: &SocketOptions, timeout: Duration) -> io::Result<TcpStream> {
+    if let [addr] = addrs {
         debug!("DNS returned only one address, using fast path");
-        return TcpStream::connect_timeout(addr, timeout);
+        return connect_from(addr, options, timeout);
     }
 
     let ipv4 = addrs.iter().filter(|a| a.is_ipv4());
@@ -60,11 +81,12 @@ pub fn connect(host: &Host<&str>, port: u16, timeout: Duration) -> io::Result<Tc
     // connection attempt is successful.
     for &addr in sorted {
         let tx = tx.clone();
+        let options = options.clone();
 
         thread::spawn(move || {
             debug!("trying to connect to {}", addr);
 
-            let _ = tx.send((addr, TcpStream::connect_timeout(&addr, timeout)));
+            let _ = tx.send((addr, connect_from(&addr, &options, timeout)));
         });
 
         if let Ok((addr, res)) = rx.recv_timeout(RACE_DELAY) {
@@ -92,7 +114,37 @@ pub fn connect(host: &Host<&str>, port: u16, timeout: Duration) -> io::Result<Tc
         start.elapsed().as_millis()
     );
 
-    Err(first_err.unwrap_or_else(|| io::Error::new(io::ErrorKind::Other, "no DNS entries found")))
+    Err(first_err.unwrap_or_else(|| io::Error::other("no DNS entries found")))
+}
+
+/// Connect to an address, from the given local port if there is one, after configuring the socket.
+#[cfg(feature = "socket2")]
+fn connect_from(addr: &SocketAddr, options: &SocketOptions, timeout: Duration) -> io::Result<TcpStream> {
+    if options.local_port.is_none() && options.configure.is_none() {
+        return TcpStream::connect_timeout(addr, timeout);
+    }
+    let socket = Socket::new(Domain::for_address(*addr), Type::STREAM, Some(Protocol::TCP))?;
+    // The callback runs first, so that it can set options which only apply before binding.
+    if let Some(configure) = &options.configure {
+        configure(&socket)?;
+    }
+    if let Some(port) = options.local_port {
+        let local_addr = match addr {
+            SocketAddr::V4(_) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port),
+            SocketAddr::V6(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), port),
+        };
+        // The port of a previous connection stays in TIME_WAIT for a while after it is closed.
+        socket.set_reuse_address(true)?;
+        socket.bind(&local_addr.into())?;
+    }
+    socket.connect_timeout(&(*addr).into(), timeout)?;
+    Ok(socket.into())
+}
+
+/// Connect to an address.
+#[cfg(not(feature = "socket2"))]
+fn connect_from(addr: &SocketAddr, _: &SocketOptions, timeout: Duration) -> io::Result<TcpStream> {
+    TcpStream::connect_timeout(addr, timeout)
 }
 
 fn intertwine<T, A, B>(mut ita: A, mut itb: B) -> impl Iterator<Item = T>
@@ -1,6 +1,7 @@
 #![deny(missing_debug_implementations)]
 #![deny(missing_docs)]
 #![allow(clippy::needless_doctest_main)]
+#![cfg_attr(test, allow(clippy::bool_assert_comparison, clippy::legacy_numeric_constants))]
 //! This project's goal is to provide a lightweight and simple HTTP client for the Rust ecosystem. The intended use is for
 //! projects that have HTTP needs where performance is not critical or when HTTP is not the main purpose of the application.
 //! Note that the project still tries to perform well and avoid allocation where possible, but stays away from Rust's
@@ -39,12 +40,19 @@
 //!
 //! # Features
 //! * `charsets` support for decoding more text encodings than just UTF-8
+//! * `xml-charset` support for decoding XML bodies in the encoding of their XML declaration, implies `charsets`
+//! * `checksum` support for verifying response bodies against `Content-MD5` and `Digest` headers
+//! * `hash` support for hashing response bodies with any `Digest` implementation while they are read
 //! * `compress` support for decompressing response bodies (**default**)
 //! * `json` support for serialization and deserialization
+//! * `xml` support for XML serialization and deserialization
 //! * `form` support for url encoded forms (does not include support for multipart)
 //! * `tls` support for tls connections (**default**)
 //! * `tls-rustls` support for TLS connections using `rustls` instead of `native-tls`
 //! * `multipart-form` support for multipart forms (does not include support for url encoding)
+//! * `mime` support for guessing the `Content-Type` of files uploaded from a path
+//! * `socket2` support for binding connections to a local port and configuring their sockets
+//! * `no-std-core` exposes the `proto` layer, which serializes requests with only `core` and `alloc`
 //!
 //! # Activating a feature
 //! To activate a feature, specify it in your `Cargo.toml` file like so
@@ -61,27 +69,61 @@ macro_rules! warn {
     ($($arg:tt)+) => { log::warn!(target: "attohttpc", $($arg)+) };
 }
 
+extern crate alloc;
+
+mod cancel;
 #[cfg(feature = "charsets")]
 pub mod charsets;
+mod cookies;
 mod error;
 mod happy;
+mod metrics;
 #[cfg(feature = "multipart")]
 mod multipart;
 mod parsing;
+mod pool;
+#[cfg(feature = "no-std-core")]
+pub mod proto;
+#[cfg(not(feature = "no-std-core"))]
+#[allow(dead_code)]
+mod proto;
+mod rate_limit;
 mod request;
 mod streams;
 mod tls;
 
+pub use crate::cancel::CancelToken;
 pub use crate::error::{Error, ErrorKind, InvalidResponseKind, Result};
+pub use crate::metrics::Metrics;
 #[cfg(feature = "multipart")]
 pub use crate::multipart::{Multipart, MultipartBuilder, MultipartFile};
-pub use crate::parsing::{Response, ResponseReader};
+pub use crate::parsing::{
+    AcceptRanges, AuthChallenge, ContentRange, InformationalResponse, LinkValue, MultipartPart, MultipartPartReader,
+    MultipartReader, RedirectHop, Response, ResponseReader,
+};
+#[cfg(feature = "checksum")]
+pub use crate::parsing::{ChecksumAlgorithm, ChecksumReader};
+#[cfg(feature = "hash")]
+pub use crate::parsing::{DigestHandle, HashingReader};
 pub use crate::request::proxy::{ProxySettings, ProxySettingsBuilder};
-pub use crate::request::{body, PreparedRequest, RequestBuilder, RequestInspector, Session};
+pub use crate::request::{
+    body, Middleware, Pages, PreparedRequest, RequestBuilder, RequestInspector, RequestTarget, Session,
+};
+pub use crate::tls::{PeerCertificate, TlsVersion};
 #[cfg(feature = "charsets")]
-pub use crate::{charsets::Charset, parsing::TextReader};
+pub use crate::{
+    charsets::Charset,
+    parsing::{EncodingWriter, TextReader},
+};
 pub use http::Method;
 pub use http::StatusCode;
+pub use http::Version;
+/// The socket given to `configure_socket` callbacks.
+///
+/// This is a re-export of the `socket2` crate's `Socket`, the version of `socket2` attohttpc depends on is part of its
+/// public API when the `socket2` feature is enabled.
+#[cfg(feature = "socket2")]
+pub use socket2::Socket;
 
 pub mod header {
     //! This module is a re-export of the `http` crate's `header` module.
@@ -152,6 +194,30 @@ where
     RequestBuilder::new(Method::TRACE, base_url)
 }
 
+/// Download the resource at the given URL to the given path, resuming a previous partial download.
+///
+/// This is a shortcut for `get(url).download_resumable(path)`, see
+/// [`RequestBuilder::download_resumable`](crate::RequestBuilder::download_resumable).
+pub fn download_resumable<U, P>(url: U, path: P) -> Result<u64>
+where
+    U: AsRef<str>,
+    P: AsRef<std::path::Path>,
+{
+    get(url).download_resumable(path)
+}
+
+/// Download the resource at the given URL to the given path in several parts fetched concurrently.
+///
+/// This is a shortcut for `get(url).download_parallel(path, parts)`, see
+/// [`RequestBuilder::download_parallel`](crate::RequestBuilder::download_parallel).
+pub fn download_parallel<U, P>(url: U, path: P, parts: usize) -> Result<u64>
+where
+    U: AsRef<str>,
+    P: AsRef<std::path::Path>,
+{
+    get(url).download_parallel(path, parts)
+}
+
 mod skip_debug {
     use std::fmt;
 
@@ -0,0 +1,143 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Phases {
+    resolved: Option<Instant>,
+    connected: Option<Instant>,
+    tls_done: Option<Instant>,
+    first_byte: Option<Instant>,
+    body_done: Option<Instant>,
+}
+
+/// Collects the byte counts and timestamps of a connection as it is used.
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    start: Instant,
+    sent: AtomicU64,
+    received: AtomicU64,
+    phases: Mutex<Phases>,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder {
+            start: Instant::now(),
+            sent: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            phases: Mutex::new(Phases::default()),
+        }
+    }
+
+    fn record(&self, phase: impl FnOnce(&mut Phases) -> &mut Option<Instant>) {
+        if let Ok(mut phases) = self.phases.lock() {
+            phase(&mut phases).get_or_insert_with(Instant::now);
+        }
+    }
+
+    pub fn resolved(&self) {
+        self.record(|p| &mut p.resolved);
+    }
+
+    pub fn connected(&self) {
+        self.record(|p| &mut p.connected);
+    }
+
+    pub fn tls_done(&self) {
+        self.record(|p| &mut p.tls_done);
+    }
+
+    pub fn body_done(&self) {
+        self.record(|p| &mut p.body_done);
+    }
+
+    pub fn add_sent(&self, n: usize) {
+        self.sent.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_received(&self, n: usize) {
+        let previous = self.received.fetch_add(n as u64, Ordering::Relaxed);
+        if previous == 0 && n > 0 {
+            self.record(|p| &mut p.first_byte);
+        }
+    }
+
+    pub fn metrics(&self) -> Metrics {
+        let since_start = |instant: Option<Instant>| instant.map(|instant| instant - self.start);
+        let phases = self.phases.lock().map(|p| *p).unwrap_or_default();
+
+        Metrics {
+            bytes_sent: self.sent.load(Ordering::Relaxed),
+            bytes_received: self.received.load(Ordering::Relaxed),
+            dns: since_start(phases.resolved),
+            connect: since_start(phases.connected),
+            tls: since_start(phases.tls_done),
+            ttfb: since_start(phases.first_byte),
+            total: since_start(phases.body_done),
+        }
+    }
+}
+
+/// Byte counts and timings of the connection used by a response.
+///
+/// Byte counts include the request and response heads. Over TLS, they count the bytes before encryption.
+/// Durations are measured from the moment the connection started being opened, like curl's `time_*`
+/// variables, so they are ordered: `dns <= connect <= tls <= ttfb <= total`. A phase which has not happened,
+/// such as TLS over a plain connection, is `None`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Metrics {
+    bytes_sent: u64,
+    bytes_received: u64,
+    dns: Option<Duration>,
+    connect: Option<Duration>,
+    tls: Option<Duration>,
+    ttfb: Option<Duration>,
+    total: Option<Duration>,
+}
+
+impl Metrics {
+    /// Get the number of bytes written to the connection.
+    #[inline]
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    /// Get the number of bytes read from the connection so far.
+    #[inline]
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    /// Get the time taken to resolve the host name.
+    #[inline]
+    pub fn dns(&self) -> Option<Duration> {
+        self.dns
+    }
+
+    /// Get the time taken until the TCP connection was established.
+    #[inline]
+    pub fn connect(&self) -> Option<Duration> {
+        self.connect
+    }
+
+    /// Get the time taken until the TLS handshake was completed.
+    #[inline]
+    pub fn tls(&self) -> Option<Duration> {
+        self.tls
+    }
+
+    /// Get the time taken until the first byte of the response was received.
+    #[inline]
+    pub fn ttfb(&self) -> Option<Duration> {
+        self.ttfb
+    }
+
+    /// Get the time taken until the whole body was read.
+    ///
+    /// It is `None` until the end of the body has been reached.
+    #[inline]
+    pub fn total(&self) -> Option<Duration> {
+        self.total
+    }
+}
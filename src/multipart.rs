@@ -110,6 +110,10 @@ impl Body for Multipart<'_> {
     fn content_type(&mut self) -> IoResult<Option<String>> {
         Ok(Some(format!("multipart/form-data; boundary={}", self.data.boundary())))
     }
+
+    fn is_replayable(&self) -> bool {
+        false
+    }
 }
 
 impl fmt::Debug for Multipart<'_> {
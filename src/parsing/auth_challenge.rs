@@ -0,0 +1,278 @@
+use std::str;
+
+use http::header::{HeaderMap, WWW_AUTHENTICATE};
+
+/// `AuthChallenge` is an authentication challenge sent by the server in the `WWW-Authenticate` header.
+///
+/// It is made of a scheme, such as `Basic` or `Digest`, followed by either parameters or a single
+/// token. See [`Response::www_authenticate`](crate::Response::www_authenticate).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthChallenge {
+    scheme: String,
+    params: Vec<(String, String)>,
+    token68: Option<String>,
+}
+
+impl AuthChallenge {
+    /// Get the authentication scheme of this challenge, as sent by the server.
+    ///
+    /// Schemes are case-insensitive, compare them with `eq_ignore_ascii_case`.
+    #[inline]
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// Get the parameters of this challenge in order, quoted values are unescaped.
+    #[inline]
+    pub fn params(&self) -> &[(String, String)] {
+        &self.params
+    }
+
+    /// Get the value of a parameter of this challenge, the name is case-insensitive.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Get the token sent instead of parameters by some schemes, such as `Negotiate`.
+    #[inline]
+    pub fn token68(&self) -> Option<&str> {
+        self.token68.as_deref()
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+fn is_tchar(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c)
+}
+
+fn is_token68_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"-._~+/".contains(&c)
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn is_done(&self) -> bool {
+        self.pos >= self.input.len()
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(b' ') | Some(b'\t') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    /// Skip whitespace and the commas separating list elements.
+    fn skip_separators(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b',') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn take_while(&mut self, pred: impl Fn(u8) -> bool) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(&pred) {
+            self.pos += 1;
+        }
+        // Only ASCII characters are accepted by the predicates.
+        str::from_utf8(&self.input[start..self.pos]).unwrap_or_default()
+    }
+
+    fn token(&mut self) -> Option<String> {
+        match self.take_while(is_tchar) {
+            "" => None,
+            token => Some(token.to_string()),
+        }
+    }
+
+    fn token68(&mut self) -> Option<String> {
+        let start = self.pos;
+        if self.take_while(is_token68_char).is_empty() {
+            return None;
+        }
+        self.take_while(|c| c == b'=');
+        // Only ASCII characters are accepted by the predicates.
+        str::from_utf8(&self.input[start..self.pos]).ok().map(String::from)
+    }
+
+    fn quoted_string(&mut self) -> Option<String> {
+        if self.peek() != Some(b'"') {
+            return None;
+        }
+        self.pos += 1;
+
+        let mut value = Vec::new();
+        loop {
+            match self.peek()? {
+                b'"' => {
+                    self.pos += 1;
+                    return String::from_utf8(value).ok();
+                }
+                b'\\' => {
+                    value.push(*self.input.get(self.pos + 1)?);
+                    self.pos += 2;
+                }
+                c => {
+                    value.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    /// Parse an `auth-param`, returns `None` without consuming anything if there is none.
+    fn param(&mut self) -> Option<(String, String)> {
+        let start = self.pos;
+        let param = (|| {
+            self.skip_separators();
+            let name = self.token()?;
+            self.skip_ws();
+            if self.peek() != Some(b'=') {
+                return None;
+            }
+            self.pos += 1;
+            self.skip_ws();
+            let value = match self.peek() {
+                Some(b'"') => self.quoted_string()?,
+                _ => self.token()?,
+            };
+            Some((name, value))
+        })();
+        if param.is_none() {
+            self.pos = start;
+        }
+        param
+    }
+
+    /// Parse a `token68` if it is the only thing following the scheme of a challenge.
+    fn challenge_token68(&mut self) -> Option<String> {
+        let start = self.pos;
+        if let Some(token68) = self.token68() {
+            self.skip_ws();
+            if self.is_done() || self.peek() == Some(b',') {
+                return Some(token68);
+            }
+        }
+        self.pos = start;
+        None
+    }
+
+    fn challenge(&mut self, first: bool) -> Option<AuthChallenge> {
+        self.skip_ws();
+        if !first && self.peek() != Some(b',') {
+            return None;
+        }
+        self.skip_separators();
+        let scheme = self.token()?;
+        self.skip_ws();
+
+        let mut challenge = AuthChallenge {
+            scheme,
+            params: Vec::new(),
+            token68: None,
+        };
+        match self.challenge_token68() {
+            Some(token68) => challenge.token68 = Some(token68),
+            // A token which is not followed by `=` starts the next challenge.
+            None => {
+                while let Some(param) = self.param() {
+                    challenge.params.push(param);
+                }
+            }
+        }
+        Some(challenge)
+    }
+}
+
+/// Parse the challenges of the `WWW-Authenticate` headers, invalid syntax ends the parsing of a header.
+pub fn parse_www_authenticate(headers: &HeaderMap) -> Vec<AuthChallenge> {
+    let mut challenges = Vec::new();
+    for value in headers.get_all(WWW_AUTHENTICATE) {
+        let mut parser = Parser {
+            input: value.as_bytes(),
+            pos: 0,
+        };
+        while let Some(challenge) = parser.challenge(parser.pos == 0) {
+            challenges.push(challenge);
+        }
+    }
+    challenges
+}
+
+#[cfg(test)]
+fn parse(value: &'static str) -> Vec<AuthChallenge> {
+    let mut headers = HeaderMap::new();
+    headers.insert(WWW_AUTHENTICATE, http::HeaderValue::from_static(value));
+    parse_www_authenticate(&headers)
+}
+
+#[test]
+fn test_parse_basic() {
+    let challenges = parse("Basic realm=\"Protected area\", charset=UTF-8");
+    assert_eq!(challenges.len(), 1);
+    assert_eq!(challenges[0].scheme(), "Basic");
+    assert_eq!(challenges[0].param("realm"), Some("Protected area"));
+    assert_eq!(challenges[0].param("Charset"), Some("UTF-8"));
+    assert_eq!(challenges[0].token68(), None);
+}
+
+#[test]
+fn test_parse_digest() {
+    let challenges = parse(
+        "Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", algorithm=SHA-256, \
+         nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"",
+    );
+    assert_eq!(challenges.len(), 1);
+    let digest = &challenges[0];
+    assert_eq!(digest.scheme(), "Digest");
+    assert_eq!(digest.params().len(), 5);
+    assert_eq!(digest.param("realm"), Some("http-auth@example.org"));
+    assert_eq!(digest.param("qop"), Some("auth, auth-int"));
+    assert_eq!(digest.param("algorithm"), Some("SHA-256"));
+    assert_eq!(
+        digest.param("nonce"),
+        Some("7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v")
+    );
+    assert_eq!(digest.param("stale"), None);
+}
+
+#[test]
+fn test_parse_multiple_challenges() {
+    let challenges = parse("Newauth realm=\"apps\", type=1, title=\"Login to \\\"apps\\\"\", Basic realm=\"simple\", Negotiate a87421000492aa874209af8bc028==");
+    let schemes: Vec<_> = challenges.iter().map(AuthChallenge::scheme).collect();
+    assert_eq!(schemes, ["Newauth", "Basic", "Negotiate"]);
+    assert_eq!(challenges[0].param("title"), Some("Login to \"apps\""));
+    assert_eq!(challenges[0].param("type"), Some("1"));
+    assert_eq!(challenges[1].params(), [("realm".to_string(), "simple".to_string())]);
+    assert_eq!(challenges[2].token68(), Some("a87421000492aa874209af8bc028=="));
+}
+
+#[test]
+fn test_parse_several_headers() {
+    let mut headers = HeaderMap::new();
+    headers.append(WWW_AUTHENTICATE, http::HeaderValue::from_static("Bearer"));
+    headers.append(WWW_AUTHENTICATE, http::HeaderValue::from_static("Basic realm=x"));
+    let challenges = parse_www_authenticate(&headers);
+    assert_eq!(challenges.len(), 2);
+    assert_eq!(challenges[0].scheme(), "Bearer");
+    assert!(challenges[0].params().is_empty());
+    assert_eq!(challenges[1].param("realm"), Some("x"));
+}
+
+#[test]
+fn test_parse_invalid() {
+    assert!(parse("").is_empty());
+    assert!(parse("\"quoted\"").is_empty());
+    let challenges = parse("Basic realm=\"unterminated");
+    assert_eq!(challenges.len(), 1);
+    assert!(challenges[0].params().is_empty());
+}
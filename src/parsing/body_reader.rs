@@ -1,4 +1,6 @@
 use std::io::{self, BufRead, BufReader, Read, Take};
+use std::mem;
+use std::time::Duration;
 
 use http::header::{HeaderMap, HeaderValue, CONTENT_LENGTH, TRANSFER_ENCODING};
 
@@ -11,26 +13,43 @@ pub enum BodyReader {
     Chunked(ChunkedReader<BaseStream>),
     Length(Take<BufReader<BaseStream>>),
     Close(BufReader<BaseStream>),
+    // The body has been read entirely and the stream has been released, with the trailers of a chunked body.
+    Done(Option<HeaderMap>),
 }
 
 impl Read for BodyReader {
     #[inline]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        match self {
+        let res = match self {
             BodyReader::Chunked(r) => r.read(buf),
             BodyReader::Length(r) => r.read(buf),
             BodyReader::Close(r) => r.read(buf),
-        }
+            BodyReader::Done(_) => Ok(0),
+        };
+        self.after_read(res)
     }
 }
 
 impl BufRead for BodyReader {
     #[inline]
     fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        // The buffer is filled first to find out if the end of the body was reached, in which
+        // case the stream can be released before returning.
+        let res = match self {
+            BodyReader::Chunked(r) => r.fill_buf().map(|buf| buf.len()),
+            BodyReader::Length(r) => r.fill_buf().map(|buf| buf.len()),
+            BodyReader::Close(r) => r.fill_buf().map(|buf| buf.len()),
+            BodyReader::Done(_) => Ok(0),
+        };
+        if self.after_read(res)? == 0 {
+            return Ok(&[]);
+        }
+
         match self {
             BodyReader::Chunked(r) => r.fill_buf(),
             BodyReader::Length(r) => r.fill_buf(),
             BodyReader::Close(r) => r.fill_buf(),
+            BodyReader::Done(_) => Ok(&[]),
         }
     }
 
@@ -40,25 +59,43 @@ impl BufRead for BodyReader {
             BodyReader::Chunked(r) => r.consume(amt),
             BodyReader::Length(r) => r.consume(amt),
             BodyReader::Close(r) => r.consume(amt),
+            BodyReader::Done(_) => (),
         }
+        self.release_if_done();
     }
 }
 
-fn is_chunked(headers: &HeaderMap) -> bool {
-    headers
-        .get_all(TRANSFER_ENCODING)
-        .into_iter()
-        .filter_map(|val| val.to_str().ok())
-        .any(|val| {
-            val.split(',')
-                .map(|s| s.trim())
-                .any(|s| s.eq_ignore_ascii_case("chunked"))
-        })
+/// How the body of a response is framed according to its `Transfer-Encoding` headers.
+#[derive(Debug, PartialEq)]
+enum TransferCoding {
+    /// There is no `Transfer-Encoding` header.
+    Absent,
+    /// The body is chunked, `chunked` is the final coding and is only applied once.
+    Chunked,
+    /// The codings do not end with a single `chunked`, the body then lasts until the connection is closed.
+    Unframed,
+}
+
+fn transfer_coding(headers: &HeaderMap) -> TransferCoding {
+    let mut codings = Vec::new();
+    for value in headers.get_all(TRANSFER_ENCODING) {
+        let value = match value.to_str() {
+            Ok(value) => value,
+            Err(_) => return TransferCoding::Unframed,
+        };
+        codings.extend(value.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()));
+    }
+    let chunked = codings.iter().filter(|s| s.eq_ignore_ascii_case("chunked")).count();
+    match codings.last() {
+        None => TransferCoding::Absent,
+        Some(last) if chunked == 1 && last.eq_ignore_ascii_case("chunked") => TransferCoding::Chunked,
+        Some(_) => TransferCoding::Unframed,
+    }
 }
 
 fn parse_content_length(val: &HeaderValue) -> Result<u64> {
     let val = val.to_str().map_err(|_| InvalidResponseKind::ContentLength)?;
-    let val: u64 = u64::from_str_radix(val, 10).map_err(|_| InvalidResponseKind::ContentLength)?;
+    let val: u64 = val.parse::<u64>().map_err(|_| InvalidResponseKind::ContentLength)?;
     Ok(val)
 }
 
@@ -77,40 +114,180 @@ fn is_content_length(headers: &HeaderMap) -> Result<Option<u64>> {
     Ok(last)
 }
 
+/// Return the stream to its pool, unless the server sent more bytes than the response contained.
+fn release(reader: BufReader<BaseStream>) {
+    if reader.buffer().is_empty() {
+        reader.into_inner().release();
+    }
+}
+
 impl BodyReader {
-    pub fn new(headers: &HeaderMap, reader: BufReader<BaseStream>) -> Result<BodyReader> {
-        if is_chunked(headers) {
-            debug!("creating a chunked body reader");
-            Ok(BodyReader::Chunked(ChunkedReader::new(reader)))
-        } else if let Some(val) = is_content_length(headers)? {
-            debug!("creating a length body reader");
-            Ok(BodyReader::Length(reader.take(val)))
-        } else {
-            debug!("creating close reader");
-            Ok(BodyReader::Close(reader))
+    /// Create a reader for the body of a response.
+    ///
+    /// If `has_body` is false, like for responses to `HEAD` requests, the body is empty whatever the headers say.
+    /// A chunked body with a `Content-Length` header is rejected if `strict` is true, otherwise the length is
+    /// ignored and the stream is not reused. Likewise, `Transfer-Encoding` headers which do not end with a single
+    /// `chunked` coding are rejected if `strict` is true, otherwise the body is read until the connection is
+    /// closed, as the specification requires.
+    pub fn new(
+        headers: &HeaderMap,
+        has_body: bool,
+        strict: bool,
+        mut reader: BufReader<BaseStream>,
+    ) -> Result<BodyReader> {
+        if !has_body {
+            debug!("response has no body");
+            release(reader);
+            return Ok(BodyReader::Done(None));
+        }
+
+        match transfer_coding(headers) {
+            TransferCoding::Chunked => {
+                if headers.contains_key(CONTENT_LENGTH) {
+                    if strict {
+                        return Err(InvalidResponseKind::ContentLength.into());
+                    }
+                    warn!("response has both a content length and chunked encoding, closing connection after it");
+                    reader.get_mut().disable_reuse();
+                }
+                debug!("creating a chunked body reader");
+                Ok(BodyReader::Chunked(ChunkedReader::new(reader)))
+            }
+            TransferCoding::Unframed => {
+                if strict {
+                    return Err(InvalidResponseKind::TransferEncoding.into());
+                }
+                warn!("response transfer encoding does not end with chunked, reading until the connection closes");
+                reader.get_mut().disable_reuse();
+                Ok(BodyReader::Close(reader))
+            }
+            TransferCoding::Absent => match is_content_length(headers)? {
+                Some(val) => {
+                    debug!("creating a length body reader");
+                    Ok(BodyReader::Length(reader.take(val)))
+                }
+                None => {
+                    debug!("creating close reader");
+                    Ok(BodyReader::Close(reader))
+                }
+            },
+        }
+    }
+
+    /// Set the read timeout of the stream the body is read from, if it has not been released yet.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            BodyReader::Chunked(r) => r.get_mut().get_mut().set_read_timeout(timeout),
+            BodyReader::Length(r) => r.get_mut().get_mut().set_read_timeout(timeout),
+            BodyReader::Close(r) => r.get_mut().set_read_timeout(timeout),
+            BodyReader::Done(_) => Ok(()),
+        }
+    }
+
+    /// Get the trailers of a chunked body, once its end has been reached.
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        match self {
+            BodyReader::Done(trailers) => trailers.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Read and discard the rest of the body, so that the stream can be returned to its pool.
+    ///
+    /// If more than `limit` bytes remain or if the stream cannot be reused anyway, it is closed instead.
+    pub fn drain(&mut self, limit: u64) -> io::Result<()> {
+        let reusable = match self {
+            BodyReader::Chunked(r) => r.get_mut().get_ref().is_pooled(),
+            // The remaining length is known, there is no need to read it to know it's too long.
+            BodyReader::Length(r) => r.get_ref().get_ref().is_pooled() && r.limit() <= limit,
+            BodyReader::Close(_) | BodyReader::Done(_) => false,
+        };
+        if !reusable {
+            *self = BodyReader::Done(None);
+            return Ok(());
+        }
+
+        let mut drained = 0;
+        loop {
+            let len = self.fill_buf()?.len();
+            if len == 0 {
+                return Ok(());
+            }
+            drained += len as u64;
+            if drained > limit {
+                debug!("closing connection instead of draining a large body");
+                *self = BodyReader::Done(None);
+                return Ok(());
+            }
+            self.consume(len);
+        }
+    }
+
+    fn after_read(&mut self, res: io::Result<usize>) -> io::Result<usize> {
+        match &res {
+            Ok(_) => self.release_if_done(),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            // The position in the stream is unknown after an error, it cannot be used for another request.
+            Err(_) => match self {
+                BodyReader::Chunked(r) => r.get_mut().get_mut().disable_reuse(),
+                BodyReader::Length(r) => r.get_mut().get_mut().disable_reuse(),
+                BodyReader::Close(r) => r.get_mut().disable_reuse(),
+                BodyReader::Done(_) => (),
+            },
+        }
+        res
+    }
+
+    fn release_if_done(&mut self) {
+        let done = match self {
+            BodyReader::Chunked(r) => r.is_done(),
+            BodyReader::Length(r) => r.limit() == 0,
+            BodyReader::Close(_) | BodyReader::Done(_) => false,
+        };
+        if done {
+            match mem::replace(self, BodyReader::Done(None)) {
+                BodyReader::Chunked(mut r) => {
+                    *self = BodyReader::Done(r.take_trailers());
+                    release(r.into_inner());
+                }
+                BodyReader::Length(r) => release(r.into_inner()),
+                BodyReader::Close(_) | BodyReader::Done(_) => (),
+            }
         }
     }
 }
 
+#[cfg(test)]
+fn coding_of(values: &[&'static str]) -> TransferCoding {
+    let mut headers = HeaderMap::new();
+    for value in values {
+        headers.append(TRANSFER_ENCODING, HeaderValue::from_static(value));
+    }
+    transfer_coding(&headers)
+}
+
 #[test]
-fn test_is_chunked_false() {
+fn test_transfer_coding_absent() {
     let mut headers = HeaderMap::new();
     headers.insert("content-encoding", HeaderValue::from_static("gzip"));
-    assert!(!is_chunked(&headers));
+    assert_eq!(transfer_coding(&headers), TransferCoding::Absent);
 }
 
 #[test]
-fn test_is_chunked_simple() {
-    let mut headers = HeaderMap::new();
-    headers.insert("transfer-encoding", HeaderValue::from_static("chunked"));
-    assert!(is_chunked(&headers));
+fn test_transfer_coding_chunked() {
+    assert_eq!(coding_of(&["chunked"]), TransferCoding::Chunked);
+    assert_eq!(coding_of(&["Chunked"]), TransferCoding::Chunked);
+    assert_eq!(coding_of(&["gzip, chunked"]), TransferCoding::Chunked);
+    assert_eq!(coding_of(&["gzip", "chunked"]), TransferCoding::Chunked);
 }
 
 #[test]
-fn test_is_chunked_multi() {
-    let mut headers = HeaderMap::new();
-    headers.insert("transfer-encoding", HeaderValue::from_static("gzip, chunked"));
-    assert!(is_chunked(&headers));
+fn test_transfer_coding_unframed() {
+    assert_eq!(coding_of(&["gzip"]), TransferCoding::Unframed);
+    assert_eq!(coding_of(&["chunked, gzip"]), TransferCoding::Unframed);
+    assert_eq!(coding_of(&["chunked", "gzip"]), TransferCoding::Unframed);
+    assert_eq!(coding_of(&["chunked, chunked"]), TransferCoding::Unframed);
+    assert_eq!(coding_of(&["chunked", "chunked"]), TransferCoding::Unframed);
 }
 
 #[test]
@@ -155,3 +332,59 @@ fn test_is_content_length_many_err() {
     assert_eq!(headers.get_all("content-length").iter().count(), 2);
     assert!(is_content_length(&headers).is_err());
 }
+
+#[cfg(test)]
+fn body_reader(response_headers: &[(&'static str, &'static str)], body: &[u8], strict: bool) -> Result<BodyReader> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in response_headers {
+        headers.append(*name, HeaderValue::from_static(value));
+    }
+    BodyReader::new(&headers, true, strict, BufReader::new(BaseStream::mock(body.to_vec())))
+}
+
+#[test]
+fn test_conflicting_content_length() {
+    let headers = [("content-length", "5"), ("content-length", "7")];
+    assert!(body_reader(&headers, b"hello", false).is_err());
+}
+
+#[test]
+fn test_chunked_with_content_length() {
+    let headers = [("transfer-encoding", "chunked"), ("content-length", "3")];
+    let mut reader = body_reader(&headers, b"5\r\nhello\r\n0\r\n\r\n", false).unwrap();
+    let mut body = String::new();
+    reader.read_to_string(&mut body).unwrap();
+    assert_eq!(body, "hello");
+
+    let err = body_reader(&headers, b"5\r\nhello\r\n0\r\n\r\n", true).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        crate::ErrorKind::InvalidResponse(InvalidResponseKind::ContentLength)
+    ));
+}
+
+#[test]
+fn test_unframed_transfer_encoding() {
+    let bodies: &[&[(&'static str, &'static str)]] = &[
+        &[("transfer-encoding", "gzip")],
+        &[("transfer-encoding", "chunked, identity")],
+        &[("transfer-encoding", "chunked"), ("transfer-encoding", "chunked")],
+        &[("transfer-encoding", "identity"), ("content-length", "2")],
+    ];
+    for headers in bodies {
+        let mut reader = body_reader(headers, b"5\r\nhello\r\n0\r\n\r\n", false).unwrap();
+        let mut body = String::new();
+        reader.read_to_string(&mut body).unwrap();
+        assert_eq!(body, "5\r\nhello\r\n0\r\n\r\n", "{:?}", headers);
+
+        let err = body_reader(headers, b"5\r\nhello\r\n0\r\n\r\n", true).unwrap_err();
+        assert!(
+            matches!(
+                err.kind(),
+                crate::ErrorKind::InvalidResponse(InvalidResponseKind::TransferEncoding)
+            ),
+            "{:?}",
+            headers
+        );
+    }
+}
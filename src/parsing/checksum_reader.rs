@@ -0,0 +1,266 @@
+use std::fmt;
+use std::io::{self, Read};
+use std::mem;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use http::header::{HeaderMap, HeaderName};
+use md5::Md5;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::error::{InvalidResponseKind, Result};
+use crate::parsing::ResponseReader;
+
+/// Hash algorithm used to verify a response body.
+///
+/// Algorithms are ordered from the weakest to the strongest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChecksumAlgorithm {
+    /// MD5, from the `Content-MD5` header or the `MD5` digest.
+    Md5,
+    /// SHA-256, from the `SHA-256` digest.
+    Sha256,
+    /// SHA-512, from the `SHA-512` digest.
+    Sha512,
+}
+
+impl ChecksumAlgorithm {
+    fn from_name(name: &str) -> Option<ChecksumAlgorithm> {
+        match name.to_ascii_lowercase().as_str() {
+            "md5" => Some(ChecksumAlgorithm::Md5),
+            "sha-256" => Some(ChecksumAlgorithm::Sha256),
+            "sha-512" => Some(ChecksumAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    fn hasher(self) -> Hasher {
+        match self {
+            ChecksumAlgorithm::Md5 => Hasher::Md5(Md5::new()),
+            ChecksumAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            ChecksumAlgorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+        }
+    }
+}
+
+enum Hasher {
+    Md5(Md5),
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(h) => h.update(data),
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Sha512(h) => h.update(data),
+        }
+    }
+
+    fn finalize(self) -> Vec<u8> {
+        match self {
+            Hasher::Md5(h) => h.finalize().to_vec(),
+            Hasher::Sha256(h) => h.finalize().to_vec(),
+            Hasher::Sha512(h) => h.finalize().to_vec(),
+        }
+    }
+}
+
+impl fmt::Debug for Hasher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Hasher")
+    }
+}
+
+/// Find the checksum announced by the headers.
+///
+/// The strongest supported algorithm of the `Digest` header is preferred, `Content-MD5` is used otherwise.
+/// `Ok(None)` is returned if there is no checksum, and an error if the checksum is malformed.
+fn get_expected_checksum(headers: &HeaderMap) -> Result<Option<(ChecksumAlgorithm, Vec<u8>)>> {
+    let mut best: Option<(ChecksumAlgorithm, &str)> = None;
+
+    for value in headers.get_all(HeaderName::from_static("digest")) {
+        let value = value.to_str().map_err(|_| InvalidResponseKind::Header)?;
+        for (name, digest) in value.split(',').filter_map(|item| item.split_once('=')) {
+            if let Some(algorithm) = ChecksumAlgorithm::from_name(name.trim()) {
+                if best.map_or(true, |(best, _)| algorithm > best) {
+                    best = Some((algorithm, digest.trim()));
+                }
+            }
+        }
+    }
+
+    if best.is_none() {
+        if let Some(value) = headers.get(HeaderName::from_static("content-md5")) {
+            let value = value.to_str().map_err(|_| InvalidResponseKind::Header)?;
+            best = Some((ChecksumAlgorithm::Md5, value.trim()));
+        }
+    }
+
+    match best {
+        Some((algorithm, digest)) => {
+            let digest = base64::decode(digest).map_err(|_| InvalidResponseKind::Header)?;
+            Ok(Some((algorithm, digest)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// `ChecksumReader` verifies the body it reads against the checksum announced by the response headers.
+///
+/// The body is hashed as it is read. Once the end of the body is reached, an error of kind
+/// [`InvalidResponseKind::Checksum`] is returned if the computed digest does not match.
+/// If the response has no checksum, the body is passed through unchanged.
+#[derive(Debug)]
+pub struct ChecksumReader<R> {
+    inner: R,
+    algorithm: Option<ChecksumAlgorithm>,
+    expected: Vec<u8>,
+    hasher: Option<Arc<Mutex<Hasher>>>,
+    // The hasher is fed by the inner reader with the body before its content codings are decoded.
+    raw: bool,
+    digest: Option<Vec<u8>>,
+}
+
+impl<R> ChecksumReader<R>
+where
+    R: Read,
+{
+    pub(crate) fn new(inner: R, headers: &HeaderMap) -> Result<ChecksumReader<R>> {
+        let (algorithm, expected) = match get_expected_checksum(headers)? {
+            Some((algorithm, expected)) => (Some(algorithm), expected),
+            None => (None, Vec::new()),
+        };
+
+        Ok(ChecksumReader {
+            inner,
+            algorithm,
+            expected,
+            hasher: algorithm.map(|algorithm| Arc::new(Mutex::new(algorithm.hasher()))),
+            raw: false,
+            digest: None,
+        })
+    }
+
+    /// Get the algorithm used to verify the body, if the response has a checksum.
+    #[inline]
+    pub fn algorithm(&self) -> Option<ChecksumAlgorithm> {
+        self.algorithm
+    }
+
+    /// Get the digest computed over the body.
+    ///
+    /// It is only available once the whole body has been read, and only if the response has a checksum.
+    #[inline]
+    pub fn digest(&self) -> Option<&[u8]> {
+        self.digest.as_deref()
+    }
+}
+
+impl ChecksumReader<ResponseReader> {
+    /// Verify the body of a response as it was received, the checksum covers its content codings.
+    pub(crate) fn for_response(inner: ResponseReader, headers: &HeaderMap) -> Result<Self> {
+        let mut reader = ChecksumReader::new(inner, headers)?;
+        if let Some(hasher) = &reader.hasher {
+            let hasher = hasher.clone();
+            reader.inner.set_raw_tap(Box::new(move |bytes| {
+                hasher.lock().unwrap_or_else(PoisonError::into_inner).update(bytes);
+            }));
+            reader.raw = true;
+        }
+        Ok(reader)
+    }
+}
+
+impl<R> Read for ChecksumReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+
+        if let (Some(hasher), Some(algorithm)) = (&self.hasher, self.algorithm) {
+            let mut hasher = hasher.lock().unwrap_or_else(PoisonError::into_inner);
+            if !self.raw {
+                hasher.update(&buf[..n]);
+            }
+
+            if n == 0 && !buf.is_empty() {
+                let digest = mem::replace(&mut *hasher, algorithm.hasher()).finalize();
+                drop(hasher);
+                self.hasher = None;
+                let matches = digest == self.expected;
+                self.digest = Some(digest);
+                if !matches {
+                    return Err(InvalidResponseKind::Checksum.into());
+                }
+            }
+        }
+
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use http::header::{HeaderMap, HeaderValue};
+
+    use super::{ChecksumAlgorithm, ChecksumReader};
+
+    // Digests of "hello".
+    const MD5: &str = "XUFAKrxLKna5cZ2REBfFkg==";
+    const SHA256: &str = "LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=";
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn read_all(reader: &mut ChecksumReader<&[u8]>) -> std::io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    #[test]
+    fn test_content_md5() {
+        let mut reader = ChecksumReader::new(&b"hello"[..], &headers(&[("content-md5", MD5)])).unwrap();
+        assert_eq!(read_all(&mut reader).unwrap(), b"hello");
+        assert_eq!(reader.algorithm(), Some(ChecksumAlgorithm::Md5));
+        assert_eq!(reader.digest(), Some(&base64::decode(MD5).unwrap()[..]));
+    }
+
+    #[test]
+    fn test_digest_prefers_strongest() {
+        let digest = format!("MD5=AAAAAAAAAAAAAAAAAAAAAA==, SHA-256={}, UNKNOWN=abc", SHA256);
+        let headers = headers(&[("digest", &digest), ("content-md5", "AAAAAAAAAAAAAAAAAAAAAA==")]);
+        let mut reader = ChecksumReader::new(&b"hello"[..], &headers).unwrap();
+        assert_eq!(read_all(&mut reader).unwrap(), b"hello");
+        assert_eq!(reader.algorithm(), Some(ChecksumAlgorithm::Sha256));
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let mut reader = ChecksumReader::new(&b"hellO"[..], &headers(&[("content-md5", MD5)])).unwrap();
+        assert!(read_all(&mut reader).is_err());
+        assert_ne!(reader.digest(), Some(&base64::decode(MD5).unwrap()[..]));
+    }
+
+    #[test]
+    fn test_no_checksum() {
+        let mut reader = ChecksumReader::new(&b"hello"[..], &HeaderMap::new()).unwrap();
+        assert_eq!(read_all(&mut reader).unwrap(), b"hello");
+        assert_eq!(reader.algorithm(), None);
+        assert_eq!(reader.digest(), None);
+    }
+
+    #[test]
+    fn test_invalid_checksum_header() {
+        assert!(ChecksumReader::new(&b"hello"[..], &headers(&[("content-md5", "not base64!")])).is_err());
+    }
+}
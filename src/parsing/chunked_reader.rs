@@ -2,8 +2,11 @@ use std::cmp;
 use std::io::{self, BufRead, BufReader, Read};
 use std::str;
 
-use crate::error::InvalidResponseKind;
+use http::HeaderMap;
+
+use crate::error::{Error, ErrorKind, InvalidResponseKind};
 use crate::parsing::buffers;
+use crate::parsing::response::{parse_headers, DEFAULT_MAX_HEADER_SIZE};
 
 fn parse_chunk_size(line: &[u8]) -> io::Result<usize> {
     line.iter()
@@ -24,6 +27,7 @@ where
     consumed: usize,  // bytes consumed from `buffer`
     remaining: usize, // bytes remaining until next chunk
     reached_eof: bool,
+    trailers: Option<HeaderMap>,
 }
 
 impl<R> ChunkedReader<R>
@@ -37,6 +41,7 @@ where
             consumed: 0,
             remaining: 0,
             reached_eof: false,
+            trailers: None,
         }
     }
 
@@ -47,6 +52,36 @@ where
         }
         parse_chunk_size(&self.buffer)
     }
+
+    /// Read the trailer fields which follow the last chunk, up to the empty line ending the body.
+    fn read_trailers(&mut self) -> io::Result<HeaderMap> {
+        let mut trailers = HeaderMap::new();
+        parse_headers(&mut self.inner, &mut trailers, DEFAULT_MAX_HEADER_SIZE).map_err(|err| {
+            match err.into_kind() {
+                ErrorKind::Io(err) => err,
+                kind => Error::from(kind).into(),
+            }
+        })?;
+        Ok(trailers)
+    }
+
+    /// Take the trailers sent after the last chunk, they are available once the end of the body is reached.
+    pub fn take_trailers(&mut self) -> Option<HeaderMap> {
+        self.trailers.take()
+    }
+
+    /// Check if the terminating chunk has been read and every byte of the body has been consumed.
+    pub fn is_done(&self) -> bool {
+        self.reached_eof && self.remaining == 0 && self.consumed == self.buffer.len()
+    }
+
+    pub fn get_mut(&mut self) -> &mut BufReader<R> {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> BufReader<R> {
+        self.inner
+    }
 }
 
 impl<R> BufRead for ChunkedReader<R>
@@ -61,6 +96,10 @@ where
                 self.remaining = self.read_chunk_size()?;
                 if self.remaining == 0 {
                     self.reached_eof = true;
+                    self.buffer.clear();
+                    self.consumed = 0;
+                    self.trailers = Some(self.read_trailers()?);
+                    return Ok(&[]);
                 }
             }
 
@@ -154,3 +193,24 @@ fn test_read_invalid_bad_terminating_chunk() {
         io::ErrorKind::UnexpectedEof
     );
 }
+
+#[test]
+fn test_read_trailers() {
+    let msg = b"4\r\nwiki\r\n0\r\nX-Checksum: abc\r\nX-Other: 1\r\n\r\n";
+    let mut reader = ChunkedReader::new(BufReader::new(&msg[..]));
+    let mut s = String::new();
+    reader.read_to_string(&mut s).unwrap();
+    assert_eq!(s, "wiki");
+
+    let trailers = reader.take_trailers().unwrap();
+    assert_eq!(trailers["x-checksum"], "abc");
+    assert_eq!(trailers["x-other"], "1");
+}
+
+#[test]
+fn test_read_invalid_trailer() {
+    let msg = b"4\r\nwiki\r\n0\r\nnot a header\r\n\r\n";
+    let mut reader = ChunkedReader::new(BufReader::new(&msg[..]));
+    let mut s = String::new();
+    assert!(reader.read_to_string(&mut s).is_err());
+}
@@ -1,7 +1,11 @@
-use std::io::{self, Read};
+use std::io::{self, BufRead, Read};
+use std::time::Duration;
 
 #[cfg(feature = "compress")]
-use flate2::bufread::{DeflateDecoder, GzDecoder};
+use flate2::{
+    bufread::{DeflateDecoder, GzDecoder},
+    read,
+};
 use http::header::HeaderMap;
 #[cfg(feature = "compress")]
 use http::header::{CONTENT_ENCODING, TRANSFER_ENCODING};
@@ -11,78 +15,286 @@ use http::Method;
 use crate::error::Result;
 use crate::parsing::body_reader::BodyReader;
 use crate::request::PreparedRequest;
+use crate::skip_debug::SkipDebug;
 
+/// Callback receiving the raw bytes of the body as the decoders consume them.
+pub type RawTap = Box<dyn FnMut(&[u8]) + Send>;
+
+/// The body as received, before its content codings are decoded.
+///
+/// The bytes consumed while recording are kept, so that a tap set afterwards still sees every byte of the body.
+/// Decoders consume the header of the body as soon as they are created.
 #[derive(Debug)]
-pub enum CompressedReader {
-    Plain(BodyReader),
-    #[cfg(feature = "compress")]
-    Deflate(DeflateDecoder<BodyReader>),
+pub struct RawBody {
+    body: BodyReader,
+    recording: bool,
+    recorded: Vec<u8>,
+    tap: Option<SkipDebug<RawTap>>,
+}
+
+impl RawBody {
+    fn new(body: BodyReader) -> RawBody {
+        RawBody {
+            body,
+            recording: false,
+            recorded: Vec::new(),
+            tap: None,
+        }
+    }
+
+    fn observe(&mut self, bytes: &[u8]) {
+        if let Some(tap) = &mut self.tap {
+            (tap.0)(bytes);
+        } else if self.recording {
+            self.recorded.extend_from_slice(bytes);
+        }
+    }
+
     #[cfg(feature = "compress")]
-    Gzip(GzDecoder<BodyReader>),
+    fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+    }
+
+    #[cfg(feature = "checksum")]
+    fn set_tap(&mut self, mut tap: RawTap) {
+        tap(&std::mem::take(&mut self.recorded));
+        self.tap = Some(SkipDebug(tap));
+    }
+
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        self.body.trailers()
+    }
+
+    pub fn drain(&mut self, limit: u64) -> io::Result<()> {
+        self.body.drain(limit)
+    }
+
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.body.set_read_timeout(timeout)
+    }
 }
 
-#[cfg(feature = "compress")]
-fn have_encoding_item(value: &str, enc: &str) -> bool {
-    value.split(',').map(|s| s.trim()).any(|s| s.eq_ignore_ascii_case(enc))
+impl Read for RawBody {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.body.read(buf)?;
+        self.observe(&buf[..n]);
+        Ok(n)
+    }
 }
 
-#[cfg(feature = "compress")]
-fn have_encoding_content_encoding(headers: &HeaderMap, enc: &str) -> bool {
-    headers
-        .get_all(CONTENT_ENCODING)
-        .into_iter()
-        .filter_map(|val| val.to_str().ok())
-        .any(|val| have_encoding_item(val, enc))
+impl BufRead for RawBody {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.body.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if amt > 0 && (self.tap.is_some() || self.recording) {
+            // The bytes were returned by the last call to `fill_buf`, they are still buffered.
+            if let Ok(buf) = self.body.fill_buf() {
+                let buf = buf[..amt.min(buf.len())].to_vec();
+                self.observe(&buf);
+            }
+        }
+        self.body.consume(amt)
+    }
+}
+
+#[derive(Debug)]
+pub enum CompressedReader {
+    Plain(RawBody),
+    #[cfg(feature = "compress")]
+    Deflate(DeflateDecoder<RawBody>),
+    #[cfg(feature = "compress")]
+    Gzip(GzDecoder<RawBody>),
+    // Encodings applied on top of others, the inner reader decodes the encodings applied after this one.
+    #[cfg(feature = "compress")]
+    DeflateChained(Box<read::DeflateDecoder<CompressedReader>>),
+    #[cfg(feature = "compress")]
+    GzipChained(Box<read::GzDecoder<CompressedReader>>),
 }
 
 #[cfg(feature = "compress")]
-fn have_encoding_transfer_encoding(headers: &HeaderMap, enc: &str) -> bool {
-    headers
-        .get_all(TRANSFER_ENCODING)
-        .into_iter()
-        .filter_map(|val| val.to_str().ok())
-        .any(|val| have_encoding_item(val, enc))
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Coding {
+    Deflate,
+    Gzip,
+    Unknown,
 }
 
+/// Get the codings of the body in the order they were applied, `identity` and `chunked` are left out.
+///
+/// Content codings are applied by the origin server before transfer codings, so they come first.
 #[cfg(feature = "compress")]
-fn have_encoding(headers: &HeaderMap, enc: &str) -> bool {
-    have_encoding_content_encoding(headers, enc) || have_encoding_transfer_encoding(headers, enc)
+fn codings(headers: &HeaderMap) -> Vec<Coding> {
+    headers
+        .get_all(CONTENT_ENCODING)
+        .iter()
+        .chain(headers.get_all(TRANSFER_ENCODING).iter())
+        .flat_map(|val| val.to_str().unwrap_or("unknown").split(','))
+        .map(str::trim)
+        .filter(|s| !s.is_empty() && !s.eq_ignore_ascii_case("identity") && !s.eq_ignore_ascii_case("chunked"))
+        .map(|s| {
+            if s.eq_ignore_ascii_case("gzip") || s.eq_ignore_ascii_case("x-gzip") {
+                Coding::Gzip
+            } else if s.eq_ignore_ascii_case("deflate") {
+                Coding::Deflate
+            } else {
+                Coding::Unknown
+            }
+        })
+        .collect()
 }
 
 impl CompressedReader {
+    /// Create the decoders of the body, decoding its codings in the reverse of the order they were applied.
+    ///
+    /// Decoding stops at the first unknown coding, the rest of the body is returned as is.
     #[cfg(feature = "compress")]
     pub fn new<B>(headers: &HeaderMap, request: &PreparedRequest<B>, reader: BodyReader) -> Result<CompressedReader> {
-        if request.method() != Method::HEAD {
-            if have_encoding(headers, "gzip") {
-                debug!("creating gzip decoder");
-                return Ok(CompressedReader::Gzip(GzDecoder::new(reader)));
-            }
+        let mut reader = CompressedReader::plain(reader);
+        if request.method() == Method::HEAD {
+            debug!("creating plain reader");
+            return Ok(reader);
+        }
 
-            if have_encoding(headers, "deflate") {
-                debug!("creating deflate decoder");
-                return Ok(CompressedReader::Deflate(DeflateDecoder::new(reader)));
-            }
+        reader.raw_mut().set_recording(true);
+        let mut reader = CompressedReader::decoders(headers, reader);
+        reader.raw_mut().set_recording(false);
+        Ok(reader)
+    }
+
+    #[cfg(feature = "compress")]
+    fn decoders(headers: &HeaderMap, mut reader: CompressedReader) -> CompressedReader {
+        for coding in codings(headers).into_iter().rev() {
+            reader = match (coding, reader) {
+                (Coding::Gzip, CompressedReader::Plain(body)) => {
+                    debug!("creating gzip decoder");
+                    CompressedReader::Gzip(GzDecoder::new(body))
+                }
+                (Coding::Deflate, CompressedReader::Plain(body)) => {
+                    debug!("creating deflate decoder");
+                    CompressedReader::Deflate(DeflateDecoder::new(body))
+                }
+                (Coding::Gzip, inner) => {
+                    debug!("chaining gzip decoder");
+                    CompressedReader::GzipChained(Box::new(read::GzDecoder::new(inner)))
+                }
+                (Coding::Deflate, inner) => {
+                    debug!("chaining deflate decoder");
+                    CompressedReader::DeflateChained(Box::new(read::DeflateDecoder::new(inner)))
+                }
+                (Coding::Unknown, inner) => {
+                    debug!("unknown encoding, not decoding further");
+                    return inner;
+                }
+            };
         }
-        debug!("creating plain reader");
-        Ok(CompressedReader::Plain(reader))
+        reader
     }
 
     #[cfg(not(feature = "compress"))]
     pub fn new<B>(_: &HeaderMap, _: &PreparedRequest<B>, reader: BodyReader) -> Result<CompressedReader> {
-        Ok(CompressedReader::Plain(reader))
+        Ok(CompressedReader::plain(reader))
+    }
+
+    /// Create a reader of a body which is not decoded.
+    pub fn plain(reader: BodyReader) -> CompressedReader {
+        CompressedReader::Plain(RawBody::new(reader))
+    }
+
+    /// Get the raw body beneath the decoders.
+    #[cfg(any(feature = "compress", feature = "checksum"))]
+    fn raw_mut(&mut self) -> &mut RawBody {
+        match self {
+            CompressedReader::Plain(s) => s,
+            #[cfg(feature = "compress")]
+            CompressedReader::Deflate(s) => s.get_mut(),
+            #[cfg(feature = "compress")]
+            CompressedReader::Gzip(s) => s.get_mut(),
+            #[cfg(feature = "compress")]
+            CompressedReader::DeflateChained(s) => s.get_mut().raw_mut(),
+            #[cfg(feature = "compress")]
+            CompressedReader::GzipChained(s) => s.get_mut().raw_mut(),
+        }
+    }
+
+    /// Call `tap` with every byte of the body before it is decoded, including the bytes decoded so far.
+    #[cfg(feature = "checksum")]
+    pub fn set_raw_tap(&mut self, tap: RawTap) {
+        self.raw_mut().set_tap(tap);
+    }
+
+    /// Get the trailers of a chunked body, once its end has been reached.
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        match self {
+            CompressedReader::Plain(s) => s.trailers(),
+            #[cfg(feature = "compress")]
+            CompressedReader::Deflate(s) => s.get_ref().trailers(),
+            #[cfg(feature = "compress")]
+            CompressedReader::Gzip(s) => s.get_ref().trailers(),
+            #[cfg(feature = "compress")]
+            CompressedReader::DeflateChained(s) => s.get_ref().trailers(),
+            #[cfg(feature = "compress")]
+            CompressedReader::GzipChained(s) => s.get_ref().trailers(),
+        }
+    }
+
+    /// Discard the rest of the raw body, see `BodyReader::drain`.
+    pub fn drain(&mut self, limit: u64) -> io::Result<()> {
+        match self {
+            CompressedReader::Plain(s) => s.drain(limit),
+            #[cfg(feature = "compress")]
+            CompressedReader::Deflate(s) => s.get_mut().drain(limit),
+            #[cfg(feature = "compress")]
+            CompressedReader::Gzip(s) => s.get_mut().drain(limit),
+            #[cfg(feature = "compress")]
+            CompressedReader::DeflateChained(s) => s.get_mut().drain(limit),
+            #[cfg(feature = "compress")]
+            CompressedReader::GzipChained(s) => s.get_mut().drain(limit),
+        }
+    }
+
+    /// Set the read timeout of the stream the raw body is read from.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            CompressedReader::Plain(s) => s.set_read_timeout(timeout),
+            #[cfg(feature = "compress")]
+            CompressedReader::Deflate(s) => s.get_mut().set_read_timeout(timeout),
+            #[cfg(feature = "compress")]
+            CompressedReader::Gzip(s) => s.get_mut().set_read_timeout(timeout),
+            #[cfg(feature = "compress")]
+            CompressedReader::DeflateChained(s) => s.get_mut().set_read_timeout(timeout),
+            #[cfg(feature = "compress")]
+            CompressedReader::GzipChained(s) => s.get_mut().set_read_timeout(timeout),
+        }
+    }
+}
+
+#[cfg(feature = "compress")]
+fn read_to_body_end<R: Read>(res: io::Result<usize>, body: &mut R, buf: &[u8]) -> io::Result<usize> {
+    if let Ok(0) = res {
+        if !buf.is_empty() {
+            io::copy(body, &mut io::sink())?;
+        }
     }
+    res
 }
 
 impl Read for CompressedReader {
     #[inline]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        // TODO: gzip does not read until EOF, leaving some data in the buffer.
         match self {
             CompressedReader::Plain(s) => s.read(buf),
+            // Decoders stop at the end of the compressed stream, the end of the body is read as well so that
+            // the trailers are parsed and the connection can be reused.
             #[cfg(feature = "compress")]
-            CompressedReader::Deflate(s) => s.read(buf),
+            CompressedReader::Deflate(s) => read_to_body_end(s.read(buf), s.get_mut(), buf),
             #[cfg(feature = "compress")]
-            CompressedReader::Gzip(s) => s.read(buf),
+            CompressedReader::Gzip(s) => read_to_body_end(s.read(buf), s.get_mut(), buf),
+            #[cfg(feature = "compress")]
+            CompressedReader::DeflateChained(s) => read_to_body_end(s.read(buf), s.get_mut(), buf),
+            #[cfg(feature = "compress")]
+            CompressedReader::GzipChained(s) => read_to_body_end(s.read(buf), s.get_mut(), buf),
         }
     }
 }
@@ -101,49 +313,55 @@ mod tests {
     use http::Method;
 
     #[cfg(feature = "compress")]
-    use super::have_encoding;
+    use super::{codings, Coding};
     use crate::parsing::response::parse_response;
     use crate::streams::BaseStream;
     use crate::PreparedRequest;
 
     #[test]
     #[cfg(feature = "compress")]
-    fn test_have_encoding_none() {
+    fn test_codings_none() {
         let mut headers = HeaderMap::new();
-        headers.insert("content-encoding", HeaderValue::from_static("gzip"));
-        assert!(!have_encoding(&headers, "deflate"));
+        assert!(codings(&headers).is_empty());
+        headers.insert("content-encoding", HeaderValue::from_static("identity"));
+        assert!(codings(&headers).is_empty());
     }
 
     #[test]
     #[cfg(feature = "compress")]
-    fn test_have_encoding_content_encoding_simple() {
+    fn test_codings_content_encoding_simple() {
         let mut headers = HeaderMap::new();
         headers.insert("content-encoding", HeaderValue::from_static("gzip"));
-        assert!(have_encoding(&headers, "gzip"));
+        assert_eq!(codings(&headers), [Coding::Gzip]);
     }
 
     #[test]
     #[cfg(feature = "compress")]
-    fn test_have_encoding_content_encoding_multi() {
+    fn test_codings_content_encoding_multi() {
         let mut headers = HeaderMap::new();
         headers.insert("content-encoding", HeaderValue::from_static("identity, deflate"));
-        assert!(have_encoding(&headers, "deflate"));
+        assert_eq!(codings(&headers), [Coding::Deflate]);
+        headers.append("content-encoding", HeaderValue::from_static("X-Gzip, br"));
+        assert_eq!(codings(&headers), [Coding::Deflate, Coding::Gzip, Coding::Unknown]);
     }
 
     #[test]
     #[cfg(feature = "compress")]
-    fn test_have_encoding_transfer_encoding_simple() {
+    fn test_codings_transfer_encoding_simple() {
         let mut headers = HeaderMap::new();
         headers.insert("transfer-encoding", HeaderValue::from_static("deflate"));
-        assert!(have_encoding(&headers, "deflate"));
+        assert_eq!(codings(&headers), [Coding::Deflate]);
     }
 
     #[test]
     #[cfg(feature = "compress")]
-    fn test_have_encoding_transfer_encoding_multi() {
+    fn test_codings_transfer_encoding_multi() {
         let mut headers = HeaderMap::new();
         headers.insert("transfer-encoding", HeaderValue::from_static("gzip, chunked"));
-        assert!(have_encoding(&headers, "gzip"));
+        assert_eq!(codings(&headers), [Coding::Gzip]);
+        // Transfer codings are applied after content codings.
+        headers.insert("content-encoding", HeaderValue::from_static("deflate"));
+        assert_eq!(codings(&headers), [Coding::Deflate, Coding::Gzip]);
     }
 
     #[test]
@@ -208,6 +426,116 @@ mod tests {
         assert_eq!(response.text().unwrap(), "Hello world!!!!!!!!");
     }
 
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_stream_gzip_decoded_larger_than_content_length() {
+        let decoded = "abcd".repeat(16 * 1024);
+        let mut payload = Vec::new();
+        let mut enc = GzEncoder::new(&mut payload, Compression::default());
+        enc.write_all(decoded.as_bytes()).unwrap();
+        enc.finish().unwrap();
+        assert!(payload.len() < decoded.len() / 10);
+
+        let mut buf: Vec<u8> = Vec::new();
+        let _ = write!(
+            buf,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Encoding: gzip\r\n\r\n",
+            payload.len()
+        );
+        buf.extend(payload);
+        // Another response follows on the connection, the body ends at its length.
+        buf.extend_from_slice(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+
+        let req = PreparedRequest::new(Method::GET, "http://google.ca");
+        let sock = BaseStream::mock(buf);
+        let response = parse_response(sock, &req).unwrap();
+
+        assert_eq!(response.text().unwrap(), decoded);
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_stream_chained_encodings() {
+        // Deflate is applied first, then gzip, so gzip is decoded first.
+        let mut deflated = Vec::new();
+        let mut enc = DeflateEncoder::new(&mut deflated, Compression::default());
+        enc.write_all(b"Hello world!!!!!!!!").unwrap();
+        enc.finish().unwrap();
+        let mut payload = Vec::new();
+        let mut enc = GzEncoder::new(&mut payload, Compression::default());
+        enc.write_all(&deflated).unwrap();
+        enc.finish().unwrap();
+
+        let mut buf: Vec<u8> = Vec::new();
+        let _ = write!(
+            buf,
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nContent-Encoding: deflate, identity, gzip\r\n\r\n{:x}\r\n",
+            payload.len()
+        );
+        buf.extend(payload);
+        buf.extend_from_slice(b"\r\n0\r\nExpires: never\r\n\r\n");
+
+        let req = PreparedRequest::new(Method::GET, "http://google.ca");
+        let sock = BaseStream::mock(buf);
+        let mut response = parse_response(sock, &req).unwrap();
+
+        let mut text = String::new();
+        response.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "Hello world!!!!!!!!");
+        // The end of the chunked body is read through both decoders.
+        assert_eq!(response.trailers().unwrap()["expires"], "never");
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_stream_unknown_encoding_not_decoded() {
+        let mut payload = Vec::new();
+        let mut enc = GzEncoder::new(&mut payload, Compression::default());
+        enc.write_all(b"Hello world!!!!!!!!").unwrap();
+        enc.finish().unwrap();
+
+        let mut buf: Vec<u8> = Vec::new();
+        let _ = write!(
+            buf,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Encoding: gzip, unknown\r\n\r\n",
+            payload.len()
+        );
+        buf.extend(payload.iter());
+
+        let req = PreparedRequest::new(Method::GET, "http://google.ca");
+        let sock = BaseStream::mock(buf);
+        let response = parse_response(sock, &req).unwrap();
+
+        // The body cannot be decoded past the unknown encoding, it is returned as received.
+        assert_eq!(response.bytes().unwrap(), payload);
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_stream_gzip_without_accept_encoding() {
+        let mut payload = Vec::new();
+        let mut enc = GzEncoder::new(&mut payload, Compression::default());
+        enc.write_all(b"Hello world!!!!!!!!").unwrap();
+        enc.finish().unwrap();
+
+        let mut buf: Vec<u8> = Vec::new();
+        let _ = write!(
+            buf,
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Encoding: gzip\r\n\r\n",
+            payload.len()
+        );
+        buf.extend(payload);
+
+        // The server compresses the response even though the request did not ask for it.
+        let req = crate::get("http://google.ca").no_auto_accept_encoding().prepare();
+        assert!(!req.headers().contains_key(http::header::ACCEPT_ENCODING));
+
+        let sock = BaseStream::mock(buf);
+        let response = parse_response(sock, &req).unwrap();
+
+        assert_eq!(response.text().unwrap(), "Hello world!!!!!!!!");
+    }
+
     #[test]
     #[cfg(feature = "compress")]
     fn test_no_body_with_gzip() {
@@ -0,0 +1,160 @@
+use std::str;
+
+use http::header::{HeaderMap, ACCEPT_RANGES, CONTENT_RANGE};
+
+/// `ContentRange` describes the part of a resource contained in a partial response.
+///
+/// It is parsed from the `Content-Range` header, see [`Response::content_range`](crate::Response::content_range).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRange {
+    range: Option<(u64, u64)>,
+    complete_length: Option<u64>,
+}
+
+impl ContentRange {
+    /// Get the first and last byte positions contained in the response, both inclusive.
+    ///
+    /// This is `None` when the server reports that the requested range could not be satisfied.
+    #[inline]
+    pub fn range(&self) -> Option<(u64, u64)> {
+        self.range
+    }
+
+    /// Get the size of the whole resource, if it is known by the server.
+    #[inline]
+    pub fn complete_length(&self) -> Option<u64> {
+        self.complete_length
+    }
+
+    fn parse(value: &[u8]) -> Option<ContentRange> {
+        let value = str::from_utf8(value).ok()?.trim();
+        let (unit, rest) = value.split_once(' ')?;
+        if !unit.eq_ignore_ascii_case("bytes") {
+            return None;
+        }
+
+        let (range, complete_length) = rest.trim().split_once('/')?;
+        let complete_length = match complete_length {
+            "*" => None,
+            len => Some(len.parse().ok()?),
+        };
+        let range = match range {
+            "*" => None,
+            range => {
+                let (first, last) = range.split_once('-')?;
+                let (first, last): (u64, u64) = (first.parse().ok()?, last.parse().ok()?);
+                if last < first || complete_length.is_some_and(|len| last >= len) {
+                    return None;
+                }
+                Some((first, last))
+            }
+        };
+
+        // `bytes */*` carries no information.
+        if range.is_none() && complete_length.is_none() {
+            return None;
+        }
+
+        Some(ContentRange { range, complete_length })
+    }
+}
+
+pub fn parse_content_range(headers: &HeaderMap) -> Option<ContentRange> {
+    headers
+        .get(CONTENT_RANGE)
+        .and_then(|value| ContentRange::parse(value.as_bytes()))
+}
+
+/// `AcceptRanges` describes the range requests supported by the server for a resource.
+///
+/// It is parsed from the `Accept-Ranges` header, see [`Response::accept_ranges`](crate::Response::accept_ranges).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AcceptRanges {
+    /// The header is missing or is `none`, range requests might not be supported.
+    None,
+    /// Byte ranges are supported, they can be requested with [`RequestBuilder::range`](crate::RequestBuilder::range).
+    Bytes,
+    /// Ranges are supported in units other than bytes, listed as sent by the server.
+    Other(String),
+}
+
+pub fn parse_accept_ranges(headers: &HeaderMap) -> AcceptRanges {
+    let units: Vec<&str> = headers
+        .get_all(ACCEPT_RANGES)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|unit| !unit.is_empty() && !unit.eq_ignore_ascii_case("none"))
+        .collect();
+
+    if units.iter().any(|unit| unit.eq_ignore_ascii_case("bytes")) {
+        AcceptRanges::Bytes
+    } else if units.is_empty() {
+        AcceptRanges::None
+    } else {
+        AcceptRanges::Other(units.join(", "))
+    }
+}
+
+#[test]
+fn test_parse_content_range() {
+    assert_eq!(
+        ContentRange::parse(b"bytes 0-499/1234"),
+        Some(ContentRange {
+            range: Some((0, 499)),
+            complete_length: Some(1234),
+        })
+    );
+}
+
+#[test]
+fn test_parse_content_range_unknown_length() {
+    assert_eq!(
+        ContentRange::parse(b"bytes 500-999/*"),
+        Some(ContentRange {
+            range: Some((500, 999)),
+            complete_length: None,
+        })
+    );
+}
+
+#[test]
+fn test_parse_content_range_unsatisfied() {
+    assert_eq!(
+        ContentRange::parse(b"bytes */1234"),
+        Some(ContentRange {
+            range: None,
+            complete_length: Some(1234),
+        })
+    );
+}
+
+#[test]
+fn test_parse_content_range_invalid() {
+    assert_eq!(ContentRange::parse(b"bytes */*"), None);
+    assert_eq!(ContentRange::parse(b"bytes 10-5/100"), None);
+    assert_eq!(ContentRange::parse(b"bytes 0-100/100"), None);
+    assert_eq!(ContentRange::parse(b"items 0-5/10"), None);
+    assert_eq!(ContentRange::parse(b"bytes 0-x/10"), None);
+}
+
+#[test]
+fn test_parse_accept_ranges() {
+    use http::header::HeaderValue;
+
+    let mut headers = HeaderMap::new();
+    assert_eq!(parse_accept_ranges(&headers), AcceptRanges::None);
+
+    headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    assert_eq!(parse_accept_ranges(&headers), AcceptRanges::Bytes);
+
+    headers.insert(ACCEPT_RANGES, HeaderValue::from_static("none"));
+    assert_eq!(parse_accept_ranges(&headers), AcceptRanges::None);
+
+    headers.insert(ACCEPT_RANGES, HeaderValue::from_static("items"));
+    assert_eq!(parse_accept_ranges(&headers), AcceptRanges::Other("items".to_string()));
+
+    headers.append(ACCEPT_RANGES, HeaderValue::from_static("Bytes"));
+    assert_eq!(parse_accept_ranges(&headers), AcceptRanges::Bytes);
+}
@@ -0,0 +1,210 @@
+use std::io::{self, Write};
+use std::str;
+
+use encoding_rs::{CoderResult, Encoder};
+
+use crate::charsets::Charset;
+use crate::skip_debug::SkipDebug;
+
+/// `EncodingWriter` converts bytes in UTF-8 to bytes in a specific charset.
+///
+/// It is the counterpart of `TextReader`: the UTF-8 bytes written to it are encoded and written to the inner
+/// writer, which makes it possible to stream large bodies which must be sent in another charset. A character
+/// split across several writes is encoded once it is complete. Characters which cannot be represented in the
+/// charset are replaced by HTML numeric character references such as `&#9731;`, and the UTF-16 charsets are
+/// encoded as UTF-8 like browsers do.
+///
+/// `flush` ends the current shift sequence of stateful charsets such as ISO-2022-JP before flushing the inner
+/// writer. Dropping the writer does the same while ignoring errors, use `finish` to handle them.
+#[derive(Debug)]
+pub struct EncodingWriter<W>
+where
+    W: Write,
+{
+    inner: Option<W>,
+    charset: Charset,
+    // Older versions of `encoding_rs` have no `Debug` implementation for `Encoder`.
+    encoder: SkipDebug<Encoder>,
+    // Start of a character whose remaining bytes have not been written yet.
+    pending: Vec<u8>,
+    buf: Vec<u8>,
+}
+
+impl<W> EncodingWriter<W>
+where
+    W: Write,
+{
+    /// Create a new `EncodingWriter` with the given charset.
+    pub fn new(inner: W, charset: Charset) -> Self {
+        EncodingWriter {
+            inner: Some(inner),
+            charset,
+            encoder: SkipDebug(charset.new_encoder()),
+            pending: Vec::new(),
+            buf: Vec::new(),
+        }
+    }
+
+    /// Get a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().expect("inner writer missing")
+    }
+
+    /// Write the end of the text and return the inner writer.
+    ///
+    /// Fails if the bytes written so far end with an incomplete UTF-8 character.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.end()?;
+        Ok(self.inner.take().expect("inner writer missing"))
+    }
+
+    /// Encode `text` and write the encoded bytes to the inner writer.
+    fn encode(&mut self, mut text: &str, last: bool) -> io::Result<()> {
+        self.buf.clear();
+        loop {
+            self.buf.reserve(text.len() + 16);
+            let (result, read, _) = self.encoder.0.encode_from_utf8_to_vec(text, &mut self.buf, last);
+            text = &text[read..];
+            if let CoderResult::InputEmpty = result {
+                break;
+            }
+        }
+        self.inner.as_mut().expect("inner writer missing").write_all(&self.buf)
+    }
+
+    /// Reset the state of the encoder, writing the bytes which end the current shift sequence if any.
+    fn end(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            self.pending.clear();
+            return Err(invalid_utf8());
+        }
+        self.encode("", true)?;
+        self.encoder = SkipDebug(self.charset.new_encoder());
+        self.inner.as_mut().expect("inner writer missing").flush()
+    }
+}
+
+fn invalid_utf8() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8")
+}
+
+impl<W> Write for EncodingWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut joined;
+        let bytes = if self.pending.is_empty() {
+            buf
+        } else {
+            joined = std::mem::take(&mut self.pending);
+            joined.extend_from_slice(buf);
+            &joined[..]
+        };
+
+        let (text, rest) = match str::from_utf8(bytes) {
+            Ok(text) => (text, &[][..]),
+            // The input ends in the middle of a character.
+            Err(err) if err.error_len().is_none() => {
+                let (valid, rest) = bytes.split_at(err.valid_up_to());
+                (str::from_utf8(valid).expect("valid UTF-8"), rest)
+            }
+            Err(_) => return Err(invalid_utf8()),
+        };
+        self.encode(text, false)?;
+        self.pending = rest.to_vec();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            self.end()
+        } else {
+            // The shift sequence cannot end in the middle of a character.
+            self.inner.as_mut().expect("inner writer missing").flush()
+        }
+    }
+}
+
+impl<W> Drop for EncodingWriter<W>
+where
+    W: Write,
+{
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.end();
+        }
+    }
+}
+
+#[cfg(test)]
+fn encode_in_pieces(text: &str, charset: Charset, piece_len: usize) -> Vec<u8> {
+    let mut writer = EncodingWriter::new(Vec::new(), charset);
+    for piece in text.as_bytes().chunks(piece_len) {
+        writer.write_all(piece).unwrap();
+    }
+    writer.finish().unwrap()
+}
+
+#[test]
+fn test_encoding_writer_matches_one_shot_encode() {
+    let text = "Crème brûlée, naïve façade \u{2603} 日本語 🦀 ok".repeat(20);
+    for &charset in &[
+        crate::charsets::WINDOWS_1252,
+        crate::charsets::SHIFT_JIS,
+        crate::charsets::ISO_2022_JP,
+        crate::charsets::UTF_8,
+        crate::charsets::UTF_16LE,
+    ] {
+        let (expected, _, _) = charset.encode(&text);
+        for &piece_len in &[1, 2, 3, 7, 1000] {
+            assert_eq!(
+                encode_in_pieces(&text, charset, piece_len),
+                expected.as_ref(),
+                "{} in pieces of {}",
+                charset.name(),
+                piece_len
+            );
+        }
+    }
+}
+
+#[test]
+fn test_encoding_writer_latin1() {
+    let encoded = encode_in_pieces("québec", crate::charsets::WINDOWS_1252, 1);
+    assert_eq!(encoded, b"qu\xe9bec");
+}
+
+#[test]
+fn test_encoding_writer_flush_ends_shift_sequence() {
+    let mut writer = EncodingWriter::new(Vec::new(), crate::charsets::ISO_2022_JP);
+    writer.write_all("日本".as_bytes()).unwrap();
+    writer.flush().unwrap();
+    assert_eq!(writer.get_ref().as_slice(), b"\x1b$BF|K\\\x1b(B");
+
+    // The next characters start a new shift sequence.
+    writer.write_all("語".as_bytes()).unwrap();
+    let encoded = writer.finish().unwrap();
+    assert_eq!(encoded, b"\x1b$BF|K\\\x1b(B\x1b$B8l\x1b(B");
+}
+
+#[test]
+fn test_encoding_writer_drop_ends_shift_sequence() {
+    let mut encoded = Vec::new();
+    {
+        let mut writer = EncodingWriter::new(&mut encoded, crate::charsets::ISO_2022_JP);
+        writer.write_all("日".as_bytes()).unwrap();
+    }
+    assert_eq!(encoded, b"\x1b$BF|\x1b(B");
+}
+
+#[test]
+fn test_encoding_writer_invalid_utf8() {
+    let mut writer = EncodingWriter::new(Vec::new(), crate::charsets::WINDOWS_1252);
+    let err = writer.write_all(b"ab\xff").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let mut writer = EncodingWriter::new(Vec::new(), crate::charsets::WINDOWS_1252);
+    writer.write_all("é".as_bytes().split_at(1).0).unwrap();
+    assert_eq!(writer.finish().unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,139 @@
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+
+use digest::{Digest, Output};
+
+/// `HashingReader` hashes the body it reads with any `Digest` implementation, such as `sha2::Sha256`.
+///
+/// The body is passed through unchanged, which makes it possible to write it to a file and compute its digest
+/// in one pass. Once the end of the body is reached, the digest becomes available from the `DigestHandle`
+/// returned with the reader. See [`Response::hashing_reader`](crate::Response::hashing_reader).
+pub struct HashingReader<H, R>
+where
+    H: Digest,
+{
+    inner: R,
+    hasher: Option<H>,
+    digest: DigestHandle<H>,
+}
+
+impl<H, R> HashingReader<H, R>
+where
+    H: Digest,
+    R: Read,
+{
+    /// Create a new `HashingReader` and the handle from which its digest is retrieved.
+    pub fn new(inner: R) -> (HashingReader<H, R>, DigestHandle<H>) {
+        let digest = DigestHandle {
+            digest: Arc::new(Mutex::new(None)),
+        };
+        let reader = HashingReader {
+            inner,
+            hasher: Some(H::new()),
+            digest: digest.clone(),
+        };
+        (reader, digest)
+    }
+
+    /// Get the digest of the body, once it has been read entirely.
+    #[inline]
+    pub fn digest(&self) -> Option<Output<H>> {
+        self.digest.get()
+    }
+}
+
+impl<H, R> Read for HashingReader<H, R>
+where
+    H: Digest,
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some(hasher) = &mut self.hasher {
+            if n > 0 {
+                hasher.update(&buf[..n]);
+            } else if !buf.is_empty() {
+                let digest = self.hasher.take().map(Digest::finalize);
+                if let Ok(mut slot) = self.digest.digest.lock() {
+                    *slot = digest;
+                }
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl<H, R> fmt::Debug for HashingReader<H, R>
+where
+    H: Digest,
+    R: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HashingReader")
+            .field("inner", &self.inner)
+            .field("done", &self.hasher.is_none())
+            .finish()
+    }
+}
+
+/// `DigestHandle` gives access to the digest computed by a `HashingReader`, even after the reader is dropped.
+pub struct DigestHandle<H>
+where
+    H: Digest,
+{
+    digest: Arc<Mutex<Option<Output<H>>>>,
+}
+
+impl<H> DigestHandle<H>
+where
+    H: Digest,
+{
+    /// Get the digest of the body, once the reader has reached its end.
+    ///
+    /// `None` is returned if the body has not been read entirely.
+    pub fn get(&self) -> Option<Output<H>> {
+        self.digest.lock().ok()?.clone()
+    }
+}
+
+impl<H> Clone for DigestHandle<H>
+where
+    H: Digest,
+{
+    fn clone(&self) -> Self {
+        DigestHandle {
+            digest: self.digest.clone(),
+        }
+    }
+}
+
+impl<H> fmt::Debug for DigestHandle<H>
+where
+    H: Digest,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DigestHandle").field("digest", &self.get()).finish()
+    }
+}
+
+#[test]
+fn test_hashing_reader() {
+    let body = b"The quick brown fox jumps over the lazy dog";
+    let (mut reader, handle) = HashingReader::<sha2::Sha256, _>::new(&body[..]);
+
+    let mut buf = [0; 10];
+    reader.read_exact(&mut buf).unwrap();
+    assert!(handle.get().is_none());
+
+    let mut out = buf.to_vec();
+    reader.read_to_end(&mut out).unwrap();
+    assert_eq!(out, &body[..]);
+    drop(reader);
+
+    let expected = [
+        0xd7, 0xa8, 0xfb, 0xb3, 0x07, 0xd7, 0x80, 0x94, 0x69, 0xca, 0x9a, 0xbc, 0xb0, 0x08, 0x2e, 0x4f, 0x8d, 0x56,
+        0x51, 0xe4, 0x6d, 0x3c, 0xdb, 0x76, 0x2d, 0x02, 0xd0, 0xbf, 0x37, 0xc9, 0xe5, 0x92,
+    ];
+    assert_eq!(&handle.get().unwrap()[..], &expected[..]);
+}
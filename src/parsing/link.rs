@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::str;
+
+use http::header::{HeaderMap, LINK};
+
+/// `LinkValue` is a link to another resource sent by the server in the `Link` header.
+///
+/// See [`Response::links`](crate::Response::links).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkValue {
+    uri: String,
+    params: Vec<(String, String)>,
+}
+
+impl LinkValue {
+    /// Get the target of this link, as sent by the server.
+    ///
+    /// It may be a relative reference, which must be resolved against the URL of the request with `Url::join`.
+    #[inline]
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Get the parameters of this link in order, including `rel`. Quoted values are unescaped.
+    #[inline]
+    pub fn params(&self) -> &[(String, String)] {
+        &self.params
+    }
+
+    /// Get the value of a parameter of this link, the name is case-insensitive.
+    ///
+    /// A parameter without a value, such as `crossorigin`, has an empty value.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+fn is_tchar(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c)
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(b' ') | Some(b'\t') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn token(&mut self) -> Option<String> {
+        let start = self.pos;
+        while self.peek().is_some_and(is_tchar) {
+            self.pos += 1;
+        }
+        match &self.input[start..self.pos] {
+            b"" => None,
+            // Only ASCII characters are accepted by `is_tchar`.
+            token => str::from_utf8(token).ok().map(String::from),
+        }
+    }
+
+    fn quoted_string(&mut self) -> Option<String> {
+        if self.peek() != Some(b'"') {
+            return None;
+        }
+        self.pos += 1;
+
+        let mut value = Vec::new();
+        loop {
+            match self.peek()? {
+                b'"' => {
+                    self.pos += 1;
+                    return String::from_utf8(value).ok();
+                }
+                b'\\' => {
+                    value.push(*self.input.get(self.pos + 1)?);
+                    self.pos += 2;
+                }
+                c => {
+                    value.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn uri_reference(&mut self) -> Option<String> {
+        if self.peek() != Some(b'<') {
+            return None;
+        }
+        let start = self.pos + 1;
+        let len = self.input[start..].iter().position(|&c| c == b'>')?;
+        self.pos = start + len + 1;
+        str::from_utf8(&self.input[start..start + len]).ok().map(String::from)
+    }
+
+    fn param(&mut self) -> Option<(String, String)> {
+        let name = self.token()?;
+        self.skip_ws();
+        if self.peek() != Some(b'=') {
+            return Some((name, String::new()));
+        }
+        self.pos += 1;
+        self.skip_ws();
+        let value = match self.peek() {
+            Some(b'"') => self.quoted_string()?,
+            _ => self.token()?,
+        };
+        Some((name, value))
+    }
+
+    fn link(&mut self) -> Option<LinkValue> {
+        while let Some(b' ') | Some(b'\t') | Some(b',') = self.peek() {
+            self.pos += 1;
+        }
+        let uri = self.uri_reference()?;
+        let mut params = Vec::new();
+        loop {
+            self.skip_ws();
+            if self.peek() != Some(b';') {
+                break;
+            }
+            self.pos += 1;
+            self.skip_ws();
+            params.push(self.param()?);
+        }
+
+        self.skip_ws();
+        match self.peek() {
+            None | Some(b',') => Some(LinkValue { uri, params }),
+            Some(_) => None,
+        }
+    }
+}
+
+/// Parse the `Link` headers into a map from relation types to links, invalid syntax ends the parsing of a header.
+///
+/// A link with several space-separated relation types is added for each of them, and the first link of a relation
+/// type is kept. Relation types are case-insensitive, they are lowercased.
+pub fn parse_links(headers: &HeaderMap) -> HashMap<String, LinkValue> {
+    let mut links = HashMap::new();
+    for value in headers.get_all(LINK) {
+        let mut parser = Parser {
+            input: value.as_bytes(),
+            pos: 0,
+        };
+        while let Some(link) = parser.link() {
+            let rels = link.param("rel").unwrap_or_default().to_ascii_lowercase();
+            for rel in rels.split_ascii_whitespace() {
+                links.entry(rel.to_string()).or_insert_with(|| link.clone());
+            }
+        }
+    }
+    links
+}
+
+#[cfg(test)]
+fn parse(value: &'static str) -> HashMap<String, LinkValue> {
+    let mut headers = HeaderMap::new();
+    headers.insert(LINK, http::HeaderValue::from_static(value));
+    parse_links(&headers)
+}
+
+#[test]
+fn test_parse_pagination() {
+    let links = parse(
+        "<https://api.example.com/items?page=3&per_page=50>; rel=\"next\"; title=\"Page 3\", \
+         <https://api.example.com/items?page=9>; rel=last",
+    );
+    assert_eq!(links.len(), 2);
+    let next = &links["next"];
+    assert_eq!(next.uri(), "https://api.example.com/items?page=3&per_page=50");
+    assert_eq!(next.param("title"), Some("Page 3"));
+    assert_eq!(next.param("Rel"), Some("next"));
+    assert_eq!(links["last"].uri(), "https://api.example.com/items?page=9");
+    assert_eq!(links["last"].params(), [("rel".to_string(), "last".to_string())]);
+}
+
+#[test]
+fn test_parse_several_headers() {
+    let mut headers = HeaderMap::new();
+    headers.append(LINK, http::HeaderValue::from_static("</a,b>; rel=\"prev first\""));
+    headers.append(
+        LINK,
+        http::HeaderValue::from_static("</c>; rel=NEXT; crossorigin, </d>; rel=next"),
+    );
+    let links = parse_links(&headers);
+    assert_eq!(links.len(), 3);
+    assert_eq!(links["prev"].uri(), "/a,b");
+    assert_eq!(links["first"].uri(), "/a,b");
+    // The first link of a relation type is kept.
+    assert_eq!(links["next"].uri(), "/c");
+    assert_eq!(links["next"].param("crossorigin"), Some(""));
+}
+
+#[test]
+fn test_parse_invalid() {
+    assert!(parse("").is_empty());
+    assert!(parse("https://example.com/; rel=next").is_empty());
+    assert!(parse("</unterminated; rel=next").is_empty());
+    // Links without a relation type are ignored.
+    assert!(parse("</x>").is_empty());
+    let links = parse("</x>; rel=next, garbage, </y>; rel=last");
+    assert_eq!(links.len(), 1);
+    assert_eq!(links["next"].uri(), "/x");
+}
@@ -0,0 +1,140 @@
+/// Get the value of an attribute of an HTML tag, given the bytes between the tag name and `>`.
+fn attribute<'a>(mut attrs: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
+    loop {
+        let start = attrs.iter().position(|c| !c.is_ascii_whitespace() && *c != b'/')?;
+        attrs = &attrs[start..];
+        let end = attrs
+            .iter()
+            .position(|c| c.is_ascii_whitespace() || *c == b'=')
+            .unwrap_or(attrs.len());
+        let (key, rest) = attrs.split_at(end);
+        let rest = trim_start(rest);
+
+        let value = match rest.strip_prefix(b"=") {
+            Some(rest) => {
+                let rest = trim_start(rest);
+                let (value, len) = match rest.first() {
+                    Some(&quote) if quote == b'"' || quote == b'\'' => {
+                        let value = &rest[1..];
+                        let end = value.iter().position(|&c| c == quote).unwrap_or(value.len());
+                        (&value[..end], (end + 2).min(rest.len()))
+                    }
+                    _ => {
+                        let end = rest.iter().position(u8::is_ascii_whitespace).unwrap_or(rest.len());
+                        (&rest[..end], end)
+                    }
+                };
+                attrs = &rest[len..];
+                value
+            }
+            None => {
+                attrs = rest;
+                &[][..]
+            }
+        };
+        if key.eq_ignore_ascii_case(name) {
+            return Some(value);
+        }
+    }
+}
+
+fn trim_start(buf: &[u8]) -> &[u8] {
+    let start = buf.iter().position(|c| !c.is_ascii_whitespace()).unwrap_or(buf.len());
+    &buf[start..]
+}
+
+/// Parse the `content` of a refresh, returns the URL if the delay is zero.
+fn zero_delay_url(content: &[u8]) -> Option<String> {
+    let content = trim_start(content);
+    let digits = content
+        .iter()
+        .position(|c| !c.is_ascii_digit())
+        .unwrap_or(content.len());
+    let (delay, rest) = content.split_at(digits);
+    if delay.is_empty() || delay.iter().any(|&c| c != b'0') {
+        return None;
+    }
+    // A fractional part of the delay is ignored.
+    let rest = &rest[rest
+        .iter()
+        .position(|c| !c.is_ascii_digit() && *c != b'.')
+        .unwrap_or(rest.len())..];
+    let rest = trim_start(rest);
+    let rest = trim_start(
+        rest.strip_prefix(b";")
+            .or_else(|| rest.strip_prefix(b","))
+            .unwrap_or(rest),
+    );
+    let rest = match rest.get(..3) {
+        Some(key) if key.eq_ignore_ascii_case(b"url") => trim_start(&rest[3..]),
+        _ => rest,
+    };
+    let rest = trim_start(rest.strip_prefix(b"=").unwrap_or(rest));
+
+    let url = match rest.first() {
+        Some(&quote) if quote == b'"' || quote == b'\'' => {
+            let rest = &rest[1..];
+            &rest[..rest.iter().position(|&c| c == quote).unwrap_or(rest.len())]
+        }
+        _ => rest,
+    };
+    let url = String::from_utf8_lossy(url).trim().replace("&amp;", "&");
+    // A refresh without a URL reloads the same page.
+    if url.is_empty() {
+        None
+    } else {
+        Some(url)
+    }
+}
+
+/// Find the target of a `<meta http-equiv="refresh">` tag with a zero delay in an HTML document.
+pub fn parse_meta_refresh(html: &[u8]) -> Option<String> {
+    let mut rest = html;
+    while let Some(pos) = rest.windows(5).position(|w| w.eq_ignore_ascii_case(b"<meta")) {
+        rest = &rest[pos + 5..];
+        if !rest.first().is_some_and(|c| c.is_ascii_whitespace()) {
+            continue;
+        }
+        let end = rest.iter().position(|&c| c == b'>').unwrap_or(rest.len());
+        let attrs = &rest[..end];
+        rest = &rest[end..];
+
+        let is_refresh = attribute(attrs, b"http-equiv").is_some_and(|value| value.eq_ignore_ascii_case(b"refresh"));
+        if is_refresh {
+            return attribute(attrs, b"content").and_then(zero_delay_url);
+        }
+    }
+    None
+}
+
+#[test]
+fn test_parse_meta_refresh() {
+    let html = b"<html><head><title>Moved</title>\n<META HTTP-EQUIV=\"Refresh\" CONTENT=\"0; URL=/new/page?a=1&amp;b=2\">\n</head></html>";
+    assert_eq!(parse_meta_refresh(html).as_deref(), Some("/new/page?a=1&b=2"));
+
+    let html = b"<meta content='0;url=\"https://example.com/\"' http-equiv='refresh' />";
+    assert_eq!(parse_meta_refresh(html).as_deref(), Some("https://example.com/"));
+
+    let html = b"<meta charset=utf-8><meta http-equiv=refresh content=0,https://example.com/x>";
+    assert_eq!(parse_meta_refresh(html).as_deref(), Some("https://example.com/x"));
+}
+
+#[test]
+fn test_parse_meta_refresh_ignored() {
+    // A delay other than zero leaves the page displayed for a while.
+    assert_eq!(
+        parse_meta_refresh(b"<meta http-equiv=\"refresh\" content=\"5; url=/next\">"),
+        None
+    );
+    // Without a URL the page only reloads itself.
+    assert_eq!(parse_meta_refresh(b"<meta http-equiv=\"refresh\" content=\"0\">"), None);
+    assert_eq!(
+        parse_meta_refresh(b"<meta name=\"refresh\" content=\"0; url=/next\">"),
+        None
+    );
+    assert_eq!(
+        parse_meta_refresh(b"<metadata http-equiv=\"refresh\" content=\"0; url=/next\">"),
+        None
+    );
+    assert_eq!(parse_meta_refresh(b"<p>no meta here</p>"), None);
+}
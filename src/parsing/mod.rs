@@ -1,13 +1,34 @@
+pub mod auth_challenge;
 pub mod body_reader;
 pub mod buffers;
+#[cfg(feature = "checksum")]
+pub mod checksum_reader;
 pub mod chunked_reader;
 pub mod compressed_reader;
+pub mod content_range;
+#[cfg(feature = "charsets")]
+pub mod encoding_writer;
+#[cfg(feature = "hash")]
+pub mod hashing_reader;
+pub mod link;
+pub mod meta_refresh;
+pub mod multipart_reader;
 pub mod response;
 pub mod response_reader;
 #[cfg(feature = "charsets")]
 pub mod text_reader;
 
-pub use self::response::{parse_response, Response};
+pub use self::auth_challenge::AuthChallenge;
+#[cfg(feature = "checksum")]
+pub use self::checksum_reader::{ChecksumAlgorithm, ChecksumReader};
+pub use self::content_range::{AcceptRanges, ContentRange};
+#[cfg(feature = "charsets")]
+pub use self::encoding_writer::EncodingWriter;
+#[cfg(feature = "hash")]
+pub use self::hashing_reader::{DigestHandle, HashingReader};
+pub use self::link::LinkValue;
+pub use self::multipart_reader::{MultipartPart, MultipartPartReader, MultipartReader};
+pub use self::response::{parse_response, InformationalResponse, RedirectHop, Response};
 pub use self::response_reader::ResponseReader;
 #[cfg(feature = "charsets")]
 pub use self::text_reader::TextReader;
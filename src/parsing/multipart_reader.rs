@@ -0,0 +1,453 @@
+use std::io::{self, Read};
+use std::mem;
+
+use http::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+
+use crate::error::{Error, InvalidResponseKind, Result};
+use crate::parsing::buffers::trim_byte;
+
+const READ_SIZE: usize = 8 * 1024;
+const MAX_HEADER_LINE_LEN: usize = 16 * 1024;
+const DEFAULT_MAX_PART_SIZE: usize = 16 * 1024 * 1024;
+
+/// Extract the boundary of a `multipart/*` content type.
+pub fn get_boundary(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+    let mut params = value.split(';');
+
+    let mime = params.next()?.trim();
+    if !mime.to_ascii_lowercase().starts_with("multipart/") {
+        return None;
+    }
+
+    params
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim().trim_matches('"').to_string())
+        .filter(|boundary| !boundary.is_empty())
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum State {
+    Preamble,
+    Parts,
+    // The body of the current part has not been read to the end.
+    Body,
+    Done,
+}
+
+/// A single part of a multipart response, see [`Response::multipart`](crate::Response::multipart).
+#[derive(Debug)]
+pub struct MultipartPart {
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl MultipartPart {
+    /// Get the headers of this part.
+    #[inline]
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Get the body of this part.
+    #[inline]
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Consume this part and get its body.
+    #[inline]
+    pub fn into_body(self) -> Vec<u8> {
+        self.body
+    }
+}
+
+/// `MultipartReader` is an iterator over the parts of a multipart response.
+///
+/// Parts are read from the underlying stream as they are requested, which makes it suitable
+/// for never ending streams such as `multipart/x-mixed-replace`. The iterator buffers the body
+/// of each part in memory before the part is returned, up to the limit set with `max_part_size`.
+/// Use `next_part` to stream the bodies instead.
+#[derive(Debug)]
+pub struct MultipartReader<R> {
+    inner: R,
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    eof: bool,
+    state: State,
+    max_part_size: usize,
+}
+
+impl<R> MultipartReader<R>
+where
+    R: Read,
+{
+    pub(crate) fn new(inner: R, boundary: &str) -> MultipartReader<R> {
+        MultipartReader {
+            inner,
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            // The first delimiter is not required to be preceded by a line break, adding one
+            // lets every delimiter be searched the same way.
+            buf: b"\r\n".to_vec(),
+            eof: false,
+            state: State::Preamble,
+            max_part_size: DEFAULT_MAX_PART_SIZE,
+        }
+    }
+
+    /// Set the maximum size in bytes of the body of the parts returned by the iterator, 16 MiB by default.
+    ///
+    /// A larger part fails with [`InvalidResponseKind::PartTooLarge`](crate::InvalidResponseKind::PartTooLarge)
+    /// and ends the iteration. The bodies read with `next_part` are not limited.
+    pub fn max_part_size(mut self, bytes: usize) -> Self {
+        self.max_part_size = bytes;
+        self
+    }
+
+    /// Read the headers of the next part and return a reader of its body, or `None` after the last part.
+    ///
+    /// The body is read from the underlying stream as it is read from the returned reader. If it is not read
+    /// to the end, the rest of it is skipped when the next part is requested.
+    pub fn next_part(&mut self) -> Result<Option<MultipartPartReader<'_, R>>> {
+        if self.state == State::Done {
+            return Ok(None);
+        }
+        match self.read_headers() {
+            Ok(Some(headers)) => Ok(Some(MultipartPartReader { reader: self, headers })),
+            Ok(None) => Ok(None),
+            Err(err) => {
+                self.state = State::Done;
+                Err(err)
+            }
+        }
+    }
+
+    fn fill(&mut self) -> Result<()> {
+        if self.eof {
+            return Err(InvalidResponseKind::Multipart.into());
+        }
+        let len = self.buf.len();
+        self.buf.resize(len + READ_SIZE, 0);
+        let res = self.inner.read(&mut self.buf[len..]);
+        let n = *res.as_ref().unwrap_or(&0);
+        self.buf.truncate(len + n);
+        if res? == 0 {
+            self.eof = true;
+        }
+        Ok(())
+    }
+
+    /// Discard everything up to and including the next delimiter.
+    fn skip_to_delimiter(&mut self) -> Result<()> {
+        loop {
+            if let Some(pos) = find(&self.buf, &self.delimiter) {
+                self.buf.drain(..pos + self.delimiter.len());
+                return Ok(());
+            }
+
+            // Keep the end of the buffer around in case it contains the start of a delimiter.
+            let safe = self.buf.len().saturating_sub(self.delimiter.len() - 1);
+            self.buf.drain(..safe);
+            self.fill()?;
+        }
+    }
+
+    /// Read some bytes of the body of the current part, returns 0 once the delimiter ending it is reached.
+    fn read_body(&mut self, out: &mut [u8]) -> Result<usize> {
+        if self.state != State::Body || out.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let available = match find(&self.buf, &self.delimiter) {
+                Some(0) => {
+                    self.buf.drain(..self.delimiter.len());
+                    self.after_delimiter()?;
+                    return Ok(0);
+                }
+                Some(pos) => pos,
+                // Keep the end of the buffer around in case it contains the start of a delimiter.
+                None => self.buf.len().saturating_sub(self.delimiter.len() - 1),
+            };
+            if available > 0 {
+                let n = available.min(out.len());
+                out[..n].copy_from_slice(&self.buf[..n]);
+                self.buf.drain(..n);
+                return Ok(n);
+            }
+            self.fill()?;
+        }
+    }
+
+    fn read_line(&mut self) -> Result<Vec<u8>> {
+        loop {
+            if let Some(pos) = self.buf.iter().position(|&b| b == b'\n') {
+                let mut line: Vec<u8> = self.buf.drain(..=pos).collect();
+                line.pop();
+                if line.ends_with(b"\r") {
+                    line.pop();
+                }
+                return Ok(line);
+            }
+            if self.buf.len() > MAX_HEADER_LINE_LEN {
+                return Err(InvalidResponseKind::Multipart.into());
+            }
+            self.fill()?;
+        }
+    }
+
+    /// Process what comes after a delimiter, either the end marker or the end of the line.
+    fn after_delimiter(&mut self) -> Result<()> {
+        while self.buf.len() < 2 {
+            self.fill()?;
+        }
+        if self.buf.starts_with(b"--") {
+            self.state = State::Done;
+            return Ok(());
+        }
+        let line = self.read_line()?;
+        if !trim_byte(b'\t', trim_byte(b' ', &line)).is_empty() {
+            return Err(InvalidResponseKind::Multipart.into());
+        }
+        self.state = State::Parts;
+        Ok(())
+    }
+
+    /// Read the headers of the next part, skipping the preamble or the rest of the previous part.
+    fn read_headers(&mut self) -> Result<Option<HeaderMap>> {
+        if let State::Preamble | State::Body = self.state {
+            self.skip_to_delimiter()?;
+            self.after_delimiter()?;
+        }
+        if self.state == State::Done {
+            return Ok(None);
+        }
+
+        let mut headers = HeaderMap::new();
+        loop {
+            let line = self.read_line()?;
+            if line.is_empty() {
+                break;
+            }
+
+            let col = line
+                .iter()
+                .position(|&c| c == b':')
+                .ok_or(InvalidResponseKind::Multipart)?;
+            let name = trim_byte(b' ', &line[..col]);
+            let value = trim_byte(b' ', &line[col + 1..]);
+
+            headers.append(
+                HeaderName::from_bytes(name).map_err(http::Error::from)?,
+                HeaderValue::from_bytes(value).map_err(http::Error::from)?,
+            );
+        }
+
+        self.state = State::Body;
+        Ok(Some(headers))
+    }
+}
+
+impl<R> Iterator for MultipartReader<R>
+where
+    R: Read,
+{
+    type Item = Result<MultipartPart>;
+
+    fn next(&mut self) -> Option<Result<MultipartPart>> {
+        let max_part_size = self.max_part_size;
+        let res = match self.next_part() {
+            Ok(Some(part)) => part.buffer(max_part_size),
+            Ok(None) => return None,
+            Err(err) => return Some(Err(err)),
+        };
+        if res.is_err() {
+            self.state = State::Done;
+        }
+        Some(res)
+    }
+}
+
+/// A reader of the body of a single part of a multipart response, see [`MultipartReader::next_part`].
+#[derive(Debug)]
+pub struct MultipartPartReader<'a, R> {
+    reader: &'a mut MultipartReader<R>,
+    headers: HeaderMap,
+}
+
+impl<R> MultipartPartReader<'_, R>
+where
+    R: Read,
+{
+    /// Get the headers of this part.
+    #[inline]
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Read the whole body of this part, failing if it is longer than `max` bytes.
+    fn buffer(mut self, max: usize) -> Result<MultipartPart> {
+        let mut body = Vec::new();
+        Read::by_ref(&mut self)
+            .take((max as u64).saturating_add(1))
+            .read_to_end(&mut body)
+            .map_err(Error::from_io)?;
+        if body.len() > max {
+            return Err(InvalidResponseKind::PartTooLarge.into());
+        }
+        Ok(MultipartPart {
+            headers: mem::take(&mut self.headers),
+            body,
+        })
+    }
+}
+
+impl<R> Read for MultipartPartReader<'_, R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.reader.read_body(buf) {
+            Ok(n) => Ok(n),
+            Err(err) => {
+                self.reader.state = State::Done;
+                Err(err.into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+
+    use http::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+
+    use super::{get_boundary, MultipartReader};
+    use crate::{ErrorKind, InvalidResponseKind};
+
+    const BODY: &[u8] = b"preamble\r\n--frame\r\nContent-Type: text/plain\r\n\r\nhello\r\n\
+        --frame  \r\nContent-Type: application/octet-stream\r\nX-Index: 2\r\n\r\nwor\r\nld--fram\r\n\
+        --frame--\r\nepilogue";
+
+    struct SmallReads<'a>(&'a [u8], usize);
+
+    impl Read for SmallReads<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(self.1);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    fn check_parts<R: Read>(reader: MultipartReader<R>) {
+        let parts: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+        assert_eq!(parts.len(), 2);
+
+        assert_eq!(parts[0].headers()[CONTENT_TYPE], "text/plain");
+        assert_eq!(parts[0].body(), b"hello");
+
+        assert_eq!(parts[1].headers()[CONTENT_TYPE], "application/octet-stream");
+        assert_eq!(parts[1].headers()["x-index"], "2");
+        assert_eq!(parts[1].body(), b"wor\r\nld--fram");
+    }
+
+    #[test]
+    fn test_multipart_reader() {
+        check_parts(MultipartReader::new(BODY, "frame"));
+    }
+
+    #[test]
+    fn test_multipart_reader_split_reads() {
+        for size in 1..8 {
+            check_parts(MultipartReader::new(SmallReads(BODY, size), "frame"));
+        }
+    }
+
+    #[test]
+    fn test_multipart_reader_no_preamble() {
+        let body = b"--b\r\n\r\nfirst\r\n--b--";
+        let parts: Vec<_> = MultipartReader::new(&body[..], "b").collect::<Result<_, _>>().unwrap();
+        assert_eq!(parts.len(), 1);
+        assert!(parts[0].headers().is_empty());
+        assert_eq!(parts[0].body(), b"first");
+    }
+
+    #[test]
+    fn test_multipart_reader_truncated() {
+        let body = b"--b\r\n\r\nfirst\r\n--b\r\n\r\nsec";
+        let mut reader = MultipartReader::new(&body[..], "b");
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_multipart_reader_next_part() {
+        for size in 1..8 {
+            let mut reader = MultipartReader::new(SmallReads(BODY, size), "frame");
+
+            // The first part is not read to the end, the rest of it is skipped.
+            let mut part = reader.next_part().unwrap().unwrap();
+            assert_eq!(part.headers()[CONTENT_TYPE], "text/plain");
+            let mut start = [0; 2];
+            part.read_exact(&mut start).unwrap();
+            assert_eq!(&start, b"he");
+
+            let mut part = reader.next_part().unwrap().unwrap();
+            assert_eq!(part.headers()["x-index"], "2");
+            let mut body = Vec::new();
+            part.read_to_end(&mut body).unwrap();
+            assert_eq!(body, b"wor\r\nld--fram");
+
+            assert!(reader.next_part().unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn test_multipart_reader_max_part_size() {
+        let parts: Vec<_> = MultipartReader::new(BODY, "frame")
+            .max_part_size(13)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(parts.len(), 2);
+
+        let mut reader = MultipartReader::new(BODY, "frame").max_part_size(12);
+        assert_eq!(reader.next().unwrap().unwrap().body(), b"hello");
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::InvalidResponse(InvalidResponseKind::PartTooLarge)
+        ));
+        assert!(reader.next().is_none());
+
+        // A part which never ends is not buffered past the limit.
+        let endless = b"--b\r\n\r\n".chain(io::repeat(b'x'));
+        let mut reader = MultipartReader::new(endless, "b").max_part_size(1024);
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_get_boundary() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("multipart/x-mixed-replace; boundary=\"frame\""),
+        );
+        assert_eq!(get_boundary(&headers).as_deref(), Some("frame"));
+
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("multipart/mixed; charset=utf-8; Boundary=abc"),
+        );
+        assert_eq!(get_boundary(&headers).as_deref(), Some("abc"));
+
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain; boundary=abc"));
+        assert_eq!(get_boundary(&headers), None);
+    }
+}
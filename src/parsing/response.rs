@@ -1,51 +1,149 @@
+use std::cmp;
+use std::collections::HashMap;
 use std::io::{self, BufReader, Read, Write};
+use std::mem;
+use std::net::SocketAddr;
 use std::str;
+use std::time::Duration;
 
 use http::{
-    header::{HeaderName, HeaderValue, TRANSFER_ENCODING},
-    HeaderMap, StatusCode,
+    header::{HeaderName, HeaderValue, ALLOW, CONNECTION, CONTENT_LENGTH, CONTENT_TYPE, TRANSFER_ENCODING},
+    Extensions, HeaderMap, Method, StatusCode, Version,
 };
+use url::Url;
 
-use crate::error::{ErrorKind, InvalidResponseKind, Result};
+use crate::error::{Error, ErrorKind, InvalidResponseKind, Result};
+use crate::metrics::Metrics;
 use crate::parsing::buffers::{self, trim_byte};
-use crate::parsing::{body_reader::BodyReader, compressed_reader::CompressedReader, ResponseReader};
+use crate::parsing::{
+    auth_challenge::{parse_www_authenticate, AuthChallenge},
+    body_reader::BodyReader,
+    compressed_reader::CompressedReader,
+    content_range::{parse_accept_ranges, parse_content_range, AcceptRanges, ContentRange},
+    link::{parse_links, LinkValue},
+    meta_refresh::parse_meta_refresh,
+    multipart_reader::{get_boundary, MultipartReader},
+    ResponseReader,
+};
 use crate::request::PreparedRequest;
 use crate::streams::BaseStream;
+use crate::tls::PeerCertificate;
 
 #[cfg(feature = "charsets")]
 use crate::{charsets::Charset, parsing::TextReader};
 
-#[cfg(feature = "json")]
+#[cfg(feature = "checksum")]
+use crate::parsing::ChecksumReader;
+#[cfg(feature = "hash")]
+use crate::parsing::{DigestHandle, HashingReader};
+
+#[cfg(any(feature = "json", feature = "xml"))]
 use serde::de::DeserializeOwned;
 
-pub fn parse_response_head<R>(reader: &mut BufReader<R>) -> Result<(StatusCode, HeaderMap)>
+const MAX_LINE_LEN: usize = 16 * 1024;
+/// Maximum number of header lines in a response head or in the trailers of a chunked body.
+const MAX_HEADER_COUNT: usize = 100;
+/// Default maximum size of a response head, and maximum size of the trailers of a chunked body.
+pub const DEFAULT_MAX_HEADER_SIZE: usize = 64 * 1024;
+/// Number of bytes at the start of an HTML body searched for a meta refresh.
+const MAX_META_REFRESH_SCAN: usize = 64 * 1024;
+
+/// Read a line of the response head, `remaining` is the number of bytes the rest of the head may take.
+fn read_head_line<R>(reader: &mut BufReader<R>, line: &mut Vec<u8>, remaining: &mut usize) -> Result<usize>
 where
     R: Read,
 {
-    const MAX_LINE_LEN: u64 = 16 * 1024;
+    let limit = cmp::min(MAX_LINE_LEN, *remaining);
+    match buffers::read_line(reader, line, limit as u64) {
+        Ok(n) => {
+            *remaining -= n;
+            Ok(n)
+        }
+        Err(_) if line.len() >= limit => Err(InvalidResponseKind::HeadersTooLarge.into()),
+        Err(err) => Err(err.into()),
+    }
+}
 
+/// Parse the status line and the headers of a response, which may take at most `max_size` bytes.
+pub fn parse_response_head<R>(reader: &mut BufReader<R>, max_size: usize) -> Result<(Version, StatusCode, HeaderMap)>
+where
+    R: Read,
+{
     let mut line = Vec::new();
     let mut headers = HeaderMap::new();
+    let mut remaining = max_size;
 
     // status line
-    let status: StatusCode = {
-        buffers::read_line(reader, &mut line, MAX_LINE_LEN)?;
+    let (version, status) = {
+        read_head_line(reader, &mut line, &mut remaining)?;
         let mut parts = line.split(|&b| b == b' ').filter(|x| !x.is_empty());
 
-        let _ = parts.next().ok_or(InvalidResponseKind::StatusLine)?;
+        let version = parts.next().ok_or(InvalidResponseKind::StatusLine)?;
         let code = parts.next().ok_or(InvalidResponseKind::StatusLine)?;
 
-        str::from_utf8(code)
+        let status: StatusCode = str::from_utf8(code)
             .map_err(|_| InvalidResponseKind::StatusCode)?
             .parse()
-            .map_err(|_| InvalidResponseKind::StatusCode)?
+            .map_err(|_| InvalidResponseKind::StatusCode)?;
+        (parse_version(version), status)
     };
 
+    parse_headers(reader, &mut headers, remaining)?;
+
+    Ok((version, status, headers))
+}
+
+/// Parse the version of a status line, versions other than HTTP/1.0 and HTTP/0.9 are handled as HTTP/1.1.
+fn parse_version(version: &[u8]) -> Version {
+    match version {
+        b"HTTP/1.0" => Version::HTTP_10,
+        b"HTTP/0.9" => Version::HTTP_09,
+        _ => Version::HTTP_11,
+    }
+}
+
+/// Check if the `Connection` headers contain the given option, such as `close` or `keep-alive`.
+fn has_connection_option(headers: &HeaderMap, option: &str) -> bool {
+    headers
+        .get_all(CONNECTION)
+        .iter()
+        .flat_map(|value| value.as_bytes().split(|&b| b == b','))
+        .any(|token| trim_byte(b' ', token).eq_ignore_ascii_case(option.as_bytes()))
+}
+
+/// Parse header lines until an empty line, like the headers of a response or the trailers of a chunked body.
+///
+/// Header lines are validated strictly since responses may be forwarded: lines must end with CRLF, obsolete
+/// line folding is rejected, and so are control characters such as NUL or a bare CR in values.
+/// The header lines may take at most `max_size` bytes.
+pub fn parse_headers<R>(reader: &mut BufReader<R>, headers: &mut HeaderMap, max_size: usize) -> Result
+where
+    R: Read,
+{
+    let mut line = Vec::new();
+    let mut remaining = max_size;
+    let mut count = 0;
+
     loop {
-        buffers::read_line(reader, &mut line, MAX_LINE_LEN)?;
+        let n = read_head_line(reader, &mut line, &mut remaining)?;
+        if n != line.len() + 2 {
+            // The line ends with a bare LF.
+            return Err(InvalidResponseKind::Header.into());
+        }
         if line.is_empty() {
             break;
         }
+        count += 1;
+        if count > MAX_HEADER_COUNT {
+            return Err(InvalidResponseKind::HeadersTooLarge.into());
+        }
+        if line[0] == b' ' || line[0] == b'\t' {
+            // Obsolete line folding, the line continues the value of the previous header.
+            return Err(InvalidResponseKind::Header.into());
+        }
+        if line.iter().any(|&b| (b < b' ' && b != b'\t') || b == 0x7f) {
+            return Err(InvalidResponseKind::Header.into());
+        }
 
         let col = line
             .iter()
@@ -61,35 +159,172 @@ where
         );
     }
 
-    Ok((status, headers))
+    Ok(())
 }
 
 pub fn parse_response<B>(reader: BaseStream, request: &PreparedRequest<B>) -> Result<Response> {
+    let recorder = reader.recorder();
+    let remote_addr = reader.peer_addr();
+    let alpn_protocol = reader.alpn_protocol().map(String::from);
+    let peer_certificate = reader.peer_certificate().cloned();
     let mut reader = BufReader::new(reader);
-    let (status, mut headers) = parse_response_head(&mut reader)?;
-    let body_reader = BodyReader::new(&headers, reader)?;
+    let mut informational = Vec::new();
+
+    // Interim responses such as `100 Continue` or `103 Early Hints` may come before the final response.
+    // `101 Switching Protocols` is final since the connection stops speaking HTTP after it.
+    let (version, status, mut headers) = loop {
+        let (version, status, headers) = parse_response_head(&mut reader, request.base_settings.max_header_size)?;
+        if !status.is_informational() || status == StatusCode::SWITCHING_PROTOCOLS {
+            break (version, status, headers);
+        }
+        debug!("skipping interim response {}", status.as_u16());
+        informational.push(InformationalResponse { status, headers });
+    };
+
+    // Connections are persistent by default since HTTP/1.1, older servers close them unless asked otherwise.
+    let close = match version {
+        Version::HTTP_09 | Version::HTTP_10 => !has_connection_option(&headers, "keep-alive"),
+        _ => has_connection_option(&headers, "close"),
+    };
+    if close {
+        reader.get_mut().disable_reuse();
+    }
+
+    let has_body = request.method() != Method::HEAD
+        && !status.is_informational()
+        && status != StatusCode::NO_CONTENT
+        && status != StatusCode::NOT_MODIFIED;
+    let body_reader = BodyReader::new(&headers, has_body, request.base_settings.strict_framing, reader)?;
     let compressed_reader = CompressedReader::new(&headers, request, body_reader)?;
-    let response_reader = ResponseReader::new(&headers, request, compressed_reader);
+    let response_reader = ResponseReader::new(&headers, request, compressed_reader, recorder);
 
     // Remove HOP-BY-HOP headers
     headers.remove(TRANSFER_ENCODING);
 
     Ok(Response {
+        url: request.url().clone(),
+        version,
         status,
         headers,
         reader: response_reader,
+        redirect_history: Vec::new(),
+        informational,
+        remote_addr,
+        alpn_protocol,
+        peer_certificate,
+        extensions: Extensions::new(),
     })
 }
 
+/// `InformationalResponse` is an interim `1xx` response received before the final response.
+#[derive(Debug, Clone)]
+pub struct InformationalResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+}
+
+impl InformationalResponse {
+    /// Get the status code of this interim response, such as `103 Early Hints`.
+    #[inline]
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Get the headers of this interim response, such as the `Link` headers of early hints.
+    #[inline]
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+}
+
+/// `RedirectHop` describes a redirection that was followed while sending a request.
+#[derive(Debug, Clone)]
+pub struct RedirectHop {
+    url: Url,
+    status: StatusCode,
+}
+
+impl RedirectHop {
+    pub(crate) fn new(url: Url, status: StatusCode) -> RedirectHop {
+        RedirectHop { url, status }
+    }
+
+    /// Get the URL that was requested and answered with a redirection.
+    #[inline]
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Get the redirection status code returned for this URL.
+    #[inline]
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+}
+
 /// `Response` represents a response returned by a server.
 #[derive(Debug)]
 pub struct Response {
+    url: Url,
+    version: Version,
     status: StatusCode,
     headers: HeaderMap,
     reader: ResponseReader,
+    redirect_history: Vec<RedirectHop>,
+    informational: Vec<InformationalResponse>,
+    remote_addr: Option<SocketAddr>,
+    alpn_protocol: Option<String>,
+    peer_certificate: Option<PeerCertificate>,
+    extensions: Extensions,
 }
 
 impl Response {
+    pub(crate) fn set_redirect_history(&mut self, redirect_history: Vec<RedirectHop>) {
+        self.redirect_history = redirect_history;
+    }
+
+    pub(crate) fn set_extensions(&mut self, extensions: Extensions) {
+        self.extensions = extensions;
+    }
+
+    pub(crate) fn set_url(&mut self, url: Url) {
+        self.url = url;
+    }
+
+    /// Get the target of a zero-delay `<meta http-equiv="refresh">` tag if this is a successful HTML response.
+    ///
+    /// Only the first `MAX_META_REFRESH_SCAN` bytes of the body of an HTML response are read to be searched, they can
+    /// still be read afterwards.
+    pub(crate) fn meta_refresh(&mut self) -> Result<Option<String>> {
+        let is_html = self.headers.get(CONTENT_TYPE).is_some_and(|value| {
+            let essence = value.as_bytes().split(|&c| c == b';').next().unwrap_or_default();
+            let essence = trim_byte(b' ', essence);
+            essence.eq_ignore_ascii_case(b"text/html") || essence.eq_ignore_ascii_case(b"application/xhtml+xml")
+        });
+        if !self.status.is_success() || !is_html {
+            return Ok(None);
+        }
+        self.reader
+            .read_ahead(MAX_META_REFRESH_SCAN, |_| false)
+            .map_err(Error::from_io)?;
+        Ok(parse_meta_refresh(self.reader.ahead()))
+    }
+
+    /// Get the URL this `Response` was received from, the URL of the last request when redirections were followed.
+    #[inline]
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Get the HTTP version of the status line of this `Response`.
+    ///
+    /// Connections on which an HTTP/1.0 response is received are not reused unless the server sends
+    /// `Connection: keep-alive`.
+    #[inline]
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
     /// Get the status code of this `Response`.
     #[inline]
     pub fn status(&self) -> StatusCode {
@@ -102,12 +337,167 @@ impl Response {
         &self.headers
     }
 
+    /// Get the redirections that were followed before receiving this `Response`, in order.
+    ///
+    /// The slice is empty if no redirection was followed.
+    #[inline]
+    pub fn redirect_history(&self) -> &[RedirectHop] {
+        &self.redirect_history
+    }
+
+    /// Get the interim `1xx` responses which were received before this `Response`, in order.
+    ///
+    /// For instance, servers can send `103 Early Hints` with `Link` headers of resources to preload. With
+    /// redirections, only the interim responses of the last request are kept.
+    #[inline]
+    pub fn informational(&self) -> &[InformationalResponse] {
+        &self.informational
+    }
+
+    /// Get the address of the peer which sent this `Response`.
+    ///
+    /// This is the address the connection was established to, for instance the one which won the race
+    /// between the addresses of the host. When a proxy is used, this is the address of the proxy.
+    #[inline]
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    /// Get the protocol negotiated with ALPN during the TLS handshake of the connection of this `Response`.
+    ///
+    /// `None` is returned for plain HTTP connections, when no protocol was advertised with
+    /// [`alpn_protocols`](crate::RequestBuilder::alpn_protocols) or when the server did not pick one. The
+    /// `native-tls` backend does not support ALPN on every platform.
+    #[inline]
+    pub fn alpn_protocol(&self) -> Option<&str> {
+        self.alpn_protocol.as_deref()
+    }
+
+    /// Get the certificate presented by the server during the TLS handshake of the connection of this `Response`.
+    ///
+    /// `None` is returned for plain HTTP connections. Through a proxy, this is the certificate of the remote host
+    /// and not the one of the proxy. Only the certificate of the server is available, not the rest of its chain.
+    #[inline]
+    pub fn peer_certificate(&self) -> Option<&PeerCertificate> {
+        self.peer_certificate.as_ref()
+    }
+
+    /// Get the values attached to the request of this `Response` with
+    /// [`RequestBuilder::extension`](crate::RequestBuilder::extension).
+    #[inline]
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Get a mutable reference to the values attached to the request of this `Response`.
+    #[inline]
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Get the range of the resource contained in this `Response`.
+    ///
+    /// This is parsed from the `Content-Range` header of `206 Partial Content` and
+    /// `416 Range Not Satisfiable` responses. `None` is returned for any other status, for
+    /// instance when the server ignored the requested range and sent the whole resource with
+    /// a `200 OK`.
+    pub fn content_range(&self) -> Option<ContentRange> {
+        match self.status {
+            StatusCode::PARTIAL_CONTENT | StatusCode::RANGE_NOT_SATISFIABLE => parse_content_range(&self.headers),
+            _ => None,
+        }
+    }
+
+    /// Get the range requests supported by the server for this resource, from the `Accept-Ranges` header.
+    ///
+    /// Combined with [`content_length`](Self::content_length) on the response to a `HEAD` request, this tells
+    /// if a large resource can be downloaded in several parts with range requests.
+    pub fn accept_ranges(&self) -> AcceptRanges {
+        parse_accept_ranges(&self.headers)
+    }
+
+    /// Get the methods supported by the resource, from the `Allow` headers.
+    ///
+    /// Servers send it with the response to an `OPTIONS` request, see [`options`](crate::options), and with
+    /// `405 Method Not Allowed` responses. Tokens which are not valid method names are skipped.
+    pub fn allowed_methods(&self) -> Vec<Method> {
+        self.headers
+            .get_all(ALLOW)
+            .iter()
+            .flat_map(|value| value.as_bytes().split(|&b| b == b','))
+            .map(|token| trim_byte(b' ', trim_byte(b'\t', token)))
+            .filter(|token| !token.is_empty())
+            .filter_map(|token| Method::from_bytes(token).ok())
+            .collect()
+    }
+
+    /// Get the authentication challenges of the `WWW-Authenticate` headers, in order.
+    ///
+    /// Servers usually send them with `401 Unauthorized` responses. A header can hold several challenges,
+    /// and the parsing of a header stops at the first syntax error.
+    pub fn www_authenticate(&self) -> Vec<AuthChallenge> {
+        parse_www_authenticate(&self.headers)
+    }
+
+    /// Get the links of the `Link` headers, by relation type.
+    ///
+    /// APIs often use them for pagination, e.g. `<https://api.example.com/items?page=2>; rel="next"`. Relation
+    /// types are lowercased and the first link of each is kept, the parsing of a header stops at the first syntax
+    /// error.
+    pub fn links(&self) -> HashMap<String, LinkValue> {
+        parse_links(&self.headers)
+    }
+
+    /// Get the length of the body announced by the `Content-Length` header.
+    ///
+    /// For a `HEAD` request, this is the length of the body a `GET` request would receive. If the response is
+    /// compressed, this is the compressed length. `None` is returned if the header is missing or invalid.
+    pub fn content_length(&self) -> Option<u64> {
+        self.headers.get(CONTENT_LENGTH)?.to_str().ok()?.trim().parse().ok()
+    }
+
+    /// Get the byte counts and timings of the connection used by this `Response`.
+    ///
+    /// The counts keep increasing as the body is read. If redirections were followed, only the connection
+    /// of the final response is measured.
+    #[inline]
+    pub fn metrics(&self) -> Metrics {
+        self.reader.metrics()
+    }
+
+    /// Checks if the status code of this `Response` was an informational code, 1xx.
+    #[inline]
+    pub fn is_informational(&self) -> bool {
+        self.status.is_informational()
+    }
+
     /// Checks if the status code of this `Response` was a success code.
     #[inline]
     pub fn is_success(&self) -> bool {
         self.status.is_success()
     }
 
+    /// Checks if the status code of this `Response` was a redirection code, 3xx.
+    ///
+    /// Redirections are followed by default, this is only true for the final response when following them is
+    /// disabled or when its `Location` is missing.
+    #[inline]
+    pub fn is_redirect(&self) -> bool {
+        self.status.is_redirection()
+    }
+
+    /// Checks if the status code of this `Response` was a client error code, 4xx.
+    #[inline]
+    pub fn is_client_error(&self) -> bool {
+        self.status.is_client_error()
+    }
+
+    /// Checks if the status code of this `Response` was a server error code, 5xx.
+    #[inline]
+    pub fn is_server_error(&self) -> bool {
+        self.status.is_server_error()
+    }
+
     /// Returns error variant if the status code was not a success code.
     pub fn error_for_status(self) -> Result<Self> {
         if self.is_success() {
@@ -125,6 +515,51 @@ impl Response {
         (self.status, self.headers, self.reader)
     }
 
+    /// Read the body of this `Response` as a stream of `multipart/*` parts.
+    ///
+    /// The boundary is read from the `Content-Type` header, an error is returned if the response is
+    /// not a multipart response. Parts are parsed as they arrive, see `MultipartReader`.
+    pub fn multipart(self) -> Result<MultipartReader<ResponseReader>> {
+        let boundary = get_boundary(&self.headers).ok_or(InvalidResponseKind::Multipart)?;
+        Ok(MultipartReader::new(self.reader, &boundary))
+    }
+
+    /// Create a `HashingReader` which hashes the body with `H` while it is read, and the handle of its digest.
+    ///
+    /// The digest can be retrieved from the handle once the whole body has been read, for instance after the
+    /// reader has been copied to a file.
+    ///
+    /// This method only exists when the `hash` feature is enabled.
+    #[cfg(feature = "hash")]
+    pub fn hashing_reader<H: digest::Digest>(self) -> (HashingReader<H, ResponseReader>, DigestHandle<H>) {
+        HashingReader::new(self.reader)
+    }
+
+    /// Create a `ChecksumReader` which verifies the body against the `Content-MD5` or `Digest` header.
+    ///
+    /// The checksum is computed over the body as it was received, before its content codings such as gzip are
+    /// decoded, as those headers are defined. The reader still returns the decoded body. Reading the end of the
+    /// body returns an error if the checksum does not match. An error is returned right away if the checksum
+    /// header is malformed.
+    ///
+    /// This method only exists when the `checksum` feature is enabled.
+    #[cfg(feature = "checksum")]
+    pub fn verify_checksum(self) -> Result<ChecksumReader<ResponseReader>> {
+        ChecksumReader::for_response(self.reader, &self.headers)
+    }
+
+    /// Copy every byte read from the body into `sink`, in addition to returning it to the reader.
+    ///
+    /// This is useful to archive the body while it is processed normally, for instance with `text` or `json`.
+    /// The sink receives the body after decompression. An error writing to the sink is returned as a read error.
+    pub fn tee<W>(mut self, sink: W) -> Response
+    where
+        W: Write + Send + 'static,
+    {
+        self.reader.set_tee(Box::new(sink));
+        self
+    }
+
     /// Write the response to any object that implements `Write`.
     #[inline]
     pub fn write_to<W>(self, writer: W) -> Result<u64>
@@ -134,12 +569,81 @@ impl Response {
         self.reader.write_to(writer)
     }
 
+    /// Write the response to `writer`, calling `progress` with the number of bytes written so far after each
+    /// buffer, and return the number of bytes written.
+    ///
+    /// The bytes counted are those of the decoded body, which differ from the `Content-Length` of a compressed
+    /// response.
+    #[inline]
+    pub fn copy_to_with_progress<W, F>(self, writer: &mut W, progress: F) -> Result<u64>
+    where
+        W: Write + ?Sized,
+        F: FnMut(u64),
+    {
+        self.reader.copy_to_with_progress(writer, progress)
+    }
+
+    /// Get the trailer fields sent after the body of a chunked response.
+    ///
+    /// They are only available once the end of the body has been reached by reading from the response, `None`
+    /// is returned before that or if the response is not chunked.
+    #[inline]
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        self.reader.trailers()
+    }
+
+    /// Read and discard the rest of the response, so that its connection can be reused by the next request of
+    /// a `Session`.
+    ///
+    /// This is useful when only the status and headers matter. If more than 64 KiB remain, the connection is
+    /// closed instead of reading them.
+    #[inline]
+    pub fn drain(self) -> Result {
+        self.reader.drain()
+    }
+
+    /// Read the response and convert it to an `http::Response` with the same status, headers and body.
+    ///
+    /// This consumes the body, which is decoded like [`bytes`](Self::bytes) does while the headers are kept as
+    /// received, and so are the extensions. The trailers, the interim responses and the redirection history are
+    /// not carried over.
+    pub fn into_http(mut self) -> Result<http::Response<Vec<u8>>> {
+        let extensions = mem::take(&mut self.extensions);
+        let version = self.version;
+        let (status, headers, reader) = self.split();
+        let mut response = http::Response::new(reader.bytes()?);
+        *response.version_mut() = version;
+        *response.status_mut() = status;
+        *response.headers_mut() = headers;
+        *response.extensions_mut() = extensions;
+        Ok(response)
+    }
+
+    /// Read exactly enough bytes of the body to fill `buf`, failing if they do not all arrive within `timeout`.
+    ///
+    /// This is useful for framed protocols over HTTP. The timeout is applied to the whole read, on top of the
+    /// read timeout of the request which applies again to the next reads. A body which ends before `buf` is
+    /// filled is an error of kind `UnexpectedEof`, and a timeout is an error of kind `TimedOut`, after which the
+    /// response should be dropped.
+    #[inline]
+    pub fn read_exact_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> Result {
+        self.reader.read_exact_timeout(buf, timeout)
+    }
+
     /// Read the response to a `Vec` of bytes.
     #[inline]
     pub fn bytes(self) -> Result<Vec<u8>> {
         self.reader.bytes()
     }
 
+    /// Read the response into the given buffer, replacing its content, and return the number of bytes read.
+    ///
+    /// The capacity of the buffer is kept, reusing it across responses avoids an allocation for each of them.
+    #[inline]
+    pub fn bytes_into(self, buf: &mut Vec<u8>) -> Result<usize> {
+        self.reader.bytes_into(buf)
+    }
+
     /// Read the response to a `String`.
     ///
     /// If the `charsets` feature is enabled, it will try to decode the response using
@@ -154,6 +658,24 @@ impl Response {
         self.reader.text()
     }
 
+    /// Read the response into the given `String`, replacing its content, and return its length in bytes.
+    ///
+    /// The response is decoded like [`text`](Self::text) does and the capacity of the `String` is kept.
+    #[inline]
+    pub fn text_into(self, text: &mut String) -> Result<usize> {
+        self.reader.text_into(text)
+    }
+
+    /// Read at most `max` bytes of the response decoded to a `String`, and whether the text was truncated.
+    ///
+    /// The response is decoded like [`text`](Self::text) does, `max` bounds the length in bytes of the decoded
+    /// text which never ends in the middle of a character. The rest of the body is discarded, which makes this
+    /// method suitable to log bodies of any size.
+    #[inline]
+    pub fn text_truncated(self, max: usize) -> Result<(String, bool)> {
+        self.reader.text_truncated(max)
+    }
+
     /// Read the response to a `String`, decoding with the given `Charset`.
     ///
     /// This will ignore the encoding from the response headers and the default encoding, if any.
@@ -171,6 +693,9 @@ impl Response {
     /// Otherwise, if a default encoding is set it will be used. If there is no default encoding, ISO-8859-1
     /// will be used.
     ///
+    /// With the `xml-charset` feature, the `encoding` of an XML declaration at the start of the body takes
+    /// precedence over the default encoding when the headers contain no charset information.
+    ///
     /// This method only exists when the `charsets` feature is enabled.
     #[cfg(feature = "charsets")]
     pub fn text_reader(self) -> TextReader<BufReader<ResponseReader>> {
@@ -191,11 +716,25 @@ impl Response {
     /// Read the response body to a String using the UTF-8 encoding.
     ///
     /// This method ignores headers and the default encoding.
+    ///
+    /// If the body is not valid UTF-8, [`ErrorKind::Decoding`](crate::ErrorKind::Decoding) is returned with the
+    /// offset of the first invalid byte.
     #[inline]
     pub fn text_utf8(self) -> Result<String> {
         self.reader.text_utf8()
     }
 
+    /// Read the response body to a `String`, mapping each byte to the code point of the same value.
+    ///
+    /// This is the ISO-8859-1 encoding as defined in Unicode, unlike `windows-1252` which maps the bytes from
+    /// `0x80` to `0x9f` to other characters. It ignores headers and the default encoding and never fails to
+    /// decode, which makes it suitable to handle binary data as a string. The bytes can be recovered by
+    /// converting each character back to a `u8`.
+    #[inline]
+    pub fn text_latin1(self) -> Result<String> {
+        self.reader.text_latin1()
+    }
+
     /// Parse the response as a JSON object and return it.
     ///
     /// If the `charsets` feature is enabled, it will try to decode the response using
@@ -227,6 +766,51 @@ impl Response {
     {
         self.reader.json_utf8()
     }
+
+    /// Parse the response as newline-delimited JSON and return an iterator over its values.
+    ///
+    /// Each non-empty line of the body is deserialized as it is read, which suits streaming APIs sending NDJSON
+    /// or JSON Lines. The body is decoded like [`text`](Self::text) does when the `charsets` feature is enabled,
+    /// otherwise it must be UTF-8. The iterator stops after the first error.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "json")]
+    /// # fn main() -> attohttpc::Result {
+    /// let resp = attohttpc::get("http://example.com/events").send()?;
+    /// for event in resp.ndjson::<serde_json::Value>() {
+    ///     println!("{}", event?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "json"))]
+    /// # fn main() {}
+    /// ```
+    ///
+    /// This method only exists when the `json` feature is enabled.
+    #[cfg(feature = "json")]
+    #[inline]
+    pub fn ndjson<T>(self) -> impl Iterator<Item = Result<T>>
+    where
+        T: DeserializeOwned,
+    {
+        self.reader.ndjson()
+    }
+
+    /// Parse the response as an XML document and deserialize its root element.
+    ///
+    /// If the `charsets` feature is enabled, the response is decoded like [`text`](Self::text) does before being
+    /// parsed, otherwise it must be UTF-8.
+    ///
+    /// This method only exists when the `xml` feature is enabled.
+    #[cfg(feature = "xml")]
+    #[inline]
+    pub fn xml<T>(self) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        self.reader.xml()
+    }
 }
 
 impl Read for Response {
@@ -239,9 +823,670 @@ impl Read for Response {
 fn test_read_request_head() {
     let response = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nContent-Type: text/plain\r\n\r\nhello";
     let mut reader = BufReader::new(&response[..]);
-    let (status, headers) = parse_response_head(&mut reader).unwrap();
+    let (version, status, headers) = parse_response_head(&mut reader, DEFAULT_MAX_HEADER_SIZE).unwrap();
+    assert_eq!(version, Version::HTTP_11);
     assert_eq!(status, StatusCode::OK);
     assert_eq!(headers.len(), 2);
     assert_eq!(headers[http::header::CONTENT_LENGTH], "5");
     assert_eq!(headers[http::header::CONTENT_TYPE], "text/plain");
 }
+
+#[cfg(test)]
+fn mock_response(buf: &[u8]) -> Response {
+    let req = PreparedRequest::new(http::Method::GET, "http://google.ca");
+    parse_response(BaseStream::mock(buf.to_vec()), &req).unwrap()
+}
+
+#[test]
+fn test_status_class_predicates() {
+    let classes = |head: &[u8]| {
+        let resp = mock_response(head);
+        [
+            resp.is_informational(),
+            resp.is_success(),
+            resp.is_redirect(),
+            resp.is_client_error(),
+            resp.is_server_error(),
+        ]
+    };
+    assert_eq!(
+        classes(b"HTTP/1.1 101 Switching Protocols\r\n\r\n"),
+        [true, false, false, false, false]
+    );
+    assert_eq!(
+        classes(b"HTTP/1.1 204 No Content\r\n\r\n"),
+        [false, true, false, false, false]
+    );
+    assert_eq!(
+        classes(b"HTTP/1.1 304 Not Modified\r\n\r\n"),
+        [false, false, true, false, false]
+    );
+    assert_eq!(
+        classes(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"),
+        [false, false, false, true, false]
+    );
+    assert_eq!(
+        classes(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n"),
+        [false, false, false, false, true]
+    );
+}
+
+#[test]
+fn test_copy_to_with_progress() {
+    let body = vec![b'x'; 100_000];
+    let mut buf = b"HTTP/1.1 200 OK\r\nContent-Length: 100000\r\n\r\n".to_vec();
+    buf.extend_from_slice(&body);
+
+    let mut output = Vec::new();
+    let mut reports = Vec::new();
+    let n = mock_response(&buf)
+        .copy_to_with_progress(&mut output, |written| reports.push(written))
+        .unwrap();
+
+    assert_eq!(n, 100_000);
+    assert_eq!(output, body);
+    assert!(reports.len() > 1);
+    assert!(reports.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(reports.last(), Some(&100_000));
+}
+
+#[test]
+fn test_allowed_methods() {
+    let resp = mock_response(b"HTTP/1.1 204 No Content\r\nAllow: GET, POST, OPTIONS\r\n\r\n");
+    assert_eq!(resp.allowed_methods(), [Method::GET, Method::POST, Method::OPTIONS]);
+
+    let resp = mock_response(
+        b"HTTP/1.1 405 Method Not Allowed\r\nAllow: GET,,HEAD , bad method\r\nAllow: PROPFIND\r\nContent-Length: 0\r\n\r\n",
+    );
+    let methods = resp.allowed_methods();
+    assert_eq!(
+        methods,
+        [Method::GET, Method::HEAD, Method::from_bytes(b"PROPFIND").unwrap()]
+    );
+
+    let resp = mock_response(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+    assert!(resp.allowed_methods().is_empty());
+}
+
+#[test]
+fn test_content_range_partial_content() {
+    let resp =
+        mock_response(b"HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 2-4/10\r\nContent-Length: 3\r\n\r\ncde");
+    let range = resp.content_range().unwrap();
+    assert_eq!(range.range(), Some((2, 4)));
+    assert_eq!(range.complete_length(), Some(10));
+    assert_eq!(resp.text().unwrap(), "cde");
+}
+
+#[test]
+fn test_content_range_range_ignored() {
+    let resp = mock_response(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nabcdefghij");
+    assert_eq!(resp.content_range(), None);
+    assert_eq!(resp.text().unwrap(), "abcdefghij");
+}
+
+#[test]
+fn test_into_http() {
+    let resp = mock_response(b"HTTP/1.1 404 Not Found\r\nContent-Length: 7\r\nX-Id: a\r\nX-Id: b\r\n\r\nmissing");
+    let http_resp = resp.into_http().unwrap();
+    assert_eq!(http_resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(http_resp.version(), http::Version::HTTP_11);
+    assert_eq!(http_resp.headers()[http::header::CONTENT_LENGTH], "7");
+    let ids: Vec<_> = http_resp.headers().get_all("x-id").iter().collect();
+    assert_eq!(ids, ["a", "b"]);
+    assert_eq!(http_resp.body(), b"missing");
+}
+
+#[test]
+fn test_version() {
+    let resp = mock_response(b"HTTP/1.0 200 OK\r\nContent-Length: 2\r\n\r\nok");
+    assert_eq!(resp.version(), Version::HTTP_10);
+    assert_eq!(resp.into_http().unwrap().version(), Version::HTTP_10);
+
+    let resp = mock_response(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+    assert_eq!(resp.version(), Version::HTTP_11);
+}
+
+#[test]
+fn test_text_utf8_invalid_offset() {
+    let resp = mock_response(b"HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\nqu\xC3\xA9bec \xFFok");
+    match resp.text_utf8().unwrap_err().kind() {
+        ErrorKind::Decoding { charset, offset } => {
+            assert_eq!(*charset, "UTF-8");
+            assert_eq!(*offset, 8);
+        }
+        err => panic!("unexpected error {:?}", err),
+    }
+
+    // A multi-byte sequence cut short is reported at its first byte.
+    let resp = mock_response(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nabc\xE2");
+    assert!(matches!(
+        resp.text_utf8().unwrap_err().kind(),
+        ErrorKind::Decoding { offset: 3, .. }
+    ));
+}
+
+#[test]
+#[cfg(feature = "xml-charset")]
+fn test_text_xml_declaration_charset() {
+    let body = b"<?xml version=\"1.0\" encoding=\"windows-1251\"?><a>\xEF\xF0\xE8</a>";
+    let mut raw = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/xml\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    raw.extend_from_slice(body);
+    let resp = mock_response(&raw);
+    assert_eq!(
+        resp.text().unwrap(),
+        "<?xml version=\"1.0\" encoding=\"windows-1251\"?><a>\u{43f}\u{440}\u{438}</a>"
+    );
+
+    // The charset of the headers takes precedence over the declaration.
+    let mut raw = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/xml; charset=ISO-8859-1\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    raw.extend_from_slice(body);
+    let resp = mock_response(&raw);
+    assert!(resp.text().unwrap().ends_with("<a>\u{ef}\u{f0}\u{e8}</a>"));
+}
+
+#[test]
+#[cfg(feature = "xml-charset")]
+fn test_text_xml_declaration_charset_split() {
+    // The declaration arrives in several chunks, the body is read until its end.
+    let resp = mock_response(
+        b"HTTP/1.1 200 OK\r\nContent-Type: application/xml\r\nTransfer-Encoding: chunked\r\n\r\n\
+        3\r\n<?x\r\n10\r\nml version=\"1.0\"\r\n1a\r\n encoding=\"windows-1251\"?>\r\n\
+        a\r\n<a>\xEF\xF0\xE8</a>\r\n0\r\n\r\n",
+    );
+    assert_eq!(
+        resp.text().unwrap(),
+        "<?xml version=\"1.0\" encoding=\"windows-1251\"?><a>\u{43f}\u{440}\u{438}</a>"
+    );
+}
+
+#[test]
+#[cfg(feature = "xml")]
+fn test_xml_round_trip() {
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Item {
+        name: String,
+        tags: Vec<String>,
+        count: u32,
+    }
+
+    let item = Item {
+        name: "caf\u{e9}".to_string(),
+        tags: vec!["http".to_string(), "xml".to_string()],
+        count: 3,
+    };
+    let mut builder = crate::RequestBuilder::new(http::Method::POST, "http://localhost")
+        .xml(&item)
+        .unwrap();
+    let mut inspector = builder.inspect();
+    assert_eq!(
+        inspector.headers()[http::header::CONTENT_TYPE],
+        "application/xml; charset=utf-8"
+    );
+    let body = inspector.body().0.clone();
+
+    let mut raw = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/xml; charset=utf-8\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    raw.extend_from_slice(&body);
+    assert_eq!(mock_response(&raw).xml::<Item>().unwrap(), item);
+
+    let resp = mock_response(b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\n<Item>");
+    assert!(matches!(resp.xml::<Item>().unwrap_err().kind(), ErrorKind::XmlDe(_)));
+}
+
+#[test]
+#[cfg(feature = "xml-charset")]
+fn test_text_xml_declaration_read_error() {
+    // The error of the read looking for a declaration is not lost.
+    let resp = mock_response(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\nabc\r\n0\r\n\r\n");
+    let err = resp.text().unwrap_err();
+    assert!(format!("{:?}", err).contains("ChunkSize"), "{:?}", err);
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn test_ndjson() {
+    // The lines are split across chunks, which are read separately from the socket.
+    let resp = mock_response(
+        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+          d\r\n{\"id\": 1}\n{\"i\r\n\
+          11\r\nd\": 2}\r\n\n{\"id\": 3\r\n\
+          2\r\n}\n\r\n0\r\n\r\n",
+    );
+    let values: Vec<serde_json::Value> = resp.ndjson().collect::<Result<_>>().unwrap();
+    assert_eq!(
+        values,
+        [
+            serde_json::json!({"id": 1}),
+            serde_json::json!({"id": 2}),
+            serde_json::json!({"id": 3})
+        ]
+    );
+
+    let resp = mock_response(b"HTTP/1.1 200 OK\r\nContent-Length: 17\r\n\r\n1\nnot json\n2\n3\n");
+    let mut values = resp.ndjson::<u32>();
+    assert_eq!(values.next().unwrap().unwrap(), 1);
+    assert!(matches!(values.next().unwrap().unwrap_err().kind(), ErrorKind::Json(_)));
+    assert!(values.next().is_none());
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn test_ndjson_max_line_size() {
+    let mut body = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\n1\n2\n\r\n".to_vec();
+    for _ in 0..16 {
+        body.extend_from_slice(b"400\r\n");
+        body.extend_from_slice(&[b'9'; 1024]);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(b"0\r\n\r\n");
+
+    let req = crate::RequestBuilder::new(http::Method::GET, "http://google.ca")
+        .max_line_size(4096)
+        .prepare();
+    let resp = parse_response(BaseStream::mock(body), &req).unwrap();
+    let mut values = resp.ndjson::<f64>();
+    assert_eq!(values.next().unwrap().unwrap(), 1.0);
+    assert_eq!(values.next().unwrap().unwrap(), 2.0);
+    assert!(matches!(
+        values.next().unwrap().unwrap_err().kind(),
+        ErrorKind::InvalidResponse(InvalidResponseKind::LineTooLong)
+    ));
+    assert!(values.next().is_none());
+
+    // A line as long as the limit is accepted.
+    let req = crate::RequestBuilder::new(http::Method::GET, "http://google.ca")
+        .max_line_size(4)
+        .prepare();
+    let resp = parse_response(
+        BaseStream::mock(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n1234\n".to_vec()),
+        &req,
+    )
+    .unwrap();
+    assert_eq!(resp.ndjson::<u32>().collect::<Result<Vec<_>>>().unwrap(), [1234]);
+}
+
+#[test]
+fn test_bytes_into_reuses_buffer() {
+    let mut buf = Vec::with_capacity(64);
+    let resp = mock_response(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nabcdefghij");
+    assert_eq!(resp.bytes_into(&mut buf).unwrap(), 10);
+    assert_eq!(buf, b"abcdefghij");
+
+    let capacity = buf.capacity();
+    let resp = mock_response(b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nxyz");
+    assert_eq!(resp.bytes_into(&mut buf).unwrap(), 3);
+    assert_eq!(buf, b"xyz");
+    assert_eq!(buf.capacity(), capacity);
+}
+
+#[test]
+fn test_text_into_reuses_string() {
+    let mut text = String::new();
+    let resp = mock_response(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+    assert_eq!(resp.text_into(&mut text).unwrap(), 5);
+    assert_eq!(text, "hello");
+
+    let resp = mock_response(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nhi");
+    assert_eq!(resp.text_into(&mut text).unwrap(), 2);
+    assert_eq!(text, "hi");
+}
+
+#[test]
+fn test_text_truncated() {
+    let body = "aé🦀";
+    let mut buf = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    buf.extend(body.as_bytes());
+
+    // The crab takes 4 bytes, it does not fit in 5.
+    assert_eq!(mock_response(&buf).text_truncated(5).unwrap(), ("aé".to_string(), true));
+    assert_eq!(mock_response(&buf).text_truncated(2).unwrap(), ("a".to_string(), true));
+    assert_eq!(mock_response(&buf).text_truncated(0).unwrap(), (String::new(), true));
+    assert_eq!(
+        mock_response(&buf).text_truncated(7).unwrap(),
+        (body.to_string(), false)
+    );
+    assert_eq!(
+        mock_response(&buf).text_truncated(100).unwrap(),
+        (body.to_string(), false)
+    );
+}
+
+#[test]
+fn test_text_latin1() {
+    let body: Vec<u8> = (0..=255).collect();
+    let mut buf = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 256\r\n\r\n".to_vec();
+    buf.extend(&body);
+
+    // The charset of the headers is ignored.
+    let text = mock_response(&buf).text_latin1().unwrap();
+    assert_eq!(text.chars().count(), 256);
+    for (byte, c) in body.iter().zip(text.chars()) {
+        assert_eq!(c as u32, u32::from(*byte));
+    }
+    assert_eq!(text.chars().map(|c| c as u8).collect::<Vec<_>>(), body);
+}
+
+#[test]
+#[cfg(feature = "charsets")]
+fn test_text_utf16le() {
+    let body: Vec<u8> = "québec 🦀"
+        .encode_utf16()
+        .flat_map(|unit| unit.to_le_bytes().to_vec())
+        .collect();
+    let mut buf = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-16le\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    buf.extend(body);
+
+    assert_eq!(mock_response(&buf).text().unwrap(), "québec 🦀");
+}
+
+#[test]
+fn test_multipart() {
+    let body = b"--frame\r\nContent-Type: text/plain\r\n\r\nfirst\r\n\
+        --frame\r\nContent-Type: text/html\r\n\r\n<p>second</p>\r\n--frame--\r\n";
+    let mut buf = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: multipart/mixed; boundary=frame\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    )
+    .into_bytes();
+    buf.extend(&body[..]);
+
+    let parts: Vec<_> = mock_response(&buf).multipart().unwrap().collect::<Result<_>>().unwrap();
+    assert_eq!(parts.len(), 2);
+    assert_eq!(parts[0].headers()[http::header::CONTENT_TYPE], "text/plain");
+    assert_eq!(parts[0].body(), b"first");
+    assert_eq!(parts[1].headers()[http::header::CONTENT_TYPE], "text/html");
+    assert_eq!(parts[1].body(), b"<p>second</p>");
+}
+
+#[test]
+fn test_multipart_not_multipart() {
+    let resp = mock_response(b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 0\r\n\r\n");
+    assert!(resp.multipart().is_err());
+}
+
+#[test]
+#[cfg(feature = "hash")]
+fn test_hashing_reader() {
+    let resp = mock_response(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+    let (mut reader, digest) = resp.hashing_reader::<sha2::Sha256>();
+
+    let mut file = Vec::new();
+    io::copy(&mut reader, &mut file).unwrap();
+    drop(reader);
+    assert_eq!(file, b"hello");
+    let hex: String = digest.get().unwrap().iter().map(|b| format!("{:02x}", b)).collect();
+    assert_eq!(hex, "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
+}
+
+#[test]
+#[cfg(feature = "checksum")]
+fn test_verify_checksum() {
+    let resp =
+        mock_response(b"HTTP/1.1 200 OK\r\nContent-MD5: XUFAKrxLKna5cZ2REBfFkg==\r\nContent-Length: 5\r\n\r\nhello");
+    let mut reader = resp.verify_checksum().unwrap();
+
+    let mut body = String::new();
+    reader.read_to_string(&mut body).unwrap();
+    assert_eq!(body, "hello");
+    assert_eq!(
+        reader.digest().map(base64::encode).as_deref(),
+        Some("XUFAKrxLKna5cZ2REBfFkg==")
+    );
+}
+
+#[test]
+#[cfg(feature = "checksum")]
+fn test_verify_checksum_mismatch() {
+    let resp = mock_response(
+        b"HTTP/1.1 200 OK\r\nDigest: SHA-256=LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=\r\n\
+        Content-Length: 5\r\n\r\nhellO",
+    );
+    let mut reader = resp.verify_checksum().unwrap();
+
+    let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+    assert!(err.to_string().contains("checksum mismatch"));
+    assert!(reader.digest().is_some());
+}
+
+#[test]
+#[cfg(all(feature = "checksum", feature = "compress"))]
+fn test_verify_checksum_gzip() {
+    use flate2::{write::GzEncoder, Compression};
+    use sha2::{Digest, Sha256};
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(b"hello").unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    let mut buf = Vec::new();
+    write!(
+        buf,
+        "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nDigest: SHA-256={}\r\nContent-Length: {}\r\n\r\n",
+        base64::encode(Sha256::digest(&compressed)),
+        compressed.len()
+    )
+    .unwrap();
+    buf.extend(&compressed);
+
+    // The checksum covers the body as received, the reader still decodes it.
+    let mut reader = mock_response(&buf).verify_checksum().unwrap();
+    let mut body = String::new();
+    reader.read_to_string(&mut body).unwrap();
+    assert_eq!(body, "hello");
+    assert_eq!(reader.digest(), Some(&Sha256::digest(&compressed)[..]));
+}
+
+#[cfg(test)]
+struct SharedSink(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl Write for SharedSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_tee() {
+    use std::sync::{Arc, Mutex};
+
+    let sink = Arc::new(Mutex::new(Vec::new()));
+    let resp = mock_response(b"HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\nhello world");
+    let text = resp.tee(SharedSink(sink.clone())).text().unwrap();
+
+    assert_eq!(text, "hello world");
+    assert_eq!(*sink.lock().unwrap(), text.as_bytes());
+}
+
+#[test]
+fn test_meta_refresh_scan() {
+    use std::sync::{Arc, Mutex};
+
+    let html = format!(
+        "<html>{}<meta http-equiv=\"refresh\" content=\"0; url=/next\"></html>",
+        " ".repeat(MAX_META_REFRESH_SCAN)
+    );
+    let mut buf = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\n\r\n",
+        html.len()
+    )
+    .into_bytes();
+    buf.extend(html.as_bytes());
+
+    // A tag past the searched prefix is ignored, the whole body can still be read.
+    let mut resp = mock_response(&buf);
+    assert_eq!(resp.meta_refresh().unwrap(), None);
+    let sink = Arc::new(Mutex::new(Vec::new()));
+    let text = resp.tee(SharedSink(sink.clone())).text().unwrap();
+    assert_eq!(text, html);
+    assert_eq!(*sink.lock().unwrap(), html.as_bytes());
+
+    let html = "<meta http-equiv=\"refresh\" content=\"0; url=/next\">";
+    let mut resp = mock_response(
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\n\r\n{}",
+            html.len(),
+            html
+        )
+        .as_bytes(),
+    );
+    assert_eq!(resp.meta_refresh().unwrap().as_deref(), Some("/next"));
+    assert_eq!(resp.text().unwrap(), html);
+}
+
+#[test]
+fn test_trailers() {
+    let mut resp = mock_response(
+        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nTrailer: X-Checksum\r\n\r\n5\r\nhello\r\n0\r\nX-Checksum: abc\r\n\r\n",
+    );
+    assert!(resp.trailers().is_none());
+
+    let mut body = String::new();
+    resp.read_to_string(&mut body).unwrap();
+    assert_eq!(body, "hello");
+    assert_eq!(resp.trailers().unwrap()["x-checksum"], "abc");
+    assert!(!resp.headers().contains_key("x-checksum"));
+}
+
+#[test]
+#[cfg(feature = "compress")]
+fn test_trailers_gzip() {
+    use flate2::{write::GzEncoder, Compression};
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(b"hello").unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    let mut buf = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nContent-Encoding: gzip\r\n\r\n".to_vec();
+    write!(buf, "{:x}\r\n", compressed.len()).unwrap();
+    buf.extend(&compressed);
+    buf.extend(b"\r\n0\r\nX-Checksum: abc\r\n\r\n");
+
+    let mut resp = mock_response(&buf);
+    let mut body = String::new();
+    resp.read_to_string(&mut body).unwrap();
+    assert_eq!(body, "hello");
+    assert_eq!(resp.trailers().unwrap()["x-checksum"], "abc");
+}
+
+#[test]
+fn test_parse_headers_strict() {
+    fn parse(head: &[u8]) -> Result<(Version, StatusCode, HeaderMap)> {
+        parse_response_head(&mut BufReader::new(head), DEFAULT_MAX_HEADER_SIZE)
+    }
+
+    assert!(parse(b"HTTP/1.1 200 OK\r\nX-Test: a\tb\r\n\r\n").is_ok());
+
+    let invalid: &[&[u8]] = &[
+        b"HTTP/1.1 200 OK\r\nX-Test: a\r\n folded\r\n\r\n",
+        b"HTTP/1.1 200 OK\r\nX-Test: a\r\n\tfolded\r\n\r\n",
+        b"HTTP/1.1 200 OK\r\nX-Test: a\nX-Other: b\r\n\r\n",
+        b"HTTP/1.1 200 OK\r\nX-Test: a\r\n\n",
+        b"HTTP/1.1 200 OK\r\nX-Test: a\rb\r\n\r\n",
+        b"HTTP/1.1 200 OK\r\nX-Test: a\0b\r\n\r\n",
+        b"HTTP/1.1 200 OK\r\nX-Test: a\x1bb\r\n\r\n",
+    ];
+    for head in invalid {
+        let err = parse(head).unwrap_err();
+        assert!(
+            matches!(err.kind(), ErrorKind::InvalidResponse(InvalidResponseKind::Header)),
+            "{:?}",
+            String::from_utf8_lossy(head)
+        );
+    }
+}
+
+#[test]
+fn test_headers_too_large() {
+    fn parse(head: &[u8], max_size: usize) -> Result<(Version, StatusCode, HeaderMap)> {
+        parse_response_head(&mut BufReader::new(head), max_size)
+    }
+    fn is_too_large(res: Result<(Version, StatusCode, HeaderMap)>) -> bool {
+        matches!(
+            res.unwrap_err().kind(),
+            ErrorKind::InvalidResponse(InvalidResponseKind::HeadersTooLarge)
+        )
+    }
+
+    let mut head = b"HTTP/1.1 200 OK\r\n".to_vec();
+    for i in 0..50 {
+        head.extend(format!("X-Header-{}: {}\r\n", i, "a".repeat(1000)).as_bytes());
+    }
+    head.extend(b"\r\n");
+    assert!(parse(&head, head.len()).is_ok());
+    assert!(is_too_large(parse(&head, head.len() - 1)));
+    assert!(is_too_large(parse(&head, DEFAULT_MAX_HEADER_SIZE / 2)));
+
+    // A single line longer than the line limit.
+    let head = format!("HTTP/1.1 200 OK\r\nX-Header: {}\r\n\r\n", "a".repeat(20 * 1024));
+    assert!(is_too_large(parse(head.as_bytes(), DEFAULT_MAX_HEADER_SIZE)));
+
+    let mut head = b"HTTP/1.1 200 OK\r\n".to_vec();
+    for i in 0..=MAX_HEADER_COUNT {
+        head.extend(format!("X-Header-{}: a\r\n", i).as_bytes());
+    }
+    head.extend(b"\r\n");
+    assert!(is_too_large(parse(&head, DEFAULT_MAX_HEADER_SIZE)));
+}
+
+#[test]
+fn test_max_header_size_setting() {
+    let req = crate::RequestBuilder::new(http::Method::GET, "http://google.ca")
+        .max_header_size(32)
+        .prepare();
+    let stream = BaseStream::mock(b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\n".to_vec());
+    let err = parse_response(stream, &req).unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::InvalidResponse(InvalidResponseKind::HeadersTooLarge)
+    ));
+}
+
+#[test]
+fn test_informational_responses() {
+    let resp = mock_response(
+        b"HTTP/1.1 100 Continue\r\n\r\n\
+        HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\n\
+        HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello",
+    );
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let informational = resp.informational();
+    assert_eq!(informational.len(), 2);
+    assert_eq!(informational[0].status(), StatusCode::CONTINUE);
+    assert_eq!(informational[1].status().as_u16(), 103);
+    assert_eq!(informational[1].headers()["link"], "</style.css>; rel=preload");
+    assert!(!resp.headers().contains_key("link"));
+    assert_eq!(resp.text().unwrap(), "hello");
+}
+
+#[test]
+fn test_switching_protocols_is_final() {
+    let resp = mock_response(b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n");
+    assert_eq!(resp.status(), StatusCode::SWITCHING_PROTOCOLS);
+    assert!(resp.informational().is_empty());
+}
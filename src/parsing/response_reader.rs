@@ -1,14 +1,33 @@
-#[cfg(any(feature = "charsets", feature = "json"))]
+#[cfg(any(feature = "xml-charset", feature = "json"))]
+use std::io::BufRead;
+#[cfg(any(feature = "charsets", feature = "json", feature = "xml"))]
 use std::io::BufReader;
 use std::io::{self, Read, Write};
+use std::mem;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use http::header::HeaderMap;
-#[cfg(feature = "json")]
+#[cfg(any(feature = "json", feature = "xml"))]
 use serde::de::DeserializeOwned;
 
-use crate::error::Result;
+#[cfg(feature = "json")]
+use crate::error::InvalidResponseKind;
+use crate::error::{Error, ErrorKind, Result};
+use crate::metrics::{Metrics, Recorder};
 use crate::parsing::compressed_reader::CompressedReader;
+#[cfg(feature = "checksum")]
+use crate::parsing::compressed_reader::RawTap;
 use crate::request::PreparedRequest;
+use crate::skip_debug::SkipDebug;
+
+/// Largest remainder of a body read by `drain` to reuse the connection, larger bodies close it instead.
+const MAX_DRAIN_LEN: u64 = 64 * 1024;
+/// Largest remainder of a body read when a partially read response is dropped.
+const MAX_DROP_DRAIN_LEN: u64 = 4 * 1024;
+/// Default maximum length of a line read by the line-based readers, such as `ndjson`.
+#[cfg(feature = "json")]
+pub const DEFAULT_MAX_LINE_SIZE: usize = 1024 * 1024;
 
 #[cfg(feature = "charsets")]
 use {
@@ -21,20 +40,64 @@ use {
     http::header::CONTENT_TYPE,
 };
 
+#[cfg(feature = "charsets")]
+fn header_charset(headers: &HeaderMap) -> Option<Charset> {
+    let bytes = headers.get(CONTENT_TYPE)?.as_bytes();
+    let scol = bytes.iter().position(|&b| b == b';')?;
+    let rhs = trim_byte(b' ', &bytes[scol + 1..]);
+    if rhs.starts_with(b"charset=") {
+        Encoding::for_label(&rhs[8..])
+    } else {
+        None
+    }
+}
+
 #[cfg(feature = "charsets")]
 fn get_charset(headers: &HeaderMap, default_charset: Option<Charset>) -> Charset {
-    if let Some(value) = headers.get(CONTENT_TYPE) {
-        let bytes = value.as_bytes();
-        if let Some(scol) = bytes.iter().position(|&b| b == b';') {
-            let rhs = trim_byte(b' ', &bytes[scol + 1..]);
-            if rhs.starts_with(b"charset=") {
-                if let Some(enc) = Encoding::for_label(&rhs[8..]) {
-                    return enc;
-                }
-            }
-        }
+    header_charset(headers)
+        .or(default_charset)
+        .unwrap_or(charsets::WINDOWS_1252)
+}
+
+/// Maximum number of bytes read at the start of the body to find the end of an XML declaration.
+#[cfg(feature = "xml-charset")]
+const MAX_XML_DECLARATION_LEN: usize = 1024;
+
+/// Get the charset from the `encoding` attribute of the XML declaration at the start of `prefix`, if any.
+#[cfg(feature = "xml-charset")]
+fn xml_declaration_charset(prefix: &[u8]) -> Option<Charset> {
+    fn skip_ws(buf: &[u8]) -> &[u8] {
+        buf.iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .map_or(&[], |n| &buf[n..])
+    }
+
+    let decl = prefix.strip_prefix(b"<?xml")?;
+    let end = decl.windows(2).position(|w| w == b"?>")?;
+    let decl = &decl[..end];
+    let pos = decl.windows(8).position(|w| w == b"encoding")?;
+    let rest = skip_ws(skip_ws(&decl[pos + 8..]).strip_prefix(b"=")?);
+    let quote = *rest.first().filter(|&&q| q == b'"' || q == b'\'')?;
+    let value = &rest[1..];
+    let end = value.iter().position(|&b| b == quote)?;
+    Encoding::for_label(&value[..end])
+}
+
+/// Read a line into `buf`, replacing its content, failing instead of buffering more than `max_len` bytes.
+#[cfg(feature = "json")]
+fn read_limited_line<R>(reader: &mut R, buf: &mut Vec<u8>, max_len: usize) -> Result<usize>
+where
+    R: BufRead,
+{
+    buf.clear();
+    let n = reader
+        .take(max_len as u64 + 1)
+        .read_until(b'\n', buf)
+        .map_err(Error::from_io)?;
+    if n > max_len && !buf.ends_with(b"\n") {
+        return Err(InvalidResponseKind::LineTooLong.into());
     }
-    default_charset.unwrap_or(charsets::WINDOWS_1252)
+    Ok(n)
 }
 
 /// The `ResponseReader` is used to read the body of a response.
@@ -50,8 +113,20 @@ fn get_charset(headers: &HeaderMap, default_charset: Option<Charset>) -> Charset
 #[derive(Debug)]
 pub struct ResponseReader {
     inner: CompressedReader,
+    tee: Option<SkipDebug<Box<dyn Write + Send>>>,
+    recorder: Arc<Recorder>,
+    read_timeout: Option<Duration>,
     #[cfg(feature = "charsets")]
     charset: Charset,
+    #[cfg(feature = "xml-charset")]
+    sniff_xml_charset: bool,
+    // Start of the decoded body read ahead, returned by the next reads.
+    ahead: io::Cursor<Vec<u8>>,
+    // Error of the read done to look for an XML declaration, returned once the bytes read ahead are consumed.
+    #[cfg(feature = "xml-charset")]
+    pending_error: Option<SkipDebug<io::Error>>,
+    #[cfg(feature = "json")]
+    max_line_size: usize,
 }
 
 impl ResponseReader {
@@ -60,16 +135,96 @@ impl ResponseReader {
         headers: &HeaderMap,
         request: &PreparedRequest<B>,
         reader: CompressedReader,
+        recorder: Arc<Recorder>,
     ) -> ResponseReader {
         ResponseReader {
             inner: reader,
-            charset: get_charset(&headers, request.base_settings.default_charset),
+            tee: None,
+            recorder,
+            read_timeout: request.base_settings.read_timeout,
+            charset: get_charset(headers, request.base_settings.default_charset),
+            #[cfg(feature = "xml-charset")]
+            sniff_xml_charset: header_charset(headers).is_none(),
+            ahead: io::Cursor::new(Vec::new()),
+            #[cfg(feature = "xml-charset")]
+            pending_error: None,
+            #[cfg(feature = "json")]
+            max_line_size: request.base_settings.max_line_size,
         }
     }
 
     #[cfg(not(feature = "charsets"))]
-    pub(crate) fn new<B>(_: &HeaderMap, _: &PreparedRequest<B>, reader: CompressedReader) -> ResponseReader {
-        ResponseReader { inner: reader }
+    pub(crate) fn new<B>(
+        _: &HeaderMap,
+        request: &PreparedRequest<B>,
+        reader: CompressedReader,
+        recorder: Arc<Recorder>,
+    ) -> ResponseReader {
+        ResponseReader {
+            inner: reader,
+            tee: None,
+            recorder,
+            read_timeout: request.base_settings.read_timeout,
+            ahead: io::Cursor::new(Vec::new()),
+            #[cfg(feature = "json")]
+            max_line_size: request.base_settings.max_line_size,
+        }
+    }
+
+    /// Get the byte counts and timings of the connection used by this response.
+    ///
+    /// The counts keep increasing as the body is read, and the total time is known once the end is reached.
+    pub fn metrics(&self) -> Metrics {
+        self.recorder.metrics()
+    }
+
+    /// Get the trailer fields sent after the body of a chunked response.
+    ///
+    /// They are only available once the end of the body has been reached, `None` is returned before that or
+    /// if the response is not chunked.
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        self.inner.trailers()
+    }
+
+    /// Read the decoded body ahead, until `done` returns true for the bytes read so far or `max` bytes were read.
+    ///
+    /// The bytes are kept to be returned by the next reads, which is when they are written to the tee.
+    pub(crate) fn read_ahead<F>(&mut self, max: usize, done: F) -> io::Result<()>
+    where
+        F: Fn(&[u8]) -> bool,
+    {
+        let pos = self.ahead.position() as usize;
+        let mut ahead = mem::take(self.ahead.get_mut()).split_off(pos);
+        let mut buf = [0; 4096];
+        let res = loop {
+            if ahead.len() >= max || done(&ahead) {
+                break Ok(());
+            }
+            let len = buf.len().min(max - ahead.len());
+            match self.inner.read(&mut buf[..len]) {
+                Ok(0) => break Ok(()),
+                Ok(n) => ahead.extend_from_slice(&buf[..n]),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => break Err(err),
+            }
+        };
+        self.ahead = io::Cursor::new(ahead);
+        res
+    }
+
+    /// Get the bytes read ahead which were not returned yet.
+    pub(crate) fn ahead(&self) -> &[u8] {
+        &self.ahead.get_ref()[self.ahead.position() as usize..]
+    }
+
+    /// Call `tap` with the bytes of the body as they were received, before their content codings are decoded.
+    #[cfg(feature = "checksum")]
+    pub(crate) fn set_raw_tap(&mut self, tap: RawTap) {
+        self.inner.set_raw_tap(tap);
+    }
+
+    pub(crate) fn set_tee(&mut self, sink: Box<dyn Write + Send>) {
+        self.tee = Some(SkipDebug(sink));
     }
 
     /// Write the response to any object that implements `Write`.
@@ -77,17 +232,152 @@ impl ResponseReader {
     where
         W: Write,
     {
-        let n = io::copy(&mut self.inner, &mut writer)?;
+        let n = io::copy(&mut self, &mut writer)?;
         Ok(n)
     }
 
+    /// Write the response to `writer`, calling `progress` with the number of bytes written so far after each
+    /// buffer.
+    pub fn copy_to_with_progress<W, F>(mut self, writer: &mut W, mut progress: F) -> Result<u64>
+    where
+        W: Write + ?Sized,
+        F: FnMut(u64),
+    {
+        let mut buf = [0; 8 * 1024];
+        let mut written = 0;
+        loop {
+            let n = match self.read(&mut buf) {
+                Ok(0) => return Ok(written),
+                Ok(n) => n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(Error::from_io(err)),
+            };
+            writer.write_all(&buf[..n])?;
+            written += n as u64;
+            progress(written);
+        }
+    }
+
+    /// Read and discard the rest of the response, so that its connection can be reused by the next request of
+    /// a `Session`.
+    ///
+    /// If more than 64 KiB remain, the connection is closed instead of reading them.
+    pub fn drain(mut self) -> Result {
+        self.inner.drain(MAX_DRAIN_LEN)?;
+        Ok(())
+    }
+
+    /// Read exactly enough bytes of the body to fill `buf`, failing if they do not all arrive within `timeout`.
+    ///
+    /// A body which ends before `buf` is filled is an error of kind `UnexpectedEof`, and a timeout is an error of
+    /// kind `TimedOut`. After an error, the content of `buf` is unspecified. The timeout can only be enforced
+    /// between reads on streams provided by the user.
+    pub fn read_exact_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> Result {
+        let deadline = Instant::now() + timeout;
+        let res = self.read_until(buf, deadline);
+        // Restore the timeout of the other reads, unless the stream has been released.
+        self.inner.set_read_timeout(self.read_timeout)?;
+        res
+    }
+
+    fn read_until(&mut self, mut buf: &mut [u8], deadline: Instant) -> Result {
+        while !buf.is_empty() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::from_secs(0) {
+                return Err(io::Error::from(io::ErrorKind::TimedOut).into());
+            }
+            self.inner.set_read_timeout(Some(remaining))?;
+            match self.read(buf) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(n) => buf = &mut buf[n..],
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    return Err(io::Error::from(io::ErrorKind::TimedOut).into())
+                }
+                Err(err) => return Err(Error::from_io(err)),
+            }
+        }
+        Ok(())
+    }
+
     /// Read the response to a `Vec` of bytes.
     pub fn bytes(self) -> Result<Vec<u8>> {
         let mut buf = Vec::new();
-        self.write_to(&mut buf)?;
+        self.bytes_into(&mut buf)?;
         Ok(buf)
     }
 
+    /// Read the response into the given buffer, replacing its content, and return the number of bytes read.
+    ///
+    /// The capacity of the buffer is kept, reusing it across responses avoids an allocation for each of them.
+    pub fn bytes_into(self, buf: &mut Vec<u8>) -> Result<usize> {
+        buf.clear();
+        self.write_to(&mut *buf)?;
+        Ok(buf.len())
+    }
+
+    /// Read the response into the given `String`, replacing its content, and return its length in bytes.
+    ///
+    /// The response is decoded like [`text`](Self::text) does and the capacity of the `String` is kept.
+    pub fn text_into(self, text: &mut String) -> Result<usize> {
+        text.clear();
+        #[cfg(feature = "charsets")]
+        self.text_reader().read_to_string(text)?;
+        #[cfg(not(feature = "charsets"))]
+        self.read_utf8(text)?;
+        Ok(text.len())
+    }
+
+    /// Read at most `max` bytes of the response decoded to a `String`, and whether the text was truncated.
+    ///
+    /// The response is decoded like [`text`](Self::text) does. The text is cut at the last character boundary
+    /// before `max` bytes and the rest of the body is discarded.
+    pub fn text_truncated(self, max: usize) -> Result<(String, bool)> {
+        let mut bytes = Vec::new();
+        // A byte past the limit tells whether the text is truncated and where the last character ends.
+        #[cfg(feature = "charsets")]
+        self.text_reader()
+            .take((max as u64).saturating_add(1))
+            .read_to_end(&mut bytes)?;
+        #[cfg(not(feature = "charsets"))]
+        self.take((max as u64).saturating_add(1)).read_to_end(&mut bytes)?;
+
+        let truncated = bytes.len() > max;
+        if truncated {
+            let mut end = max;
+            while end > 0 && (bytes[end] & 0xc0) == 0x80 {
+                end -= 1;
+            }
+            bytes.truncate(end);
+        }
+        match String::from_utf8(bytes) {
+            Ok(text) => Ok((text, truncated)),
+            Err(err) => Err(ErrorKind::Decoding {
+                charset: "UTF-8",
+                offset: err.utf8_error().valid_up_to() as u64,
+            }
+            .into()),
+        }
+    }
+
+    /// Read the rest of the response into `text`, replacing its content, checking that it is valid UTF-8.
+    fn read_utf8(mut self, text: &mut String) -> Result {
+        let mut bytes = mem::take(text).into_bytes();
+        bytes.clear();
+        self.read_to_end(&mut bytes)?;
+        match String::from_utf8(bytes) {
+            Ok(decoded) => {
+                *text = decoded;
+                Ok(())
+            }
+            Err(err) => Err(ErrorKind::Decoding {
+                charset: "UTF-8",
+                offset: err.utf8_error().valid_up_to() as u64,
+            }
+            .into()),
+        }
+    }
+
     /// Read the response to a `String`.
     ///
     /// If the `charsets` feature is enabled, it will try to decode the response using
@@ -113,8 +403,9 @@ impl ResponseReader {
     /// `text_utf8`.
     #[cfg(feature = "charsets")]
     pub fn text(self) -> Result<String> {
-        let charset = self.charset;
-        self.text_with(charset)
+        let mut text = String::new();
+        self.text_reader().read_to_string(&mut text)?;
+        Ok(text)
     }
 
     /// Read the response to a `String`, decoding with the given `Charset`.
@@ -136,13 +427,39 @@ impl ResponseReader {
     /// Otherwise, if a default encoding is set it will be used. If there is no default encoding, ISO-8859-1
     /// will be used.
     ///
+    /// With the `xml-charset` feature, the `encoding` of an XML declaration at the start of the body takes
+    /// precedence over the default encoding when the headers contain no charset information.
+    ///
     /// This method only exists when the `charsets` feature is enabled.
     #[cfg(feature = "charsets")]
     pub fn text_reader(self) -> TextReader<BufReader<ResponseReader>> {
         let charset = self.charset;
+        #[cfg(feature = "xml-charset")]
+        if self.sniff_xml_charset {
+            let mut reader = self;
+            let charset = reader.sniff_xml_declaration().unwrap_or(charset);
+            return TextReader::new(BufReader::new(reader), charset);
+        }
         self.text_reader_with(charset)
     }
 
+    /// Read the start of the body until the end of the XML declaration it starts with, and get its charset.
+    ///
+    /// The bytes read are kept to be returned by the next reads. A declaration longer than
+    /// `MAX_XML_DECLARATION_LEN` bytes is ignored.
+    #[cfg(feature = "xml-charset")]
+    fn sniff_xml_declaration(&mut self) -> Option<Charset> {
+        // Stop as soon as the body cannot start with a declaration, or once its end was read.
+        let res = self.read_ahead(MAX_XML_DECLARATION_LEN, |prefix| {
+            let len = prefix.len().min(5);
+            prefix[..len] != b"<?xml"[..len] || prefix.windows(2).any(|w| w == b"?>")
+        });
+        if let Err(err) = res {
+            self.pending_error = Some(SkipDebug(err));
+        }
+        xml_declaration_charset(self.ahead())
+    }
+
     /// Create a `TextReader` from this `ResponseReader`, decoding with the given `Charset`.
     ///
     /// This will ignore the encoding from the response headers and the default encoding, if any.
@@ -150,18 +467,31 @@ impl ResponseReader {
     /// This method only exists when the `charsets` feature is enabled.
     #[cfg(feature = "charsets")]
     pub fn text_reader_with(self, charset: Charset) -> TextReader<BufReader<ResponseReader>> {
-        TextReader::new(BufReader::new(self), charset)
+        TextReader::from_read(self, charset)
     }
 
     /// Read the response body to a String using the UTF-8 encoding.
     ///
     /// This method ignores headers and the default encoding.
-    pub fn text_utf8(mut self) -> Result<String> {
+    ///
+    /// If the body is not valid UTF-8, [`ErrorKind::Decoding`](crate::ErrorKind::Decoding) is returned with the
+    /// offset of the first invalid byte.
+    pub fn text_utf8(self) -> Result<String> {
         let mut text = String::new();
-        self.inner.read_to_string(&mut text)?;
+        self.read_utf8(&mut text)?;
         Ok(text)
     }
 
+    /// Read the response body to a `String`, mapping each byte to the code point of the same value.
+    ///
+    /// This is the ISO-8859-1 encoding as defined in Unicode, unlike `windows-1252` which maps the bytes from
+    /// `0x80` to `0x9f` to other characters. It ignores headers and the default encoding and never fails to
+    /// decode, the bytes can be recovered by converting each character back to a `u8`.
+    pub fn text_latin1(self) -> Result<String> {
+        let bytes = self.bytes()?;
+        Ok(bytes.iter().map(|&b| char::from(b)).collect())
+    }
+
     /// Parse the response as a JSON object and return it.
     ///
     /// If the `charsets` feature is enabled, it will try to decode the response using
@@ -214,12 +544,92 @@ impl ResponseReader {
         let obj = serde_json::from_reader(reader)?;
         Ok(obj)
     }
+
+    /// Parse the response as newline-delimited JSON and return an iterator over its values.
+    ///
+    /// Each non-empty line of the body is deserialized as it is read, which suits streaming APIs sending NDJSON
+    /// or JSON Lines. The body is decoded like [`text`](Self::text) does when the `charsets` feature is enabled,
+    /// otherwise it must be UTF-8. The iterator stops after the first error.
+    ///
+    /// A line longer than the limit set with `max_line_size`, 1 MiB by default, is not buffered further and
+    /// fails with [`InvalidResponseKind::LineTooLong`](crate::InvalidResponseKind::LineTooLong).
+    ///
+    /// This method only exists when the `json` feature is enabled.
+    #[cfg(feature = "json")]
+    pub fn ndjson<T>(self) -> impl Iterator<Item = Result<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let max_line_size = self.max_line_size;
+        #[cfg(feature = "charsets")]
+        let mut reader = BufReader::new(self.text_reader());
+        #[cfg(not(feature = "charsets"))]
+        let mut reader = BufReader::new(self);
+
+        let mut line = Vec::new();
+        let mut failed = false;
+        std::iter::from_fn(move || loop {
+            if failed {
+                return None;
+            }
+            let value = match read_limited_line(&mut reader, &mut line, max_line_size) {
+                Ok(0) => return None,
+                Ok(_) if line.iter().all(u8::is_ascii_whitespace) => continue,
+                Ok(_) => serde_json::from_slice(&line).map_err(Error::from),
+                Err(err) => Err(err),
+            };
+            failed = value.is_err();
+            return Some(value);
+        })
+    }
+
+    /// Parse the response as an XML document and deserialize its root element.
+    ///
+    /// If the `charsets` feature is enabled, the response is decoded like [`text`](Self::text) does before being
+    /// parsed, otherwise it must be UTF-8.
+    ///
+    /// This method only exists when the `xml` feature is enabled.
+    #[cfg(feature = "xml")]
+    pub fn xml<T>(self) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        #[cfg(feature = "charsets")]
+        let reader = BufReader::new(self.text_reader());
+        #[cfg(not(feature = "charsets"))]
+        let reader = BufReader::new(self);
+        let obj = quick_xml::de::from_reader(reader)?;
+        Ok(obj)
+    }
 }
 
 impl Read for ResponseReader {
     #[inline]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner.read(buf)
+        let n = if self.ahead.position() < self.ahead.get_ref().len() as u64 {
+            self.ahead.read(buf)?
+        } else {
+            #[cfg(feature = "xml-charset")]
+            if let Some(SkipDebug(err)) = self.pending_error.take() {
+                return Err(err);
+            }
+            self.inner.read(buf)?
+        };
+        if let Some(tee) = &mut self.tee {
+            tee.0.write_all(&buf[..n])?;
+        }
+        if n == 0 && !buf.is_empty() {
+            self.recorder.body_done();
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for ResponseReader {
+    fn drop(&mut self) {
+        // The connection of a partially read response can only be reused once the rest of the body has been
+        // read. Reading a little is cheaper than opening a new connection, otherwise it is closed.
+        let _ = self.inner.drain(MAX_DROP_DRAIN_LEN);
     }
 }
 
@@ -229,6 +639,8 @@ mod tests {
     use http::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
 
     use super::get_charset;
+    #[cfg(feature = "xml-charset")]
+    use super::xml_declaration_charset;
     use crate::charsets;
 
     #[test]
@@ -251,6 +663,22 @@ mod tests {
         assert_eq!(get_charset(&headers, None), charsets::UTF_8);
     }
 
+    #[test]
+    fn test_get_charset_from_header_utf16() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_bytes(&b"text/plain; charset=utf-16le"[..]).unwrap(),
+        );
+        assert_eq!(get_charset(&headers, None), charsets::UTF_16LE);
+
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_bytes(&b"text/plain; charset=UTF-16BE"[..]).unwrap(),
+        );
+        assert_eq!(get_charset(&headers, None), charsets::UTF_16BE);
+    }
+
     #[test]
     fn test_get_charset_from_default() {
         let headers = HeaderMap::new();
@@ -262,4 +690,23 @@ mod tests {
         let headers = HeaderMap::new();
         assert_eq!(get_charset(&headers, None), charsets::WINDOWS_1252);
     }
+
+    #[test]
+    #[cfg(feature = "xml-charset")]
+    fn test_xml_declaration_charset() {
+        assert_eq!(
+            xml_declaration_charset(b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><a/>"),
+            Some(charsets::WINDOWS_1252)
+        );
+        assert_eq!(
+            xml_declaration_charset(b"<?xml version='1.0' encoding = 'Shift_JIS' ?>"),
+            Some(charsets::SHIFT_JIS)
+        );
+        assert_eq!(
+            xml_declaration_charset(b"<?xml version=\"1.0\"?><a encoding=\"x\"/>"),
+            None
+        );
+        assert_eq!(xml_declaration_charset(b"<a/>"), None);
+        assert_eq!(xml_declaration_charset(b"<?xml encoding=\"ISO-8859-1\""), None);
+    }
 }
@@ -1,4 +1,4 @@
-use std::io::{self, Read};
+use std::io::{self, BufReader, Read};
 
 use encoding_rs_io::{DecodeReaderBytes, DecodeReaderBytesBuilder};
 
@@ -22,6 +22,18 @@ where
     }
 }
 
+impl<R> TextReader<BufReader<R>>
+where
+    R: Read,
+{
+    /// Create a new `TextReader` with the given charset, buffering reads from the inner reader.
+    ///
+    /// This is a shortcut for `TextReader::new(BufReader::new(inner), charset)`.
+    pub fn from_read(inner: R, charset: Charset) -> Self {
+        TextReader::new(BufReader::new(inner), charset)
+    }
+}
+
 impl<R> Read for TextReader<R>
 where
     R: Read,
@@ -64,3 +76,113 @@ fn test_string_reader_large_buffer_latin1() {
         assert_eq!(c, 'É');
     }
 }
+
+#[test]
+fn test_from_read() {
+    let inner: &mut dyn Read = &mut &b"qu\xC9bec"[..];
+    let mut reader = TextReader::from_read(inner, crate::charsets::WINDOWS_1252);
+
+    let mut text = String::new();
+    assert_eq!(reader.read_to_string(&mut text).ok(), Some(7));
+
+    assert_eq!(text, "quÉbec");
+}
+
+#[cfg(test)]
+struct OneByteReader<'a>(&'a [u8]);
+
+#[cfg(test)]
+impl io::BufRead for OneByteReader<'_> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(&self.0[..self.0.len().min(1)])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.0 = &self.0[amt..];
+    }
+}
+
+#[cfg(test)]
+impl Read for OneByteReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = io::BufRead::fill_buf(self)?.read(buf)?;
+        io::BufRead::consume(self, n);
+        Ok(n)
+    }
+}
+
+#[test]
+fn test_stream_decoder_split_multibyte() {
+    let source = "québec ÉÀ ✓ 🦀".repeat(50);
+    let mut reader = TextReader::new(OneByteReader(source.as_bytes()), crate::charsets::UTF_8);
+
+    let mut text = String::new();
+    reader.read_to_string(&mut text).unwrap();
+
+    assert!(!text.contains('\u{FFFD}'));
+    assert_eq!(text, source);
+}
+
+#[test]
+fn test_stream_decoder_split_multibyte_small_buffer() {
+    let source = "québec ÉÀ ✓ 🦀".repeat(50);
+    let mut reader = TextReader::new(OneByteReader(source.as_bytes()), crate::charsets::UTF_8);
+
+    let mut bytes = Vec::new();
+    let mut buf = [0; 3];
+    loop {
+        match reader.read(&mut buf).unwrap() {
+            0 => break,
+            n => bytes.extend_from_slice(&buf[..n]),
+        }
+    }
+
+    assert_eq!(String::from_utf8(bytes).unwrap(), source);
+}
+
+#[cfg(test)]
+fn utf16le(text: &str) -> Vec<u8> {
+    text.encode_utf16()
+        .flat_map(|unit| unit.to_le_bytes().to_vec())
+        .collect()
+}
+
+#[test]
+fn test_stream_decoder_utf16le() {
+    let source = "québec 🦀 ok";
+    let bytes = utf16le(source);
+    let mut reader = TextReader::new(&bytes[..], crate::charsets::UTF_16LE);
+
+    let mut text = String::new();
+    reader.read_to_string(&mut text).unwrap();
+
+    assert_eq!(text, source);
+}
+
+#[test]
+fn test_stream_decoder_utf16be() {
+    let source = "québec 🦀 ok";
+    let bytes: Vec<u8> = source
+        .encode_utf16()
+        .flat_map(|unit| unit.to_be_bytes().to_vec())
+        .collect();
+    let mut reader = TextReader::new(&bytes[..], crate::charsets::UTF_16BE);
+
+    let mut text = String::new();
+    reader.read_to_string(&mut text).unwrap();
+
+    assert_eq!(text, source);
+}
+
+#[test]
+fn test_stream_decoder_utf16le_split_surrogates() {
+    let source = "🦀🦀 québec 🦀".repeat(50);
+    let bytes = utf16le(&source);
+    let mut reader = TextReader::new(OneByteReader(&bytes), crate::charsets::UTF_16LE);
+
+    let mut text = String::new();
+    reader.read_to_string(&mut text).unwrap();
+
+    assert!(!text.contains('\u{FFFD}'));
+    assert_eq!(text, source);
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use http::header::CONNECTION;
+use url::Url;
+
+use crate::error::{ErrorKind, Result};
+use crate::request::HostFilter;
+use crate::streams::{BaseStream, ConnectInfo};
+use crate::tls::{certificate_der, TlsVersion};
+
+/// Maximum number of idle connections kept for a single host.
+const MAX_IDLE_PER_HOST: usize = 8;
+
+/// Time after which an idle connection is closed rather than reused.
+const MAX_IDLE_TIME: Duration = Duration::from_secs(90);
+
+/// Identifies the connections which can be used interchangeably to send a request.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PoolKey {
+    scheme: String,
+    host: String,
+    port: u16,
+    proxy: Option<String>,
+    accept_invalid_certs: bool,
+    accept_invalid_hostnames: bool,
+    tls_server_name: Option<String>,
+    root_certificates: Vec<Vec<u8>>,
+    min_tls_version: Option<TlsVersion>,
+    max_tls_version: Option<TlsVersion>,
+    alpn_protocols: Vec<String>,
+    #[cfg(feature = "socket2")]
+    local_port: Option<u16>,
+    // Callbacks are compared by address, the clones of the settings of a session share the same callback.
+    #[cfg(feature = "socket2")]
+    configure_socket: Option<usize>,
+    host_filter: HostFilter,
+}
+
+impl PoolKey {
+    fn new(info: &ConnectInfo) -> Result<PoolKey> {
+        let url: &Url = info.url;
+        Ok(PoolKey {
+            scheme: url.scheme().to_string(),
+            host: url.host_str().ok_or(ErrorKind::InvalidUrlHost)?.to_string(),
+            port: url.port_or_known_default().ok_or(ErrorKind::InvalidUrlPort)?,
+            proxy: info.proxy.map(|proxy| proxy.to_string()),
+            accept_invalid_certs: info.base_settings.accept_invalid_certs,
+            accept_invalid_hostnames: info.base_settings.accept_invalid_hostnames,
+            tls_server_name: info.base_settings.tls_server_name.clone(),
+            root_certificates: info
+                .base_settings
+                .root_certificates
+                .0
+                .iter()
+                .map(certificate_der)
+                .collect(),
+            min_tls_version: info.base_settings.min_tls_version,
+            max_tls_version: info.base_settings.max_tls_version,
+            alpn_protocols: info.base_settings.alpn_protocols.clone(),
+            #[cfg(feature = "socket2")]
+            local_port: info.base_settings.local_port,
+            #[cfg(feature = "socket2")]
+            configure_socket: info
+                .base_settings
+                .configure_socket
+                .as_ref()
+                .map(|callback| Arc::as_ptr(&callback.0) as *const () as usize),
+            host_filter: info.base_settings.host_filter.clone(),
+        })
+    }
+}
+
+/// Idle connections kept open to be reused by later requests of a `Session`.
+#[derive(Default)]
+pub struct Pool {
+    idle: Mutex<HashMap<PoolKey, Vec<(BaseStream, Instant)>>>,
+}
+
+impl Pool {
+    /// Get a connection for the given request, reusing an idle one if `reuse` is true and one is available.
+    ///
+    /// The connection is returned along with whether it was idle. It is returned to the pool once its response
+    /// has been read entirely, unless the request or the response asks for it to be closed.
+    ///
+    /// Connections made with a custom TLS connector are never pooled, as connectors cannot be told apart.
+    pub fn connect(pool: &Arc<Pool>, info: &ConnectInfo, reuse: bool) -> Result<(BaseStream, bool)> {
+        #[cfg(feature = "tls")]
+        {
+            if info.base_settings.tls_connector.is_some() {
+                return Ok((BaseStream::connect(info)?, false));
+            }
+        }
+
+        let key = PoolKey::new(info)?;
+
+        let idle = if reuse { pool.take(&key) } else { None };
+        let reused = idle.is_some();
+        let mut stream = match idle {
+            Some(mut stream) => {
+                debug!("reusing idle connection to {}:{}", key.host, key.port);
+                stream.reuse(info.base_settings)?;
+                stream
+            }
+            None => BaseStream::connect(info)?,
+        };
+
+        let close = info
+            .base_settings
+            .headers
+            .get_all(CONNECTION)
+            .iter()
+            .any(|value| value.as_bytes().eq_ignore_ascii_case(b"close"));
+        if !close {
+            stream.set_pool(pool.clone(), key);
+        }
+
+        Ok((stream, reused))
+    }
+
+    /// Take an idle connection, discarding the ones the server closed or which were idle for too long.
+    fn take(&self, key: &PoolKey) -> Option<BaseStream> {
+        let mut idle = self.idle.lock().ok()?;
+        let streams = idle.get_mut(key)?;
+        let mut stream = None;
+        while let Some((candidate, since)) = streams.pop() {
+            if since.elapsed() >= MAX_IDLE_TIME {
+                debug!("discarding connection to {}:{} idle for too long", key.host, key.port);
+                continue;
+            }
+            if candidate.is_alive() {
+                stream = Some(candidate);
+                break;
+            }
+            debug!("discarding idle connection closed by {}:{}", key.host, key.port);
+        }
+        if streams.is_empty() {
+            idle.remove(key);
+        }
+        stream
+    }
+
+    pub fn put(&self, key: PoolKey, stream: BaseStream) {
+        if let Ok(mut idle) = self.idle.lock() {
+            // Close the connections of hosts which were not used for a while.
+            idle.retain(|_, streams| {
+                streams.retain(|(_, since)| since.elapsed() < MAX_IDLE_TIME);
+                !streams.is_empty()
+            });
+
+            let streams = idle.entry(key).or_insert_with(Vec::new);
+            if streams.len() < MAX_IDLE_PER_HOST {
+                streams.push((stream, Instant::now()));
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Pool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let idle = self
+            .idle
+            .lock()
+            .map(|idle| idle.values().map(Vec::len).sum::<usize>())
+            .unwrap_or(0);
+        f.debug_struct("Pool").field("idle", &idle).finish()
+    }
+}
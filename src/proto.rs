@@ -0,0 +1,336 @@
+//! Serialization of HTTP/1.1 requests which only needs `core` and `alloc`.
+//!
+//! This layer splits URLs, assembles the request line and headers and frames chunked bodies, without any I/O:
+//! the bytes are written to a [`Transport`] provided by the caller. The std-based connections of this crate use
+//! it to write their requests, with the `no-std-core` feature it can be used directly on targets where
+//! `std::net` is not available. Only this module is restricted to `core` and `alloc`, the crate as a whole still
+//! links `std` through its `http` and `url` dependencies.
+//!
+//! # Example
+//! ```
+//! # #[cfg(feature = "no-std-core")]
+//! # fn main() -> Result<(), attohttpc::proto::Error> {
+//! use attohttpc::proto::Request;
+//!
+//! let request = Request::new("POST", "http://example.com:8080/submit?a=1")?
+//!     .header("Content-Type", "text/plain")?
+//!     .body("hello");
+//!
+//! let mut bytes = Vec::new();
+//! request.write(&mut bytes).unwrap();
+//! assert_eq!(
+//!     bytes,
+//!     &b"POST /submit?a=1 HTTP/1.1\r\nhost: example.com:8080\r\ncontent-type: text/plain\r\n\
+//!         content-length: 5\r\n\r\nhello"[..]
+//! );
+//! # Ok(())
+//! # }
+//! # #[cfg(not(feature = "no-std-core"))]
+//! # fn main() {
+//! # }
+//! ```
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use core::fmt;
+
+/// A destination for the bytes of a request, such as a socket of an embedded network stack.
+pub trait Transport {
+    /// The error returned when writing fails.
+    type Error;
+
+    /// Write all of `buf` to the transport.
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl Transport for Vec<u8> {
+    type Error = Infallible;
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Infallible> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    type Error = T::Error;
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), T::Error> {
+        (**self).write_all(buf)
+    }
+}
+
+/// Errors of the `core` layer, they are found while building a request, before anything is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The URL is not an absolute `http` or `https` URL.
+    Url,
+    /// The method is not a valid token.
+    Method,
+    /// The name of a header is not a valid token, or its value contains a line break.
+    Header,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Url => write!(f, "invalid url"),
+            Error::Method => write!(f, "invalid method"),
+            Error::Header => write!(f, "invalid header"),
+        }
+    }
+}
+
+/// The parts of an `http` or `https` URL a request is made of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target<'a> {
+    /// Whether the scheme is `https`.
+    pub tls: bool,
+    /// The host, with the brackets of an IPv6 address.
+    pub host: &'a str,
+    /// The port, or the default port of the scheme.
+    pub port: u16,
+    /// The path and the query, which is the target of the request line. An empty path is sent as `/`.
+    pub path: Cow<'a, str>,
+}
+
+impl<'a> Target<'a> {
+    /// Split an absolute URL, which must already be percent-encoded. The user info and fragment are not sent in
+    /// requests, a URL with user info is refused and the fragment is dropped.
+    pub fn parse(url: &'a str) -> Result<Target<'a>, Error> {
+        if !url.bytes().all(|c| c.is_ascii_graphic()) {
+            return Err(Error::Url);
+        }
+        let (tls, rest) = match url.find("://") {
+            Some(pos) if url[..pos].eq_ignore_ascii_case("http") => (false, &url[pos + 3..]),
+            Some(pos) if url[..pos].eq_ignore_ascii_case("https") => (true, &url[pos + 3..]),
+            _ => return Err(Error::Url),
+        };
+        let rest = rest.split('#').next().unwrap_or_default();
+        let end = rest.find(['/', '?']).unwrap_or(rest.len());
+        let (authority, path) = rest.split_at(end);
+        let path = match path.starts_with('/') {
+            true => Cow::Borrowed(path),
+            false => Cow::Owned(["/", path].concat()),
+        };
+        if authority.contains('@') {
+            return Err(Error::Url);
+        }
+        let port_start = match authority.rfind(':') {
+            // The colons of an IPv6 address are inside its brackets.
+            Some(pos) if !authority[pos..].contains(']') => Some(pos),
+            _ => None,
+        };
+        let (host, port) = match port_start {
+            Some(pos) => {
+                let port = authority[pos + 1..].parse().map_err(|_| Error::Url)?;
+                (&authority[..pos], port)
+            }
+            None if tls => (authority, 443),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(Error::Url);
+        }
+        Ok(Target { tls, host, port, path })
+    }
+
+    /// Get the value of the `Host` header for this target, the port is omitted when it is the default one.
+    pub fn host_header(&self) -> String {
+        let default_port = if self.tls { 443 } else { 80 };
+        if self.port == default_port {
+            self.host.to_string()
+        } else {
+            let mut host = String::with_capacity(self.host.len() + 6);
+            host.push_str(self.host);
+            host.push(':');
+            host.push_str(&self.port.to_string());
+            host
+        }
+    }
+}
+
+/// A request serialized by the `core` layer.
+///
+/// The `Host` header is derived from the URL, headers are sent in the order they are added and a body is sent
+/// with its `Content-Length`.
+#[derive(Debug, Clone)]
+pub struct Request {
+    method: String,
+    path: String,
+    host: String,
+    headers: Vec<(String, Vec<u8>)>,
+    body: Option<Vec<u8>>,
+}
+
+impl Request {
+    /// Create a request with the given method to the given absolute URL.
+    pub fn new(method: &str, url: &str) -> Result<Request, Error> {
+        if !is_token(method) {
+            return Err(Error::Method);
+        }
+        let target = Target::parse(url)?;
+        Ok(Request {
+            method: method.to_string(),
+            path: target.path.to_string(),
+            host: target.host_header(),
+            headers: Vec::new(),
+            body: None,
+        })
+    }
+
+    /// Add a header to this request. A `Host` header replaces the one derived from the URL.
+    pub fn header(mut self, name: &str, value: impl AsRef<[u8]>) -> Result<Request, Error> {
+        let value = value.as_ref();
+        if !is_token(name) || !is_header_value(value) {
+            return Err(Error::Header);
+        }
+        if name.eq_ignore_ascii_case("host") {
+            self.host = String::from_utf8(value.to_vec()).map_err(|_| Error::Header)?;
+        } else {
+            self.headers.push((name.to_ascii_lowercase(), value.to_vec()));
+        }
+        Ok(self)
+    }
+
+    /// Set the body of this request, it replaces any `Content-Length` header.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Request {
+        self.headers.retain(|(name, _)| name != "content-length");
+        let body = body.into();
+        self.headers
+            .push(("content-length".to_string(), body.len().to_string().into_bytes()));
+        self.body = Some(body);
+        self
+    }
+
+    /// Write this request to the transport.
+    pub fn write<T: Transport>(&self, mut transport: T) -> Result<(), T::Error> {
+        let host = ("host", self.host.as_bytes());
+        let headers = self
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_slice()));
+        write_head(
+            &mut transport,
+            &self.method,
+            &self.path,
+            core::iter::once(host).chain(headers),
+        )?;
+        if let Some(body) = &self.body {
+            transport.write_all(body)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write the request line and the headers of an HTTP/1.1 request, followed by the empty line which ends them.
+pub fn write_head<'a, T, I>(transport: &mut T, method: &str, target: &str, headers: I) -> Result<(), T::Error>
+where
+    T: Transport,
+    I: IntoIterator<Item = (&'a str, &'a [u8])>,
+{
+    transport.write_all(method.as_bytes())?;
+    transport.write_all(b" ")?;
+    transport.write_all(target.as_bytes())?;
+    transport.write_all(b" HTTP/1.1\r\n")?;
+    for (name, value) in headers {
+        transport.write_all(name.as_bytes())?;
+        transport.write_all(b": ")?;
+        transport.write_all(value)?;
+        transport.write_all(b"\r\n")?;
+    }
+    transport.write_all(b"\r\n")
+}
+
+/// Write a chunk of a chunked body. An empty chunk ends the body, use [`write_last_chunk`] for it.
+pub fn write_chunk<T: Transport>(transport: &mut T, data: &[u8]) -> Result<(), T::Error> {
+    let mut size = [0; 16];
+    transport.write_all(hex(data.len(), &mut size))?;
+    transport.write_all(b"\r\n")?;
+    transport.write_all(data)?;
+    transport.write_all(b"\r\n")
+}
+
+/// Write the last chunk of a chunked body, without trailers.
+pub fn write_last_chunk<T: Transport>(transport: &mut T) -> Result<(), T::Error> {
+    transport.write_all(b"0\r\n\r\n")
+}
+
+fn hex(mut n: usize, buf: &mut [u8; 16]) -> &[u8] {
+    let mut start = buf.len();
+    loop {
+        start -= 1;
+        buf[start] = b"0123456789abcdef"[n % 16];
+        n /= 16;
+        if n == 0 {
+            return &buf[start..];
+        }
+    }
+}
+
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|c| c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c))
+}
+
+fn is_header_value(value: &[u8]) -> bool {
+    value.iter().all(|&c| c != b'\r' && c != b'\n' && c != 0)
+}
+
+#[test]
+fn test_parse_target() {
+    assert_eq!(
+        Target::parse("http://example.com"),
+        Ok(Target {
+            tls: false,
+            host: "example.com",
+            port: 80,
+            path: "/".into()
+        })
+    );
+    assert_eq!(
+        Target::parse("HTTPS://[::1]:8443/a/b?c=d#frag"),
+        Ok(Target {
+            tls: true,
+            host: "[::1]",
+            port: 8443,
+            path: "/a/b?c=d".into()
+        })
+    );
+    assert_eq!(Target::parse("http://[::1]/").map(|t| t.port), Ok(80));
+    assert_eq!(Target::parse("http://a?b").map(|t| t.path), Ok("/?b".into()));
+    assert_eq!(Target::parse("ftp://example.com/"), Err(Error::Url));
+    assert_eq!(Target::parse("http://user@example.com/"), Err(Error::Url));
+    assert_eq!(Target::parse("http://example.com:port/"), Err(Error::Url));
+    assert_eq!(Target::parse("http:///path"), Err(Error::Url));
+    assert_eq!(Target::parse("http://example.com/a b"), Err(Error::Url));
+}
+
+#[test]
+fn test_host_header() {
+    let host = |url| Target::parse(url).unwrap().host_header();
+    assert_eq!(host("http://example.com:80/"), "example.com");
+    assert_eq!(host("https://example.com:80/"), "example.com:80");
+    assert_eq!(host("https://[::1]:443/"), "[::1]");
+}
+
+#[test]
+fn test_write_chunk() {
+    let mut bytes = Vec::new();
+    write_chunk(&mut bytes, &[b'x'; 26]).unwrap();
+    write_last_chunk(&mut bytes).unwrap();
+    let mut expected = b"1a\r\n".to_vec();
+    expected.extend(&[b'x'; 26]);
+    expected.extend(b"\r\n0\r\n\r\n");
+    assert_eq!(bytes, expected);
+}
+
+#[test]
+fn test_invalid_request() {
+    assert_eq!(Request::new("GE T", "http://a/").unwrap_err(), Error::Method);
+    let req = Request::new("GET", "http://a/").unwrap();
+    assert_eq!(req.clone().header("a b", "c").unwrap_err(), Error::Header);
+    assert_eq!(req.header("a", "b\r\nc: d").unwrap_err(), Error::Header);
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+/// Spaces out the requests made to each host by a minimum interval.
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(interval: Duration) -> RateLimiter {
+        RateLimiter {
+            interval,
+            next: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Block the calling thread until a request can be made to the host of the given URL.
+    ///
+    /// Each call reserves the next slot of the host, so concurrent callers are spaced out as well.
+    pub fn wait(&self, url: &Url) {
+        let host = match url.host_str() {
+            Some(host) => host,
+            None => return,
+        };
+
+        let now = Instant::now();
+        let slot = match self.next.lock() {
+            Ok(mut next) => {
+                let next = next.entry(host.to_string()).or_insert(now);
+                let slot = (*next).max(now);
+                *next = slot + self.interval;
+                slot
+            }
+            Err(_) => return,
+        };
+
+        if slot > now {
+            debug!("waiting {:?} before sending a request to {}", slot - now, host);
+            thread::sleep(slot - now);
+        }
+    }
+}
+
+#[test]
+fn test_rate_limiter_per_host() {
+    let limiter = RateLimiter::new(Duration::from_millis(50));
+    let first = Url::parse("http://example.com/a").unwrap();
+    let second = Url::parse("http://example.org/").unwrap();
+
+    let start = Instant::now();
+    limiter.wait(&first);
+    limiter.wait(&second);
+    assert!(start.elapsed() < Duration::from_millis(50));
+
+    limiter.wait(&first);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+}
@@ -1,6 +1,12 @@
 use std::convert::TryInto;
+use std::fmt;
 use std::fs;
-use std::io::{copy, Result as IoResult, Seek, SeekFrom, Write};
+use std::io::{copy, ErrorKind as IoErrorKind, Read, Result as IoResult, Seek, SeekFrom, Write};
+use std::sync::mpsc::Receiver;
+
+use crate::error::{Error, ErrorKind};
+use crate::proto;
+use crate::request::IoTransport;
 
 /// The kinds of request bodies currently supported by this crate.
 #[derive(Debug, Clone, Copy)]
@@ -27,6 +33,21 @@ pub trait Body {
     fn content_type(&mut self) -> IoResult<Option<String>> {
         Ok(None)
     }
+
+    /// Gets the content coding applied to this body, such as `gzip`, if it has one.
+    ///
+    /// It is sent in the `Content-Encoding` header of the request.
+    fn content_encoding(&mut self) -> IoResult<Option<&'static str>> {
+        Ok(None)
+    }
+
+    /// Check if this body can be written out again after having been written.
+    ///
+    /// A request whose idle connection turns out to have been closed by the server is only sent again on a
+    /// new connection if its body can be replayed. Bodies streamed from readers should return false.
+    fn is_replayable(&self) -> bool {
+        true
+    }
 }
 
 /// An empty request body
@@ -90,19 +111,198 @@ impl Body for File {
     }
 }
 
+/// A request body streamed from a reader whose length is known in advance
+///
+/// The body is sent with a `Content-Length` header instead of chunked encoding. Writing it fails with
+/// [`ErrorKind::BodyLengthMismatch`] if the reader produces more or fewer bytes than announced. Since
+/// the reader cannot be rewound, the body can only be written once.
+#[derive(Debug)]
+pub struct SizedReader<R> {
+    reader: R,
+    len: u64,
+}
+
+impl<R> SizedReader<R> {
+    /// Create a body which reads exactly `len` bytes from the given reader.
+    pub fn new(reader: R, len: u64) -> Self {
+        SizedReader { reader, len }
+    }
+}
+
+impl<R: Read> Body for SizedReader<R> {
+    fn kind(&mut self) -> IoResult<BodyKind> {
+        Ok(BodyKind::KnownLength(self.len))
+    }
+
+    fn write<W: Write>(&mut self, mut writer: W) -> IoResult<()> {
+        let expected = self.len;
+        let mismatch = |actual| Error::from(ErrorKind::BodyLengthMismatch { expected, actual });
+
+        let written = copy(&mut (&mut self.reader).take(self.len), &mut writer)?;
+        if written < self.len {
+            return Err(mismatch(written).into());
+        }
+
+        let mut extra = [0; 1];
+        loop {
+            match self.reader.read(&mut extra) {
+                Ok(0) => return Ok(()),
+                Ok(_) => return Err(mismatch(self.len + 1).into()),
+                Err(err) if err.kind() == IoErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn is_replayable(&self) -> bool {
+        false
+    }
+}
+
+/// A request body made of the chunks received from a channel
+///
+/// The body is sent with chunked encoding, each chunk is written out as soon as it is received and the
+/// body ends when every sender has been dropped. Since the chunks are consumed, the body can only be
+/// written once.
+#[derive(Debug)]
+pub struct Channel(pub Receiver<Vec<u8>>);
+
+impl Body for Channel {
+    fn kind(&mut self) -> IoResult<BodyKind> {
+        Ok(BodyKind::Chunked)
+    }
+
+    fn write<W: Write>(&mut self, mut writer: W) -> IoResult<()> {
+        for chunk in self.0.iter() {
+            // An empty chunk would mark the end of the body.
+            if !chunk.is_empty() {
+                writer.write_all(&chunk)?;
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn is_replayable(&self) -> bool {
+        false
+    }
+}
+
+/// A request body whose type has been erased
+///
+/// This is the body of the requests handed to a [`Middleware`](crate::Middleware), it forwards every call to
+/// the body of the original request.
+pub struct Boxed<'a>(Box<dyn ErasedBody + 'a>);
+
+impl<'a> Boxed<'a> {
+    /// Erase the type of a body.
+    pub fn new<B: Body + 'a>(body: B) -> Self {
+        Boxed(Box::new(body))
+    }
+
+    pub(crate) fn borrowed<B: Body>(body: &'a mut B) -> Self {
+        Boxed(Box::new(Borrowed(body)))
+    }
+}
+
+impl fmt::Debug for Boxed<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Boxed")
+    }
+}
+
+impl Body for Boxed<'_> {
+    fn kind(&mut self) -> IoResult<BodyKind> {
+        self.0.kind_dyn()
+    }
+
+    fn write<W: Write>(&mut self, mut writer: W) -> IoResult<()> {
+        self.0.write_dyn(&mut writer)
+    }
+
+    fn content_type(&mut self) -> IoResult<Option<String>> {
+        self.0.content_type_dyn()
+    }
+
+    fn content_encoding(&mut self) -> IoResult<Option<&'static str>> {
+        self.0.content_encoding_dyn()
+    }
+
+    fn is_replayable(&self) -> bool {
+        self.0.is_replayable_dyn()
+    }
+}
+
+/// Object safe version of `Body`.
+trait ErasedBody {
+    fn kind_dyn(&mut self) -> IoResult<BodyKind>;
+
+    fn write_dyn(&mut self, writer: &mut dyn Write) -> IoResult<()>;
+
+    fn content_type_dyn(&mut self) -> IoResult<Option<String>>;
+
+    fn content_encoding_dyn(&mut self) -> IoResult<Option<&'static str>>;
+
+    fn is_replayable_dyn(&self) -> bool;
+}
+
+impl<B: Body> ErasedBody for B {
+    fn kind_dyn(&mut self) -> IoResult<BodyKind> {
+        Body::kind(self)
+    }
+
+    fn write_dyn(&mut self, writer: &mut dyn Write) -> IoResult<()> {
+        Body::write(self, writer)
+    }
+
+    fn content_type_dyn(&mut self) -> IoResult<Option<String>> {
+        Body::content_type(self)
+    }
+
+    fn content_encoding_dyn(&mut self) -> IoResult<Option<&'static str>> {
+        Body::content_encoding(self)
+    }
+
+    fn is_replayable_dyn(&self) -> bool {
+        Body::is_replayable(self)
+    }
+}
+
+struct Borrowed<'a, B>(&'a mut B);
+
+impl<B: Body> Body for Borrowed<'_, B> {
+    fn kind(&mut self) -> IoResult<BodyKind> {
+        self.0.kind()
+    }
+
+    fn write<W: Write>(&mut self, writer: W) -> IoResult<()> {
+        self.0.write(writer)
+    }
+
+    fn content_type(&mut self) -> IoResult<Option<String>> {
+        self.0.content_type()
+    }
+
+    fn content_encoding(&mut self) -> IoResult<Option<&'static str>> {
+        self.0.content_encoding()
+    }
+
+    fn is_replayable(&self) -> bool {
+        self.0.is_replayable()
+    }
+}
+
 pub(crate) struct ChunkedWriter<W>(pub W);
 
 impl<W: Write> ChunkedWriter<W> {
-    pub fn close(mut self) -> IoResult<()> {
-        self.0.write_all(b"0\r\n\r\n")
+    pub fn close(self) -> IoResult<()> {
+        proto::write_last_chunk(&mut IoTransport(self.0))
     }
 }
 
 impl<W: Write> Write for ChunkedWriter<W> {
     fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
-        write!(self.0, "{:x}\r\n", buf.len())?;
-        self.0.write_all(buf)?;
-        write!(self.0, "\r\n")?;
+        proto::write_chunk(&mut IoTransport(&mut self.0), buf)?;
         Ok(buf.len())
     }
 
@@ -136,7 +336,149 @@ mod json {
             Ok(())
         }
     }
+
+    /// A request body for streaming out the items of an iterator as newline-delimited JSON
+    ///
+    /// Each item is serialized on its own line followed by a newline. Since the items are consumed, the body
+    /// can only be written once.
+    #[derive(Debug)]
+    pub struct NdJson<I> {
+        items: Option<I>,
+    }
+
+    impl<I: Iterator> NdJson<I> {
+        /// Create a body which serializes the items of the given iterator.
+        pub fn new<T: IntoIterator<IntoIter = I>>(items: T) -> Self {
+            NdJson {
+                items: Some(items.into_iter()),
+            }
+        }
+    }
+
+    impl<I> Body for NdJson<I>
+    where
+        I: Iterator,
+        I::Item: Serialize,
+    {
+        fn kind(&mut self) -> IoResult<BodyKind> {
+            Ok(BodyKind::Chunked)
+        }
+
+        fn write<W: Write>(&mut self, writer: W) -> IoResult<()> {
+            let mut writer = BufWriter::new(writer);
+            for item in self.items.take().into_iter().flatten() {
+                to_writer(&mut writer, &item)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+            Ok(())
+        }
+
+        fn is_replayable(&self) -> bool {
+            false
+        }
+    }
 }
 
 #[cfg(feature = "json")]
-pub use json::Json;
+pub use json::{Json, NdJson};
+
+#[cfg(feature = "compress")]
+mod gzip {
+    use super::*;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    /// A request body which gzip-compresses another body
+    ///
+    /// Bodies with a known length are compressed once up front so that the compressed length can be
+    /// announced. Chunked bodies are compressed on the fly while they are written out.
+    #[derive(Debug, Clone)]
+    pub struct Gzip<B> {
+        inner: B,
+        compressed: Option<Vec<u8>>,
+        // Bodies left uncompressed because they are too small are written out as they are.
+        enabled: bool,
+    }
+
+    impl<B> Gzip<B> {
+        /// Wrap the given body to compress it using gzip.
+        pub fn new(inner: B) -> Self {
+            Gzip {
+                inner,
+                compressed: None,
+                enabled: true,
+            }
+        }
+
+        /// Wrap the given body without compressing it.
+        pub(crate) fn uncompressed(inner: B) -> Self {
+            Gzip {
+                inner,
+                compressed: None,
+                enabled: false,
+            }
+        }
+    }
+
+    impl<B: Body> Body for Gzip<B> {
+        fn kind(&mut self) -> IoResult<BodyKind> {
+            if !self.enabled {
+                return self.inner.kind();
+            }
+            match self.inner.kind()? {
+                BodyKind::Empty => Ok(BodyKind::Empty),
+                BodyKind::KnownLength(_) => {
+                    if self.compressed.is_none() {
+                        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                        self.inner.write(&mut encoder)?;
+                        self.compressed = Some(encoder.finish()?);
+                    }
+                    let len = self.compressed.as_ref().map_or(0, |buf| buf.len());
+                    Ok(BodyKind::KnownLength(len.try_into().unwrap()))
+                }
+                BodyKind::Chunked => Ok(BodyKind::Chunked),
+            }
+        }
+
+        fn write<W: Write>(&mut self, mut writer: W) -> IoResult<()> {
+            if !self.enabled {
+                return self.inner.write(writer);
+            }
+            match self.kind()? {
+                BodyKind::Empty => Ok(()),
+                BodyKind::KnownLength(_) => match &self.compressed {
+                    Some(buf) => writer.write_all(buf),
+                    None => Ok(()),
+                },
+                BodyKind::Chunked => {
+                    let mut encoder = GzEncoder::new(writer, Compression::default());
+                    self.inner.write(&mut encoder)?;
+                    encoder.finish()?;
+                    Ok(())
+                }
+            }
+        }
+
+        fn content_type(&mut self) -> IoResult<Option<String>> {
+            self.inner.content_type()
+        }
+
+        fn content_encoding(&mut self) -> IoResult<Option<&'static str>> {
+            // Empty bodies and bodies left uncompressed are sent as they are.
+            if self.enabled && !matches!(self.inner.kind()?, BodyKind::Empty) {
+                return Ok(Some("gzip"));
+            }
+            self.inner.content_encoding()
+        }
+
+        fn is_replayable(&self) -> bool {
+            // A body with a known length is compressed once and kept in memory.
+            self.compressed.is_some() || self.inner.is_replayable()
+        }
+    }
+}
+
+#[cfg(feature = "compress")]
+pub use gzip::Gzip;
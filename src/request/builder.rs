@@ -1,29 +1,44 @@
 use std::borrow::Borrow;
 use std::convert::{From, TryInto};
 use std::fs;
+#[cfg(feature = "socket2")]
+use std::io;
+use std::io::Read;
+use std::net::IpAddr;
+use std::path::Path;
 use std::str;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
 use std::time::Duration;
 
 use http::{
     header::{
-        HeaderMap, HeaderValue, IntoHeaderName, ACCEPT, CONNECTION, CONTENT_LENGTH, CONTENT_TYPE, TRANSFER_ENCODING,
-        USER_AGENT,
+        HeaderMap, HeaderValue, IntoHeaderName, ACCEPT, CONNECTION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE,
+        FORWARDED, TRANSFER_ENCODING, USER_AGENT,
     },
-    Method,
+    Extensions, Method, StatusCode,
 };
+#[cfg(feature = "socket2")]
+use socket2::Socket;
 use url::Url;
 
+use crate::cancel::CancelToken;
 #[cfg(feature = "charsets")]
-use crate::charsets::Charset;
+use crate::charsets::{self, Charset};
 use crate::error::{Error, ErrorKind, Result};
 use crate::parsing::Response;
+#[cfg(feature = "socket2")]
+use crate::request::SocketCallback;
 use crate::request::{
     body::{self, Body, BodyKind},
-    header_append, header_insert, header_insert_if_missing,
+    download, header_append, header_insert, header_insert_if_missing,
     proxy::ProxySettings,
-    BaseSettings, PreparedRequest,
+    BaseSettings, ForwardedParams, PreparedRequest, RequestTarget, StatusPredicate,
 };
-use crate::tls::Certificate;
+use crate::skip_debug::SkipDebug;
+#[cfg(feature = "tls")]
+use crate::tls::TlsConnector;
+use crate::tls::{Certificate, TlsVersion};
 
 /// `RequestBuilder` is the main way of building requests.
 ///
@@ -36,6 +51,7 @@ pub struct RequestBuilder<B = body::Empty> {
     method: Method,
     body: B,
     base_settings: BaseSettings,
+    extensions: Extensions,
 }
 
 impl RequestBuilder {
@@ -61,6 +77,17 @@ impl RequestBuilder {
         Self::try_with_settings(method, base_url, BaseSettings::default())
     }
 
+    /// Try to create a new `RequestBuilder` with a method given by name, such as `PROPFIND` or `PURGE`.
+    ///
+    /// The method is sent verbatim. If it is not a valid HTTP token, an error is returned.
+    /// The same rules as `try_new` apply to the base URL and to CONNECT.
+    pub fn custom_method<U>(method: &str, base_url: U) -> Result<Self>
+    where
+        U: AsRef<str>,
+    {
+        Self::try_new(Method::from_bytes(method.as_bytes())?, base_url)
+    }
+
     pub(crate) fn with_settings<U>(method: Method, base_url: U, base_settings: BaseSettings) -> Self
     where
         U: AsRef<str>,
@@ -68,11 +95,19 @@ impl RequestBuilder {
         Self::try_with_settings(method, base_url, base_settings).expect("invalid url or method")
     }
 
+    pub(crate) fn set_extensions(&mut self, extensions: Extensions) {
+        self.extensions = extensions;
+    }
+
     pub(crate) fn try_with_settings<U>(method: Method, base_url: U, base_settings: BaseSettings) -> Result<Self>
     where
         U: AsRef<str>,
     {
-        let url = Url::parse(base_url.as_ref()).map_err(|_| ErrorKind::InvalidBaseUrl)?;
+        let url = match &base_settings.base_url {
+            Some(base) => base.join(base_url.as_ref()),
+            None => Url::parse(base_url.as_ref()),
+        }
+        .map_err(|_| ErrorKind::InvalidBaseUrl)?;
 
         if method == Method::CONNECT {
             return Err(ErrorKind::ConnectNotSupported.into());
@@ -83,6 +118,65 @@ impl RequestBuilder {
             method,
             body: body::Empty,
             base_settings,
+            extensions: Extensions::new(),
+        })
+    }
+}
+
+impl RequestBuilder {
+    /// Download the resource to the given path, resuming a previous partial download.
+    ///
+    /// If the file already contains data, only the missing bytes are requested using the `Range`
+    /// header and appended to the file. If the server does not support ranges and sends the whole
+    /// resource, the file is truncated and the download restarts from the beginning.
+    ///
+    /// Interrupted transfers are retried a few times, with a delay which starts at 100 milliseconds and doubles
+    /// after each attempt. Refused connections and other errors which are unlikely to go away are returned
+    /// right away. Between attempts, the `ETag` or `Last-Modified` header of the first response is sent back in
+    /// the `If-Range` header and compared before appending, so that a resource which changed in the meantime is
+    /// downloaded again in full. A partial file left by a previous call cannot be verified this way.
+    ///
+    /// Ranges count the bytes of the body as sent, so compression is not requested. A partial response with a
+    /// `Content-Encoding` header anyway is an
+    /// [`InvalidResponseKind::EncodedRange`](crate::InvalidResponseKind::EncodedRange) error.
+    ///
+    /// The final length of the file is returned.
+    pub fn download_resumable<P>(self, path: P) -> Result<u64>
+    where
+        P: AsRef<Path>,
+    {
+        download::download_resumable(path.as_ref(), || RequestBuilder {
+            url: self.url.clone(),
+            method: self.method.clone(),
+            body: body::Empty,
+            base_settings: self.base_settings.clone(),
+            extensions: Extensions::new(),
+        })
+    }
+}
+
+impl RequestBuilder {
+    /// Download the resource to the given path in several parts fetched concurrently.
+    ///
+    /// A `HEAD` request is sent first to learn the length of the resource and if the server supports byte
+    /// ranges. If it does, the resource is split into `parts` ranges which are requested on as many threads
+    /// and written at their offset in the file. Otherwise, or if the server ignores the ranges, the resource
+    /// is downloaded sequentially like [`download_resumable`](Self::download_resumable) would from an empty
+    /// file. The file is truncated either way.
+    ///
+    /// The parts are not retried, a failed part fails the whole download. Like for `download_resumable`,
+    /// compression is not requested and a partial response with a `Content-Encoding` header is an error. The
+    /// final length of the file is returned.
+    pub fn download_parallel<P>(self, path: P, parts: usize) -> Result<u64>
+    where
+        P: AsRef<Path>,
+    {
+        download::download_parallel(path.as_ref(), parts, move |method| RequestBuilder {
+            url: self.url.clone(),
+            method,
+            body: body::Empty,
+            base_settings: self.base_settings.clone(),
+            extensions: Extensions::new(),
         })
     }
 }
@@ -102,7 +196,8 @@ impl<B> RequestBuilder<B> {
 
     /// Associated a list of pairs to query parameters.
     ///
-    /// The same key can be used multiple times.
+    /// The same key can be used multiple times, for instance `&[("tag", "a"), ("tag", "b")]`. Keys and values are
+    /// percent-encoded and appended in order after the query already in the URL.
     ///
     /// # Example
     /// ```
@@ -122,6 +217,46 @@ impl<B> RequestBuilder<B> {
         self
     }
 
+    /// Remove the query string of the URL, including the parameters added so far.
+    ///
+    /// # Example
+    /// ```
+    /// attohttpc::get("http://foo.bar/?page=2&sort=asc").clear_query().param("page", 3);
+    /// ```
+    pub fn clear_query(mut self) -> Self {
+        self.url.set_query(None);
+        self
+    }
+
+    /// Percent-encode the characters of the path and query of the URL which are not allowed there.
+    ///
+    /// Spaces and non-ASCII characters are already encoded when the URL is parsed, but characters
+    /// such as `|`, `^`, `[` or `]` and `%` signs which do not start an escape sequence are kept as is,
+    /// which some servers reject. Valid URLs and existing escape sequences are left untouched.
+    pub fn encode_url(mut self) -> Self {
+        let path = encode_url_component(self.url.path(), b"/:@");
+        self.url.set_path(&path);
+        if let Some(query) = self.url.query() {
+            let query = encode_url_component(query, b"/:@?");
+            self.url.set_query(Some(&query));
+        }
+        self
+    }
+
+    /// Request a range of bytes of the resource using the `Range` header.
+    ///
+    /// Both positions are inclusive. If `end` is `None`, every byte from `start` until the end
+    /// of the resource is requested. Servers are free to ignore the range and send the whole
+    /// resource, use [`Response::content_range`](crate::Response::content_range) to know what
+    /// was returned.
+    pub fn range(self, start: u64, end: Option<u64>) -> Self {
+        let value = match end {
+            Some(end) => format!("bytes={}-{}", start, end),
+            None => format!("bytes={}-", start),
+        };
+        self.header(http::header::RANGE, value)
+    }
+
     /// Enable HTTP basic authentication.
     ///
     /// This is available only on Linux and when TLS support is enabled.
@@ -145,6 +280,42 @@ impl<B> RequestBuilder<B> {
         self.header(http::header::AUTHORIZATION, format!("Bearer {}", token.into()))
     }
 
+    /// Append a `Forwarded` header describing a hop of this request through a proxy.
+    ///
+    /// The header is appended, so calling this method for each proxy builds the whole chain.
+    ///
+    /// # Panics
+    /// This method will panic if a parameter contains characters which are not allowed in headers, such as
+    /// newlines.
+    pub fn forwarded(self, params: ForwardedParams) -> Self {
+        self.header_append(FORWARDED, params.to_string())
+    }
+
+    /// Append a `Forwarded` header with the address of the client which made this request, e.g. `for=192.0.2.60`.
+    pub fn forwarded_for(self, ip: IpAddr) -> Self {
+        self.forwarded(ForwardedParams::new().for_ip(ip))
+    }
+
+    /// Attach a value to this request, which can be read back from the extensions of its response.
+    ///
+    /// Values are identified by their type, a value replaces the previous one of the same type. They are not
+    /// sent to the server, they let code wrapping requests correlate them with their responses, for instance
+    /// with a request id. They are moved to the first response, a `PreparedRequest` sent again has none.
+    ///
+    /// # Example
+    /// ```
+    /// struct RequestId(u64);
+    ///
+    /// attohttpc::get("http://foo.bar").extension(RequestId(42));
+    /// ```
+    pub fn extension<T>(mut self, value: T) -> Self
+    where
+        T: Send + Sync + 'static,
+    {
+        self.extensions.insert(value);
+        self
+    }
+
     /// Set the body of this request.
     ///
     /// The [BodyKind enum](crate::body::BodyKind) and [Body trait](crate::body::Body)
@@ -155,12 +326,24 @@ impl<B> RequestBuilder<B> {
             method: self.method,
             body,
             base_settings: self.base_settings,
+            extensions: self.extensions,
         }
     }
 
+    /// Set the body of this request to be explicitly empty.
+    ///
+    /// The `Content-Length: 0` header is sent whatever the method. Without a body, it is only sent for the
+    /// `POST`, `PUT` and `PATCH` methods and it is omitted for the other methods, such as `GET` and `HEAD`.
+    pub fn empty_body(self) -> RequestBuilder<body::Bytes<&'static [u8]>> {
+        self.body(body::Bytes(&[]))
+    }
+
     /// Set the body of this request to be text.
     ///
     /// If the `Content-Type` header is unset, it will be set to `text/plain` and the carset to UTF-8.
+    ///
+    /// The body is written out from a reference to `body`, so a `&str` or a borrowed `Cow<str>` is sent without
+    /// being copied while a `String` or an owned `Cow<str>` is moved into the request.
     pub fn text<B1: AsRef<str>>(mut self, body: B1) -> RequestBuilder<body::Text<B1>> {
         self.base_settings
             .headers
@@ -169,9 +352,39 @@ impl<B> RequestBuilder<B> {
         self.body(body::Text(body))
     }
 
+    /// Set the body of this request to be text encoded in the given charset.
+    ///
+    /// Characters which cannot be represented in the charset are replaced by numeric character references such
+    /// as `&#9731;` if `escape_unmappable` is set, otherwise they fail with
+    /// [`ErrorKind::Encoding`](crate::ErrorKind::Encoding). The UTF-16 charsets are encoded as UTF-8, like
+    /// browsers do.
+    ///
+    /// If the `Content-Type` header is unset, it will be set to `text/plain` and the charset to the one used.
+    ///
+    /// This method only exists when the `charsets` feature is enabled.
+    #[cfg(feature = "charsets")]
+    pub fn text_with_charset(
+        mut self,
+        text: &str,
+        charset: Charset,
+        escape_unmappable: bool,
+    ) -> Result<RequestBuilder<body::Bytes<Vec<u8>>>> {
+        let body = charsets::encode(text, charset, escape_unmappable)?;
+        if !self.base_settings.headers.contains_key(CONTENT_TYPE) {
+            let content_type = format!("text/plain; charset={}", charset.output_encoding().name());
+            self.base_settings
+                .headers
+                .insert(CONTENT_TYPE, HeaderValue::from_str(&content_type)?);
+        }
+        Ok(self.body(body::Bytes(body)))
+    }
+
     /// Set the body of this request to be bytes.
     ///
     /// If the `Content-Type` header is unset, it will be set to `application/octet-stream`.
+    ///
+    /// The body is written out from a reference to `body`, so a `&[u8]` or a borrowed `Cow<[u8]>` is sent
+    /// without being copied while a `Vec<u8>` or an owned `Cow<[u8]>` is moved into the request.
     pub fn bytes<B1: AsRef<[u8]>>(mut self, body: B1) -> RequestBuilder<body::Bytes<B1>> {
         self.base_settings
             .headers
@@ -191,6 +404,59 @@ impl<B> RequestBuilder<B> {
         self.body(body::File(body))
     }
 
+    /// Set the body of this request to stream the file at the given path.
+    ///
+    /// The file is opened right away and its length is sent in the `Content-Length` header. If the
+    /// `Content-Type` header is unset, it is guessed from the extension of the file when the `mime` feature
+    /// is enabled, and set to `application/octet-stream` otherwise or if the extension is unknown.
+    pub fn file_path<P: AsRef<Path>>(self, path: P) -> Result<RequestBuilder<body::File>> {
+        let path = path.as_ref();
+        let file = fs::File::open(path)?;
+
+        #[cfg(feature = "mime")]
+        if let Some(mime) = mime_guess::from_path(path).first_raw() {
+            let mut builder = self;
+            builder
+                .base_settings
+                .headers
+                .entry(http::header::CONTENT_TYPE)
+                .or_insert(HeaderValue::from_static(mime));
+            return Ok(builder.file(file));
+        }
+
+        Ok(self.file(file))
+    }
+
+    /// Set the body of this request to stream the content of a reader whose length is known in advance.
+    ///
+    /// The `Content-Length` header is set to `len` and chunked encoding is not used. Sending the request fails
+    /// with [`ErrorKind::BodyLengthMismatch`](crate::ErrorKind::BodyLengthMismatch) if the reader produces more
+    /// or fewer than `len` bytes.
+    ///
+    /// If the `Content-Type` header is unset, it will be set to `application/octet-stream`.
+    pub fn body_reader_sized<R: Read>(mut self, reader: R, len: u64) -> RequestBuilder<body::SizedReader<R>> {
+        self.base_settings
+            .headers
+            .entry(http::header::CONTENT_TYPE)
+            .or_insert(HeaderValue::from_static("application/octet-stream"));
+        self.body(body::SizedReader::new(reader, len))
+    }
+
+    /// Set the body of this request to the chunks received from a channel.
+    ///
+    /// The body is sent with chunked encoding as the chunks are received, until every sender is dropped.
+    /// Since the channel is drained, the body cannot be sent again: redirections are not followed and the
+    /// `3xx` response is returned instead.
+    ///
+    /// If the `Content-Type` header is unset, it will be set to `application/octet-stream`.
+    pub fn body_channel(mut self, rx: Receiver<Vec<u8>>) -> RequestBuilder<body::Channel> {
+        self.base_settings
+            .headers
+            .entry(http::header::CONTENT_TYPE)
+            .or_insert(HeaderValue::from_static("application/octet-stream"));
+        self.body(body::Channel(rx))
+    }
+
     /// Set the body of this request to be the JSON representation of the given object.
     ///
     /// If the `Content-Type` header is unset, it will be set to `application/json` and the charset to UTF-8.
@@ -204,6 +470,34 @@ impl<B> RequestBuilder<B> {
         Ok(self.body(body::Bytes(body)))
     }
 
+    /// Set the body of this request to be the XML representation of the given object.
+    ///
+    /// The root element is named after the type of the object. If the `Content-Type` header is unset, it will be
+    /// set to `application/xml` and the charset to UTF-8.
+    #[cfg(feature = "xml")]
+    pub fn xml<T: serde::Serialize>(mut self, value: &T) -> Result<RequestBuilder<body::Bytes<Vec<u8>>>> {
+        let body = quick_xml::se::to_string(value)?;
+        self.base_settings
+            .headers
+            .entry(http::header::CONTENT_TYPE)
+            .or_insert(HeaderValue::from_static("application/xml; charset=utf-8"));
+        Ok(self.body(body::Bytes(body.into_bytes())))
+    }
+
+    /// Set the body of this request to be the pretty-printed JSON representation of the given object.
+    ///
+    /// This is the same as [`json`](Self::json) with newlines and indentation, which makes captured
+    /// requests easier to read.
+    #[cfg(feature = "json")]
+    pub fn json_pretty<T: serde::Serialize>(mut self, value: &T) -> Result<RequestBuilder<body::Bytes<Vec<u8>>>> {
+        let body = serde_json::to_vec_pretty(value)?;
+        self.base_settings
+            .headers
+            .entry(http::header::CONTENT_TYPE)
+            .or_insert(HeaderValue::from_static("application/json; charset=utf-8"));
+        Ok(self.body(body::Bytes(body)))
+    }
+
     /// Set the body of this request to stream out a JSON representation of the given object.
     ///
     /// If the `Content-Type` header is unset, it will be set to `application/json` and the charset to UTF-8.
@@ -216,6 +510,23 @@ impl<B> RequestBuilder<B> {
         self.body(body::Json(value))
     }
 
+    /// Set the body of this request to stream out the items of an iterator as newline-delimited JSON.
+    ///
+    /// Each item is serialized on its own line and the body is sent with chunked encoding as the items are
+    /// produced. If the `Content-Type` header is unset, it will be set to `application/x-ndjson`.
+    #[cfg(feature = "json")]
+    pub fn ndjson<T, I>(mut self, items: I) -> RequestBuilder<body::NdJson<I::IntoIter>>
+    where
+        T: serde::Serialize,
+        I: IntoIterator<Item = T>,
+    {
+        self.base_settings
+            .headers
+            .entry(http::header::CONTENT_TYPE)
+            .or_insert(HeaderValue::from_static("application/x-ndjson"));
+        self.body(body::NdJson::new(items))
+    }
+
     /// Set the body of this request to be the URL-encoded representation of the given object.
     ///
     /// If the `Content-Type` header is unset, it will be set to `application/x-www-form-urlencoded`.
@@ -229,10 +540,95 @@ impl<B> RequestBuilder<B> {
         Ok(self.body(body::Bytes(body)))
     }
 
+    /// Compress the body of this request using gzip.
+    ///
+    /// The `Content-Encoding` header is set to `gzip` when the request is prepared, unless the body is empty.
+    /// Bodies with a known length are compressed before being sent so that the `Content-Length` header matches
+    /// the compressed size, streamed bodies are compressed on the fly. Only use this if the server is known to
+    /// accept compressed request bodies.
+    #[cfg(feature = "compress")]
+    pub fn gzip_body(self) -> RequestBuilder<body::Gzip<B>>
+    where
+        B: Body,
+    {
+        let body = body::Gzip::new(self.body);
+        RequestBuilder {
+            url: self.url,
+            method: self.method,
+            body,
+            base_settings: self.base_settings,
+            extensions: self.extensions,
+        }
+    }
+
+    /// Compress the body of this request using gzip if it is larger than `threshold` bytes.
+    ///
+    /// Compressing small bodies costs more than sending them as they are. Bodies with a known length are
+    /// compressed like [`gzip_body`](Self::gzip_body) does if they are larger than the threshold, otherwise they
+    /// are sent uncompressed without a `Content-Encoding` header. The length of streamed bodies is unknown, they
+    /// are always compressed, and so are bodies whose length cannot be determined, such as files whose metadata
+    /// cannot be read.
+    #[cfg(feature = "compress")]
+    pub fn gzip_body_if_larger(mut self, threshold: usize) -> RequestBuilder<body::Gzip<B>>
+    where
+        B: Body,
+    {
+        let compress = match self.body.kind() {
+            Ok(BodyKind::Empty) => false,
+            Ok(BodyKind::KnownLength(len)) => len > threshold as u64,
+            Ok(BodyKind::Chunked) | Err(_) => true,
+        };
+        if compress {
+            return self.gzip_body();
+        }
+        let body = body::Gzip::uncompressed(self.body);
+        RequestBuilder {
+            url: self.url,
+            method: self.method,
+            body,
+            base_settings: self.base_settings,
+            extensions: self.extensions,
+        }
+    }
+
+    /// Read the whole body of this request into memory, so that it is sent with a `Content-Length` header.
+    ///
+    /// Streamed bodies, such as the ones of `body_channel` or `json_streaming`, are otherwise sent with chunked
+    /// encoding, which some servers reject. The entire body is kept in memory until the request is dropped,
+    /// avoid this for large bodies. Unlike a body streamed from a reader, a buffered body can be sent again
+    /// when the request is redirected or retried.
+    pub fn buffer_body(mut self) -> Result<RequestBuilder<body::Bytes<Vec<u8>>>>
+    where
+        B: Body,
+    {
+        let mut buf = Vec::new();
+        self.body.kind()?;
+        self.body.write(&mut buf)?;
+        if let Some(typ) = self.body.content_type()? {
+            header_insert(&mut self.base_settings.headers, CONTENT_TYPE, typ)?;
+        }
+        if let Some(encoding) = self.body.content_encoding()? {
+            header_insert(&mut self.base_settings.headers, CONTENT_ENCODING, encoding)?;
+        }
+        Ok(self.body(body::Bytes(buf)))
+    }
+
     //
     // Settings
     //
 
+    /// Set the `Content-Type` header of this request.
+    ///
+    /// Body setters such as `json` or `bytes` only set a `Content-Type` when none is set yet, while this
+    /// method and `header` always replace it. The explicit type therefore wins whether it is set before or
+    /// after the body, for instance `application/vnd.api+json` for a body set with `json`.
+    ///
+    /// # Panics
+    /// This method will panic if the value is invalid.
+    pub fn content_type(self, mime: &str) -> Self {
+        self.header(CONTENT_TYPE, mime)
+    }
+
     /// Modify a header for this request.
     ///
     /// If the header is already present, the value will be replaced. If you wish to append a new header,
@@ -291,6 +687,29 @@ impl<B> RequestBuilder<B> {
         Ok(self)
     }
 
+    /// Override the `Host` header sent with this request.
+    ///
+    /// By default the `Host` header is derived from the URL. The connection is still made to the
+    /// URL's address, which is useful to test virtual host routing. The override only applies while
+    /// the request stays on the host of its URL, redirections to another host get their own `Host`.
+    ///
+    /// The `Host` header cannot be omitted: requests are always sent as HTTP/1.1, which requires it, and
+    /// servers answer a request without it with `400 Bad Request`.
+    ///
+    /// Fails if the value is not a valid header value.
+    pub fn host_header(mut self, host: &str) -> Result<Self> {
+        self.base_settings.host_header = Some(HeaderValue::from_str(host)?);
+        Ok(self)
+    }
+
+    /// Set the form of the target written in the request line.
+    ///
+    /// By default the origin form is used, or the absolute form when the request goes through an HTTP proxy.
+    pub fn request_target(mut self, target: RequestTarget) -> Self {
+        self.base_settings.request_target = Some(target);
+        self
+    }
+
     /// Set the maximum number of redirections this request can perform.
     ///
     /// The default is 5.
@@ -301,12 +720,134 @@ impl<B> RequestBuilder<B> {
 
     /// Sets if this request should follow redirects, 3xx codes.
     ///
-    /// This value defaults to true.
+    /// This value defaults to true. Redirections are never followed by requests whose body is streamed
+    /// from a reader or a channel, since it cannot be sent again.
     pub fn follow_redirects(mut self, follow_redirects: bool) -> Self {
         self.base_settings.follow_redirects = follow_redirects;
         self
     }
 
+    /// Follow the zero-delay `<meta http-equiv="refresh">` tags of the successful HTML responses to GET requests.
+    ///
+    /// Some pages redirect this way instead of with a 3xx status. The target is fetched like a redirection: it
+    /// counts toward `max_redirections`, its scheme must be allowed and it is recorded in the redirection history.
+    /// The body of HTML responses is read into memory to be searched. This is disabled by default.
+    pub fn follow_meta_refresh(mut self) -> Self {
+        self.base_settings.follow_meta_refresh = true;
+        self
+    }
+
+    /// Sets the URL schemes redirections may lead to.
+    ///
+    /// By default, only redirections to `http` and `https` URLs are followed, others fail with
+    /// [`InvalidResponseKind::RedirectionUrl`](crate::InvalidResponseKind::RedirectionUrl) instead of being
+    /// attempted. Passing `&["https"]` refuses redirections from HTTPS to plain HTTP. Schemes other than
+    /// `http` and `https` can be allowed but cannot be connected to.
+    pub fn allowed_redirect_schemes(mut self, schemes: &[&str]) -> Self {
+        self.base_settings.allowed_redirect_schemes = schemes.iter().map(|scheme| scheme.to_string()).collect();
+        self
+    }
+
+    /// Sets the `TCP_NODELAY` option of the connection, which disables Nagle's algorithm.
+    ///
+    /// Small writes are then sent immediately instead of being grouped, which lowers the latency of small
+    /// requests at the cost of more packets. The default value is `false`.
+    pub fn tcp_nodelay(mut self, nodelay: bool) -> Self {
+        self.base_settings.tcp_nodelay = nodelay;
+        self
+    }
+
+    /// Bind the connection of this request to the given local port before connecting.
+    ///
+    /// This is only useful when a firewall filters connections on their source port. `SO_REUSEADDR` is set so
+    /// that the port can be bound again while a previous connection is in `TIME_WAIT`, but connecting twice to the
+    /// same address from the same port at once is impossible. Connecting fails with
+    /// [`ErrorKind::LocalPortInUse`](crate::ErrorKind::LocalPortInUse) if another socket uses the port.
+    ///
+    /// This method only exists when the `socket2` feature is enabled.
+    #[cfg(feature = "socket2")]
+    pub fn local_port(mut self, port: u16) -> Self {
+        self.base_settings.local_port = Some(port);
+        self
+    }
+
+    /// Configure the sockets of this request before they connect, with options such as `SO_RCVBUF`.
+    ///
+    /// The callback is given the raw [`Socket`](crate::Socket) right after it is created, before it is bound
+    /// to the [`local_port`](Self::local_port) and before it connects, which gives access to options attohttpc
+    /// has no method for. It is called once per connection attempt, so possibly several times when a host has
+    /// several addresses or when redirections are followed. An error returned by the callback fails the
+    /// connection attempt.
+    ///
+    /// This method only exists when the `socket2` feature is enabled.
+    ///
+    /// # Example
+    /// ```
+    /// attohttpc::get("http://foo.bar").configure_socket(|socket| socket.set_recv_buffer_size(256 * 1024));
+    /// ```
+    #[cfg(feature = "socket2")]
+    pub fn configure_socket<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Socket) -> io::Result<()> + Send + Sync + 'static,
+    {
+        let callback: SocketCallback = Arc::new(callback);
+        self.base_settings.configure_socket = Some(SkipDebug(callback));
+        self
+    }
+
+    /// Flush the request line and headers to the connection before writing the body.
+    ///
+    /// The head of a request is buffered with the beginning of its body by default, so a server waiting for the
+    /// headers only receives them once the body starts being produced. With a body which is slow to produce, this
+    /// lets the server start working on the request early. Combine it with [`tcp_nodelay`](Self::tcp_nodelay) to
+    /// send the headers in their own packet without delay.
+    pub fn flush_headers_early(mut self) -> Self {
+        self.base_settings.flush_headers_early = true;
+        self
+    }
+
+    /// Set how long to wait for the server to answer a request sent with `Expect: 100-continue`, defaults to 1 second.
+    ///
+    /// The body of such a request is held back until the server sends `100 Continue`, or a final response which
+    /// refuses the request without the body being sent. Many servers ignore the header, so the body is sent
+    /// anyway once this timeout expires. Over TLS, to the server or to a proxy, the body is sent without waiting.
+    pub fn expect_continue_timeout(mut self, timeout: Duration) -> Self {
+        self.base_settings.expect_continue_timeout = timeout;
+        self
+    }
+
+    /// Refuse to connect to loopback, private, link-local and unique-local addresses, such as `127.0.0.1`,
+    /// `192.168.0.1`, `169.254.169.254` or `fd00::1`.
+    ///
+    /// This protects servers fetching URLs supplied by their users from reaching internal services. The addresses
+    /// a host resolves to are checked, so a public name pointing to an internal address is refused too, and every
+    /// redirection is checked again. Through a proxy, only hosts which are IP addresses can be checked. Requests
+    /// to blocked addresses fail with [`ErrorKind::HostBlocked`](crate::ErrorKind::HostBlocked).
+    pub fn block_private_addresses(mut self) -> Self {
+        self.base_settings.host_filter.block_private_addresses();
+        self
+    }
+
+    /// Only allow connecting to hosts matching one of the given patterns, such as `example.com` or `*.example.com`.
+    ///
+    /// Patterns are matched against the host of the URL of each request and redirection, `*` matches any
+    /// sequence of characters and `?` any single character. Other requests fail with
+    /// [`ErrorKind::HostBlocked`](crate::ErrorKind::HostBlocked).
+    pub fn allowed_hosts(mut self, patterns: &[&str]) -> Self {
+        self.base_settings.host_filter.allow_hosts(patterns);
+        self
+    }
+
+    /// Refuse to connect to hosts matching one of the given patterns, such as `internal.example.com` or `10.*`.
+    ///
+    /// Patterns are matched against the host of the URL of each request and redirection, and against the
+    /// addresses it resolves to. `*` matches any sequence of characters and `?` any single character. Blocked
+    /// requests fail with [`ErrorKind::HostBlocked`](crate::ErrorKind::HostBlocked).
+    pub fn blocked_hosts(mut self, patterns: &[&str]) -> Self {
+        self.base_settings.host_filter.block_hosts(patterns);
+        self
+    }
+
     /// Sets a connect timeout for this request.
     ///
     /// The default is 30 seconds.
@@ -315,22 +856,45 @@ impl<B> RequestBuilder<B> {
         self
     }
 
+    /// Set how many times to retry opening a connection which failed, defaults to 0.
+    ///
+    /// Connections which are refused, time out or cannot be resolved are retried whatever the method of the
+    /// request, since no part of it was sent yet. The delay between attempts starts at 100 milliseconds and
+    /// doubles after each of them. A request which fails once its connection is established is not retried.
+    pub fn connect_retries(mut self, retries: u32) -> Self {
+        self.base_settings.connect_retries = retries;
+        self
+    }
+
     /// Sets a read timeout for this request.
     ///
-    /// The default is 30 seconds.
+    /// The default is 30 seconds. When the request is created from a `Session`, this overrides the
+    /// `Session`'s read timeout.
     pub fn read_timeout(mut self, duration: Duration) -> Self {
-        self.base_settings.read_timeout = duration;
+        self.base_settings.read_timeout = Some(duration);
         self
     }
 
     /// Sets a timeout for the whole request.
     ///
-    /// Applies after a TCP connection is established. Defaults to no timeout.
+    /// Applies after a TCP connection is established. Defaults to no timeout. When the request is
+    /// created from a `Session`, this overrides the `Session`'s timeout.
     pub fn timeout(mut self, duration: Duration) -> Self {
         self.base_settings.timeout = Some(duration);
         self
     }
 
+    /// Removes the whole request timeout and the read timeout from this request.
+    ///
+    /// This is useful for long-polling endpoints, when the request is created from a `Session` which
+    /// has timeouts configured. The connect timeout is kept. Calling `timeout` or `read_timeout` after
+    /// this method sets the corresponding timeout again.
+    pub fn no_timeout(mut self) -> Self {
+        self.base_settings.timeout = None;
+        self.base_settings.read_timeout = None;
+        self
+    }
+
     /// Sets the proxy settigns for this request.
     ///
     /// If left untouched, the defaults are to use system proxy settings found in environment variables.
@@ -353,12 +917,87 @@ impl<B> RequestBuilder<B> {
     ///
     /// This value defaults to true. Note that this only lets the browser know that this request supports
     /// compression, the server might choose not to compress the content.
+    ///
+    /// An `Accept-Encoding` header set with [`header`](Self::header) is always sent as is. Either way,
+    /// responses are decompressed according to their `Content-Encoding` header.
     #[cfg(feature = "compress")]
     pub fn allow_compression(mut self, allow_compression: bool) -> Self {
         self.base_settings.allow_compression = allow_compression;
         self
     }
 
+    /// Do not add the `Accept-Encoding: gzip, deflate` header to this request.
+    ///
+    /// This is a shortcut for `allow_compression(false)`. Responses which are compressed anyway are still
+    /// decompressed according to their `Content-Encoding` header.
+    #[cfg(feature = "compress")]
+    pub fn no_auto_accept_encoding(self) -> Self {
+        self.allow_compression(false)
+    }
+
+    /// Set the maximum size of the status line and headers of the response, in bytes.
+    ///
+    /// This value defaults to 64 KiB. A response with a larger head, or with more than 100 headers, is
+    /// rejected with [`InvalidResponseKind::HeadersTooLarge`](crate::InvalidResponseKind::HeadersTooLarge).
+    pub fn max_header_size(mut self, bytes: usize) -> Self {
+        self.base_settings.max_header_size = bytes;
+        self
+    }
+
+    /// Set the maximum length of a line read by the line-based readers of the response, in bytes.
+    ///
+    /// This value defaults to 1 MiB. It bounds the memory used by [`Response::ndjson`](crate::Response::ndjson)
+    /// when a server never sends a newline, a longer line fails with
+    /// [`InvalidResponseKind::LineTooLong`](crate::InvalidResponseKind::LineTooLong).
+    ///
+    /// This method only exists when the `json` feature is enabled.
+    #[cfg(feature = "json")]
+    pub fn max_line_size(mut self, bytes: usize) -> Self {
+        self.base_settings.max_line_size = bytes;
+        self
+    }
+
+    /// Sets if responses whose framing is ambiguous are rejected.
+    ///
+    /// Such responses announce both a `Content-Length` and `Transfer-Encoding: chunked`, or have `Transfer-Encoding`
+    /// headers which do not end with a single `chunked` coding. This value defaults to false, in which case the
+    /// `Content-Length` header is ignored as the specification requires and bodies with other transfer codings are
+    /// read until the connection closes. The connection is not reused either way, since the server could be trying
+    /// to smuggle a response. Conflicting `Content-Length` headers are always rejected.
+    pub fn strict_framing(mut self, strict_framing: bool) -> Self {
+        self.base_settings.strict_framing = strict_framing;
+        self
+    }
+
+    /// Set a predicate deciding which statuses of the final response are successful.
+    ///
+    /// Sending the request fails with [`ErrorKind::StatusCode`](crate::ErrorKind::StatusCode) when the predicate
+    /// returns false, after redirections are followed. By default any status is returned as a response, use
+    /// [`Response::error_for_status`](crate::Response::error_for_status) to only accept 2xx statuses.
+    ///
+    /// # Example
+    /// ```
+    /// attohttpc::get("http://foo.bar").expect_status(|status| status.is_success() || status.is_redirection());
+    /// ```
+    pub fn expect_status<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(StatusCode) -> bool + Send + Sync + 'static,
+    {
+        let predicate: StatusPredicate = Arc::new(predicate);
+        self.base_settings.expect_status = Some(SkipDebug(predicate));
+        self
+    }
+
+    /// Cancel this request when the given token is cancelled, from any thread.
+    ///
+    /// Sending the request or reading its response fails with
+    /// [`ErrorKind::Cancelled`](crate::ErrorKind::Cancelled) once the token is cancelled. Reads of the
+    /// response body can only return I/O errors, they return one wrapping that error.
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.base_settings.cancel_token = Some(token);
+        self
+    }
+
     /// Sets if this request will accept invalid TLS certificates.
     ///
     /// Accepting invalid certificates implies that invalid hostnames are accepted
@@ -387,11 +1026,65 @@ impl<B> RequestBuilder<B> {
         self
     }
 
+    /// Set the name used for SNI and to validate the TLS certificate of the server.
+    ///
+    /// By default the host of the URL is used. When the host is an IP address, no SNI is sent and the
+    /// certificate is validated against the address. Setting a name is useful to connect to a server by its
+    /// address while validating its certificate against its usual name. The name is not used for proxies.
+    ///
+    /// The `rustls` backend cannot validate certificates against IP addresses, a name must be set to connect
+    /// to an IP address with it.
+    pub fn tls_server_name(mut self, name: impl Into<String>) -> Self {
+        self.base_settings.tls_server_name = Some(name.into());
+        self
+    }
+
     /// Adds a root certificate that will be trusted.
     pub fn add_root_certificate(mut self, cert: Certificate) -> Self {
         self.base_settings.root_certificates.0.push(cert);
         self
     }
+
+    /// Sets the oldest TLS version accepted for the connection, older versions are rejected.
+    ///
+    /// By default the minimum version of the TLS backend is used.
+    pub fn min_tls_version(mut self, version: TlsVersion) -> Self {
+        self.base_settings.min_tls_version = Some(version);
+        self
+    }
+
+    /// Sets the newest TLS version accepted for the connection.
+    ///
+    /// By default the maximum version of the TLS backend is used.
+    pub fn max_tls_version(mut self, version: TlsVersion) -> Self {
+        self.base_settings.max_tls_version = Some(version);
+        self
+    }
+
+    /// Sets the protocols advertised with ALPN during the TLS handshake, in order of preference.
+    ///
+    /// attohttpc only speaks HTTP/1.1, advertising another protocol is only useful to make the server accept
+    /// the connection, the negotiated protocol is available with
+    /// [`Response::alpn_protocol`](crate::Response::alpn_protocol). The protocols are ignored when a
+    /// custom `tls_connector` is set, it advertises its own.
+    pub fn alpn_protocols(mut self, protocols: &[&str]) -> Self {
+        self.base_settings.alpn_protocols = protocols.iter().map(|protocol| protocol.to_string()).collect();
+        self
+    }
+
+    /// Use a `native_tls::TlsConnector` configured by the caller for TLS connections.
+    ///
+    /// The connector supersedes `danger_accept_invalid_certs`, `danger_accept_invalid_hostnames`,
+    /// `add_root_certificate` and the TLS versions, which are ignored when it is set. SNI is sent according to
+    /// the connector as well, including for IP addresses. The server name set with `tls_server_name` is still
+    /// used.
+    ///
+    /// This method only exists when the `tls` feature is enabled.
+    #[cfg(feature = "tls")]
+    pub fn tls_connector(mut self, connector: TlsConnector) -> Self {
+        self.base_settings.tls_connector = Some(connector);
+        self
+    }
 }
 
 impl<B: Body> RequestBuilder<B> {
@@ -410,11 +1103,19 @@ impl<B: Body> RequestBuilder<B> {
             method: self.method,
             body: self.body,
             base_settings: self.base_settings,
+            extensions: self.extensions,
         };
 
-        header_insert(&mut prepped.base_settings.headers, CONNECTION, "close")?;
+        // Without a pool, the connection is only used for this request.
+        if prepped.base_settings.pool.is_none() {
+            header_insert(&mut prepped.base_settings.headers, CONNECTION, "close")?;
+        }
         prepped.set_compression()?;
         match prepped.body.kind()? {
+            // Methods which are expected to carry a body announce an empty one, some servers reject them otherwise.
+            BodyKind::Empty if matches!(prepped.method, Method::POST | Method::PUT | Method::PATCH) => {
+                header_insert(&mut prepped.base_settings.headers, CONTENT_LENGTH, 0)?;
+            }
             BodyKind::Empty => (),
             BodyKind::KnownLength(len) => {
                 header_insert(&mut prepped.base_settings.headers, CONTENT_LENGTH, len)?;
@@ -427,6 +1128,9 @@ impl<B: Body> RequestBuilder<B> {
         if let Some(typ) = prepped.body.content_type()? {
             header_insert(&mut prepped.base_settings.headers, CONTENT_TYPE, typ)?;
         }
+        if let Some(encoding) = prepped.body.content_encoding()? {
+            header_insert(&mut prepped.base_settings.headers, CONTENT_ENCODING, encoding)?;
+        }
 
         header_insert_if_missing(&mut prepped.base_settings.headers, ACCEPT, "*/*")?;
         header_insert_if_missing(
@@ -475,6 +1179,33 @@ impl<B> RequestInspector<'_, B> {
     pub fn headers(&self) -> &HeaderMap {
         &self.0.base_settings.headers
     }
+
+    /// Access the values attached with `extension`
+    pub fn extensions(&self) -> &Extensions {
+        &self.0.extensions
+    }
+}
+
+/// Percent-encode the bytes which are neither unreserved, sub-delimiters nor part of `allowed`.
+fn encode_url_component(value: &str, allowed: &[u8]) -> String {
+    let bytes = value.as_bytes();
+    let mut encoded = String::with_capacity(bytes.len());
+    for (i, &byte) in bytes.iter().enumerate() {
+        let valid = match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => true,
+            b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=' => true,
+            b'%' => bytes
+                .get(i + 1..i + 3)
+                .is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit)),
+            _ => allowed.contains(&byte),
+        };
+        if valid {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
 }
 
 #[test]
@@ -488,3 +1219,244 @@ fn test_accept_invalid_certs_disabled_by_default() {
     assert_eq!(prepped.base_settings.accept_invalid_certs, false);
     assert_eq!(prepped.base_settings.accept_invalid_hostnames, false);
 }
+
+#[test]
+fn test_range_header() {
+    let mut builder = RequestBuilder::new(Method::GET, "http://localhost").range(10, Some(19));
+    assert_eq!(builder.inspect().headers()[http::header::RANGE], "bytes=10-19");
+
+    let mut builder = RequestBuilder::new(Method::GET, "http://localhost").range(10, None);
+    assert_eq!(builder.inspect().headers()[http::header::RANGE], "bytes=10-");
+}
+
+#[test]
+fn test_custom_method() {
+    let mut req = RequestBuilder::custom_method("PROPFIND", "http://localhost/dav")
+        .unwrap()
+        .prepare();
+    assert_eq!(req.method().as_str(), "PROPFIND");
+
+    let mut buf: Vec<u8> = vec![];
+    req.write_request(&mut buf, &req.url.clone(), None).unwrap();
+    assert!(buf.starts_with(b"PROPFIND /dav HTTP/1.1\r\n"));
+}
+
+#[test]
+fn test_custom_method_invalid() {
+    let err = RequestBuilder::custom_method("BAD METHOD", "http://localhost").unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::Http(_)));
+
+    assert!(RequestBuilder::custom_method("", "http://localhost").is_err());
+}
+
+#[test]
+fn test_empty_body_content_length() {
+    let req = RequestBuilder::new(Method::GET, "http://localhost").prepare();
+    assert!(!req.headers().contains_key(CONTENT_LENGTH));
+
+    let req = RequestBuilder::new(Method::HEAD, "http://localhost").prepare();
+    assert!(!req.headers().contains_key(CONTENT_LENGTH));
+
+    let req = RequestBuilder::new(Method::POST, "http://localhost").prepare();
+    assert_eq!(req.headers()[CONTENT_LENGTH], "0");
+
+    let req = RequestBuilder::new(Method::PUT, "http://localhost").prepare();
+    assert_eq!(req.headers()[CONTENT_LENGTH], "0");
+
+    let req = RequestBuilder::new(Method::GET, "http://localhost")
+        .empty_body()
+        .prepare();
+    assert_eq!(req.headers()[CONTENT_LENGTH], "0");
+    assert!(!req.headers().contains_key(CONTENT_TYPE));
+}
+
+#[test]
+fn test_params_keep_order_and_duplicates() {
+    let builder = RequestBuilder::new(Method::GET, "http://localhost/?page=2").params(&[
+        ("tag", "a"),
+        ("q", "fish & chips"),
+        ("tag", "b"),
+        ("caf\u{e9}=", "50%+1"),
+    ]);
+    assert_eq!(
+        builder.url.query(),
+        Some("page=2&tag=a&q=fish+%26+chips&tag=b&caf%C3%A9%3D=50%25%2B1")
+    );
+    let pairs: Vec<_> = builder.url.query_pairs().collect();
+    assert_eq!(pairs[1], ("tag".into(), "a".into()));
+    assert_eq!(pairs[3], ("tag".into(), "b".into()));
+    assert_eq!(pairs[4], ("caf\u{e9}=".into(), "50%+1".into()));
+
+    let builder = RequestBuilder::new(Method::GET, "http://localhost/").params(&[("a", "1"), ("a", "2")]);
+    assert_eq!(builder.url.as_str(), "http://localhost/?a=1&a=2");
+}
+
+#[test]
+fn test_clear_query() {
+    let builder = RequestBuilder::new(Method::GET, "http://localhost/items?page=2&sort=asc#top")
+        .clear_query()
+        .param("page", 3)
+        .params(&[("tag", "a")]);
+    assert_eq!(builder.url.as_str(), "http://localhost/items?page=3&tag=a#top");
+
+    let builder = RequestBuilder::new(Method::GET, "http://localhost/items?page=2").clear_query();
+    assert_eq!(builder.url.as_str(), "http://localhost/items");
+}
+
+#[test]
+fn test_encode_url() {
+    let builder =
+        RequestBuilder::new(Method::GET, "http://localhost/caf\u{e9}/a|b?q=hello world&r=[1]&p=100%").encode_url();
+    assert_eq!(builder.url.path(), "/caf%C3%A9/a%7Cb");
+    assert_eq!(builder.url.query(), Some("q=hello%20world&r=%5B1%5D&p=100%25"));
+
+    let url = "http://localhost/a%20b/c:d@e?x=1&y=%41/?";
+    let builder = RequestBuilder::new(Method::GET, url).encode_url();
+    assert_eq!(builder.url.as_str(), url);
+}
+
+#[test]
+#[cfg(feature = "compress")]
+fn test_accept_encoding() {
+    use http::header::ACCEPT_ENCODING;
+
+    let req = RequestBuilder::new(Method::GET, "http://localhost").prepare();
+    assert_eq!(req.headers()[ACCEPT_ENCODING], "gzip, deflate");
+
+    let req = RequestBuilder::new(Method::GET, "http://localhost")
+        .no_auto_accept_encoding()
+        .prepare();
+    assert!(!req.headers().contains_key(ACCEPT_ENCODING));
+
+    let req = RequestBuilder::new(Method::GET, "http://localhost")
+        .header(ACCEPT_ENCODING, "gzip")
+        .prepare();
+    assert_eq!(req.headers()[ACCEPT_ENCODING], "gzip");
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn test_json_pretty() {
+    let value = serde_json::json!({"name": "attohttpc", "tags": ["http"]});
+
+    let mut builder = RequestBuilder::new(Method::POST, "http://localhost")
+        .json_pretty(&value)
+        .unwrap();
+    let mut inspector = builder.inspect();
+    assert_eq!(inspector.headers()[CONTENT_TYPE], "application/json; charset=utf-8");
+    let body = str::from_utf8(&inspector.body().0).unwrap();
+    assert_eq!(
+        body,
+        "{\n  \"name\": \"attohttpc\",\n  \"tags\": [\n    \"http\"\n  ]\n}"
+    );
+
+    let mut builder = RequestBuilder::new(Method::POST, "http://localhost")
+        .json(&value)
+        .unwrap();
+    assert!(!builder.inspect().body().0.contains(&b'\n'));
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn test_ndjson_body() {
+    let items = vec![
+        serde_json::json!({"id": 1}),
+        serde_json::json!({"id": 2, "tags": ["a"]}),
+        serde_json::json!("three"),
+    ];
+    let mut req = RequestBuilder::new(Method::POST, "http://localhost")
+        .ndjson(items)
+        .prepare();
+    assert_eq!(req.headers()[CONTENT_TYPE], "application/x-ndjson");
+
+    let mut buf = Vec::new();
+    req.write_request(&mut buf, &req.url.clone(), None).unwrap();
+    let text = String::from_utf8(buf).unwrap();
+    assert!(text.contains("\r\ntransfer-encoding: chunked\r\n"));
+    let body = "{\"id\":1}\n{\"id\":2,\"tags\":[\"a\"]}\n\"three\"\n";
+    assert!(text.ends_with(&format!("\r\n\r\n{:x}\r\n{}\r\n0\r\n\r\n", body.len(), body)));
+}
+
+#[test]
+fn test_content_type_overrides_body_default() {
+    let req = RequestBuilder::new(Method::POST, "http://localhost")
+        .bytes("data")
+        .content_type("application/vnd.api+json")
+        .prepare();
+    assert_eq!(req.headers()[CONTENT_TYPE], "application/vnd.api+json");
+
+    let req = RequestBuilder::new(Method::POST, "http://localhost")
+        .content_type("application/vnd.api+json")
+        .bytes("data")
+        .prepare();
+    assert_eq!(req.headers()[CONTENT_TYPE], "application/vnd.api+json");
+
+    let req = RequestBuilder::new(Method::POST, "http://localhost")
+        .bytes("data")
+        .header("content-type", "text/csv")
+        .prepare();
+    assert_eq!(req.headers()[CONTENT_TYPE], "text/csv");
+}
+
+#[test]
+#[cfg(feature = "json")]
+fn test_content_type_overrides_json() {
+    let mut req = RequestBuilder::new(Method::POST, "http://localhost")
+        .json(&[1, 2])
+        .unwrap()
+        .content_type("application/vnd.api+json")
+        .prepare();
+    assert_eq!(req.headers()[CONTENT_TYPE], "application/vnd.api+json");
+
+    let mut buf = Vec::new();
+    req.write_request(&mut buf, &req.url.clone(), None).unwrap();
+    let text = String::from_utf8(buf).unwrap();
+    assert!(text.contains("\r\ncontent-type: application/vnd.api+json\r\n"));
+    assert!(text.ends_with("\r\n\r\n[1,2]"));
+}
+
+#[test]
+#[cfg(feature = "charsets")]
+fn test_text_with_charset() {
+    let mut req = RequestBuilder::new(Method::POST, "http://localhost")
+        .text_with_charset("Cr\u{e8}me br\u{fb}l\u{e9}e", crate::charsets::WINDOWS_1252, false)
+        .unwrap()
+        .prepare();
+    assert_eq!(req.headers()[CONTENT_TYPE], "text/plain; charset=windows-1252");
+
+    let mut buf = Vec::new();
+    req.write_request(&mut buf, &req.url.clone(), None).unwrap();
+    let head_end = buf.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+    assert!(String::from_utf8_lossy(&buf[..head_end]).contains("\r\ncontent-length: 12"));
+    assert_eq!(&buf[head_end + 4..], b"Cr\xe8me br\xfbl\xe9e");
+
+    let err = RequestBuilder::new(Method::POST, "http://localhost")
+        .text_with_charset("\u{2603}", crate::charsets::WINDOWS_1252, false)
+        .unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::Encoding {
+            character: '\u{2603}',
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_forwarded_for() {
+    let req = RequestBuilder::new(Method::GET, "http://localhost")
+        .forwarded_for("192.0.2.60".parse().unwrap())
+        .forwarded_for("2001:db8:cafe::17".parse().unwrap())
+        .prepare();
+    let values: Vec<_> = req.headers().get_all(FORWARDED).iter().collect();
+    assert_eq!(values, ["for=192.0.2.60", "for=\"[2001:db8:cafe::17]\""]);
+
+    let req = RequestBuilder::new(Method::GET, "http://localhost")
+        .forwarded(
+            ForwardedParams::new()
+                .for_ip("198.51.100.17".parse().unwrap())
+                .proto("https"),
+        )
+        .prepare();
+    assert_eq!(req.headers()[FORWARDED], "for=198.51.100.17;proto=https");
+}
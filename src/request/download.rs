@@ -0,0 +1,265 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+use http::header::{HeaderMap, HeaderValue, CONTENT_ENCODING, ETAG, IF_RANGE, LAST_MODIFIED};
+use http::{Method, StatusCode};
+
+use crate::error::{Error, ErrorKind, InvalidResponseKind, Result};
+use crate::parsing::AcceptRanges;
+use crate::request::{is_closed_connection, retry_delay, RequestBuilder};
+
+const MAX_ATTEMPTS: u32 = 5;
+
+enum Attempt {
+    Complete(u64),
+    Interrupted,
+}
+
+fn validator(headers: &HeaderMap) -> Option<HeaderValue> {
+    headers.get(ETAG).or_else(|| headers.get(LAST_MODIFIED)).cloned()
+}
+
+/// Check if a response has a content coding, the bytes it is decoded to are not the bytes its ranges count.
+fn is_encoded(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_ENCODING)
+        .is_some_and(|value| !value.as_bytes().eq_ignore_ascii_case(b"identity"))
+}
+
+fn restart(file: &File, known_validator: &mut Option<HeaderValue>) -> io::Result<()> {
+    debug!("restarting download from the beginning");
+    *known_validator = None;
+    file.set_len(0)
+}
+
+fn attempt(request: RequestBuilder, file: &mut File, known_validator: &mut Option<HeaderValue>) -> Result<Attempt> {
+    let offset = file.metadata()?.len();
+    // Offsets count the bytes written to the file, so they only match the ranges of unencoded responses.
+    #[cfg(feature = "compress")]
+    let request = request.allow_compression(false);
+
+    let request = if offset > 0 {
+        debug!("resuming download at byte {}", offset);
+        let request = request.range(offset, None);
+        match known_validator {
+            Some(val) => request.header(IF_RANGE, val.clone()),
+            None => request,
+        }
+    } else {
+        request
+    };
+
+    let mut resp = request.send()?;
+
+    let expected = match resp.status() {
+        StatusCode::PARTIAL_CONTENT if is_encoded(resp.headers()) => {
+            return Err(ErrorKind::InvalidResponse(InvalidResponseKind::EncodedRange).into());
+        }
+        StatusCode::PARTIAL_CONTENT => {
+            let range = resp.content_range();
+            let current_validator = validator(resp.headers());
+
+            // Only append if the part starts where the file ends and the resource did not change.
+            let starts_at_offset = range.and_then(|r| r.range()).map(|(first, _)| first) == Some(offset);
+            let unchanged = match known_validator {
+                Some(known) => current_validator.as_ref() == Some(known),
+                None => true,
+            };
+            if !starts_at_offset || !unchanged {
+                restart(file, known_validator)?;
+                return Ok(Attempt::Interrupted);
+            }
+
+            if known_validator.is_none() {
+                *known_validator = current_validator;
+            }
+            range.and_then(|r| r.complete_length())
+        }
+        StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => {
+            let complete_length = resp.content_range().and_then(|r| r.complete_length());
+            if complete_length == Some(offset) {
+                return Ok(Attempt::Complete(offset));
+            }
+            restart(file, known_validator)?;
+            return Ok(Attempt::Interrupted);
+        }
+        status if status.is_success() => {
+            // The server ignored the range or the resource changed, the whole resource is sent again.
+            if offset > 0 {
+                restart(file, known_validator)?;
+            }
+            *known_validator = validator(resp.headers());
+            resp.content_length()
+        }
+        status => return Err(ErrorKind::StatusCode(status).into()),
+    };
+
+    let encoded = is_encoded(resp.headers());
+    if let Err(err) = io::copy(&mut resp, file) {
+        debug!("download interrupted: {}", err);
+        // The decoded bytes of a partial transfer cannot be resumed.
+        if encoded {
+            restart(file, known_validator)?;
+        }
+        return Ok(Attempt::Interrupted);
+    }
+
+    let len = file.metadata()?.len();
+    match expected {
+        Some(expected) if len < expected => {
+            debug!("download interrupted after {} of {} bytes", len, expected);
+            Ok(Attempt::Interrupted)
+        }
+        _ => Ok(Attempt::Complete(len)),
+    }
+}
+
+pub(crate) fn download_resumable<F>(path: &Path, make_request: F) -> Result<u64>
+where
+    F: Fn() -> RequestBuilder,
+{
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut known_validator = None;
+    let mut attempts = 0;
+
+    loop {
+        match attempt(make_request(), &mut file, &mut known_validator) {
+            Ok(Attempt::Complete(len)) => return Ok(len),
+            Ok(Attempt::Interrupted) if attempts + 1 < MAX_ATTEMPTS => (),
+            Ok(Attempt::Interrupted) => {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "download interrupted too many times").into())
+            }
+            Err(err) if attempts + 1 < MAX_ATTEMPTS && is_transient(&err) => {
+                debug!("download attempt failed: {}", err);
+            }
+            Err(err) => return Err(err),
+        }
+
+        let delay = retry_delay(attempts);
+        debug!("resuming download in {:?}", delay);
+        thread::sleep(delay);
+        attempts += 1;
+    }
+}
+
+/// Check if an error interrupted a transfer in a way a new attempt can recover from.
+///
+/// Refused connections and failed name resolutions are not retried, they are unlikely to succeed right away.
+fn is_transient(err: &Error) -> bool {
+    match err.kind() {
+        ErrorKind::Io(io_err) => {
+            is_closed_connection(err) || matches!(io_err.kind(), io::ErrorKind::TimedOut | io::ErrorKind::Interrupted)
+        }
+        _ => false,
+    }
+}
+
+/// Download the given range of bytes into the same range of the file, returns false if the server ignored it.
+fn download_part(path: &Path, first: u64, last: u64, request: RequestBuilder) -> Result<bool> {
+    #[cfg(feature = "compress")]
+    let request = request.allow_compression(false);
+    let resp = request.range(first, Some(last)).send()?;
+    if resp.status() == StatusCode::PARTIAL_CONTENT && is_encoded(resp.headers()) {
+        return Err(ErrorKind::InvalidResponse(InvalidResponseKind::EncodedRange).into());
+    }
+    if resp.status() != StatusCode::PARTIAL_CONTENT {
+        return if resp.is_success() {
+            Ok(false)
+        } else {
+            Err(ErrorKind::StatusCode(resp.status()).into())
+        };
+    }
+    if resp.content_range().and_then(|r| r.range()) != Some((first, last)) {
+        debug!("server sent another range than bytes {}-{}", first, last);
+        return Ok(false);
+    }
+
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    file.seek(SeekFrom::Start(first))?;
+    let expected = last - first + 1;
+    let written = io::copy(&mut resp.take(expected), &mut file)?;
+    if written < expected {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "download of a part interrupted").into());
+    }
+    Ok(true)
+}
+
+fn download_sequential<F>(path: &Path, make_request: &F) -> Result<u64>
+where
+    F: Fn(Method) -> RequestBuilder,
+{
+    File::create(path)?;
+    download_resumable(path, || make_request(Method::GET))
+}
+
+pub(crate) fn download_parallel<F>(path: &Path, parts: usize, make_request: F) -> Result<u64>
+where
+    F: Fn(Method) -> RequestBuilder + Send + Sync + 'static,
+{
+    // The length of the resource is the length of its unencoded representation.
+    let head = make_request(Method::HEAD);
+    #[cfg(feature = "compress")]
+    let head = head.allow_compression(false);
+    let head = head.send()?;
+    let len = match head.content_length() {
+        Some(len)
+            if parts > 1
+                && len > 0
+                && head.is_success()
+                && head.accept_ranges() == AcceptRanges::Bytes
+                && !is_encoded(head.headers()) =>
+        {
+            len
+        }
+        _ => {
+            debug!("byte ranges are not supported, downloading sequentially");
+            return download_sequential(path, &make_request);
+        }
+    };
+    // Parts are only appended if the resource did not change since it was measured.
+    let known_validator = validator(head.headers());
+
+    File::create(path)?.set_len(len)?;
+
+    let parts = (parts as u64).min(len);
+    let part_len = len.div_ceil(parts);
+    // Rounding the length of the parts up can leave fewer parts than asked for, e.g. 10 bytes in 6 parts of 2 bytes.
+    let parts = len.div_ceil(part_len);
+    let make_request = Arc::new(make_request);
+    let threads: Vec<_> = (0..parts)
+        .map(|i| {
+            let first = i * part_len;
+            let last = ((i + 1) * part_len).min(len) - 1;
+            let path = path.to_path_buf();
+            let make_request = make_request.clone();
+            let known_validator = known_validator.clone();
+            thread::spawn(move || {
+                let request = make_request(Method::GET);
+                let request = match known_validator {
+                    Some(val) => request.header(IF_RANGE, val),
+                    None => request,
+                };
+                download_part(&path, first, last, request)
+            })
+        })
+        .collect();
+
+    let mut result = Ok(true);
+    for thread in threads {
+        let part = thread.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+        if let Ok(true) = result {
+            result = part;
+        }
+    }
+
+    match result? {
+        true => Ok(len),
+        false => {
+            debug!("the server ignored a range, downloading sequentially");
+            download_sequential(path, &*make_request)
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::fmt;
+use std::net::IpAddr;
+
+/// Parameters of a `Forwarded` header, as defined by RFC 7239.
+///
+/// They describe a hop of a request through proxies, see
+/// [`RequestBuilder::forwarded`](crate::RequestBuilder::forwarded). Values which are not tokens, such as IPv6
+/// addresses, are quoted when the header is formatted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForwardedParams {
+    by: Option<String>,
+    for_node: Option<String>,
+    host: Option<String>,
+    proto: Option<String>,
+}
+
+fn node(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{}]", ip),
+    }
+}
+
+fn is_tchar(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)
+}
+
+impl ForwardedParams {
+    /// Create empty parameters.
+    pub fn new() -> ForwardedParams {
+        ForwardedParams::default()
+    }
+
+    /// Set the `by` parameter to the address of the interface on which the proxy received the request.
+    pub fn by_ip(self, ip: IpAddr) -> ForwardedParams {
+        self.by_node(node(ip))
+    }
+
+    /// Set the `by` parameter to a node identifier, such as `unknown`, an obfuscated identifier like `_proxy1` or
+    /// an address with a port like `[2001:db8::1]:8080`.
+    pub fn by_node(mut self, node: impl Into<String>) -> ForwardedParams {
+        self.by = Some(node.into());
+        self
+    }
+
+    /// Set the `for` parameter to the address of the client which made the request.
+    pub fn for_ip(self, ip: IpAddr) -> ForwardedParams {
+        self.for_node(node(ip))
+    }
+
+    /// Set the `for` parameter to a node identifier, see `by_node`.
+    pub fn for_node(mut self, node: impl Into<String>) -> ForwardedParams {
+        self.for_node = Some(node.into());
+        self
+    }
+
+    /// Set the `host` parameter to the `Host` header of the request received by the proxy.
+    pub fn host(mut self, host: impl Into<String>) -> ForwardedParams {
+        self.host = Some(host.into());
+        self
+    }
+
+    /// Set the `proto` parameter to the protocol used to make the request received by the proxy, e.g. `https`.
+    pub fn proto(mut self, proto: impl Into<String>) -> ForwardedParams {
+        self.proto = Some(proto.into());
+        self
+    }
+}
+
+impl fmt::Display for ForwardedParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params = [
+            ("by", &self.by),
+            ("for", &self.for_node),
+            ("host", &self.host),
+            ("proto", &self.proto),
+        ];
+        let mut first = true;
+        for (name, value) in params.iter() {
+            let value = match value {
+                Some(value) => value,
+                None => continue,
+            };
+            if !first {
+                f.write_str(";")?;
+            }
+            first = false;
+
+            write!(f, "{}=", name)?;
+            if !value.is_empty() && value.chars().all(is_tchar) {
+                f.write_str(value)?;
+            } else {
+                f.write_str("\"")?;
+                for c in value.chars() {
+                    if c == '"' || c == '\\' {
+                        f.write_str("\\")?;
+                    }
+                    write!(f, "{}", c)?;
+                }
+                f.write_str("\"")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_forwarded_params_format() {
+    let params = ForwardedParams::new()
+        .for_ip("192.0.2.60".parse().unwrap())
+        .by_ip("2001:db8:cafe::17".parse().unwrap())
+        .host("example.com")
+        .proto("https");
+    assert_eq!(
+        params.to_string(),
+        "by=\"[2001:db8:cafe::17]\";for=192.0.2.60;host=example.com;proto=https"
+    );
+
+    let params = ForwardedParams::new().for_node("[2001:db8::1]:4711").by_node("_hidden");
+    assert_eq!(params.to_string(), "by=_hidden;for=\"[2001:db8::1]:4711\"");
+    assert_eq!(
+        ForwardedParams::new().host("a \"b\"").to_string(),
+        "host=\"a \\\"b\\\"\""
+    );
+    assert_eq!(ForwardedParams::new().to_string(), "");
+}
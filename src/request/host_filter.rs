@@ -0,0 +1,176 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use url::Host;
+use wildmatch::WildMatch;
+
+use crate::{ErrorKind, Result};
+
+/// Restricts the hosts and addresses requests are allowed to connect to.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HostFilter {
+    block_private: bool,
+    allowed: Vec<String>,
+    blocked: Vec<String>,
+}
+
+impl HostFilter {
+    pub fn block_private_addresses(&mut self) {
+        self.block_private = true;
+    }
+
+    pub fn allow_hosts(&mut self, patterns: &[&str]) {
+        self.allowed
+            .extend(patterns.iter().map(|pattern| pattern.to_ascii_lowercase()));
+    }
+
+    pub fn block_hosts(&mut self, patterns: &[&str]) {
+        self.blocked
+            .extend(patterns.iter().map(|pattern| pattern.to_ascii_lowercase()));
+    }
+
+    /// Check the host of a URL before it is resolved, hosts which are IP addresses are fully checked.
+    pub fn check_host(&self, host: &Host<&str>) -> Result {
+        let name = match host {
+            Host::Domain(domain) => domain.to_ascii_lowercase(),
+            Host::Ipv4(ip) => ip.to_string(),
+            Host::Ipv6(ip) => ip.to_string(),
+        };
+        if !self.allowed.is_empty() && !matches_any(&self.allowed, &name) {
+            return Err(ErrorKind::HostBlocked(name).into());
+        }
+        if matches_any(&self.blocked, &name) {
+            return Err(ErrorKind::HostBlocked(name).into());
+        }
+        match *host {
+            Host::Domain(_) => Ok(()),
+            Host::Ipv4(ip) => self.check_addr(IpAddr::V4(ip)),
+            Host::Ipv6(ip) => self.check_addr(IpAddr::V6(ip)),
+        }
+    }
+
+    /// Remove the blocked addresses a host resolved to, failing if none remains.
+    ///
+    /// Checking the resolved addresses rather than the name prevents a name from pointing to an internal address.
+    pub fn check_addrs(&self, mut addrs: Vec<SocketAddr>) -> Result<Vec<SocketAddr>> {
+        let first = match addrs.first() {
+            Some(addr) => addr.ip(),
+            None => return Ok(addrs),
+        };
+        addrs.retain(|addr| self.check_addr(addr.ip()).is_ok());
+        if addrs.is_empty() {
+            return Err(ErrorKind::HostBlocked(first.to_string()).into());
+        }
+        Ok(addrs)
+    }
+
+    fn check_addr(&self, ip: IpAddr) -> Result {
+        let name = ip.to_string();
+        if (self.block_private && is_private(ip)) || matches_any(&self.blocked, &name) {
+            debug!("address {} is blocked", ip);
+            return Err(ErrorKind::HostBlocked(name).into());
+        }
+        Ok(())
+    }
+}
+
+fn matches_any(patterns: &[String], name: &str) -> bool {
+    patterns.iter().any(|pattern| WildMatch::new(pattern).is_match(name))
+}
+
+/// Check if an address is loopback, private, link-local, unique-local or unspecified.
+fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_v4(ip),
+        IpAddr::V6(ip) => is_private_v6(ip),
+    }
+}
+
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, _, _] = ip.octets();
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        // Shared address space of carrier-grade NATs, 100.64.0.0/10.
+        || a == 100 && (b & 0xc0) == 64
+}
+
+fn is_private_v6(ip: Ipv6Addr) -> bool {
+    if let Some(ipv4) = ip.to_ipv4_mapped() {
+        return is_private_v4(ipv4);
+    }
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // Unique local addresses, fc00::/7.
+        || (first & 0xfe00) == 0xfc00
+        // Link-local addresses, fe80::/10.
+        || (first & 0xffc0) == 0xfe80
+}
+
+#[cfg(test)]
+fn addrs(ips: &[&str]) -> Vec<SocketAddr> {
+    ips.iter().map(|ip| SocketAddr::new(ip.parse().unwrap(), 80)).collect()
+}
+
+#[test]
+fn test_is_private() {
+    for ip in &[
+        "127.0.0.1",
+        "10.1.2.3",
+        "172.16.0.1",
+        "192.168.1.1",
+        "169.254.169.254",
+        "0.0.0.0",
+        "100.64.0.1",
+        "::1",
+        "::",
+        "fd00::1",
+        "fe80::1",
+        "::ffff:127.0.0.1",
+    ] {
+        assert!(is_private(ip.parse().unwrap()), "{}", ip);
+    }
+    for ip in &[
+        "8.8.8.8",
+        "172.32.0.1",
+        "100.128.0.1",
+        "2001:4860:4860::8888",
+        "::ffff:8.8.8.8",
+    ] {
+        assert!(!is_private(ip.parse().unwrap()), "{}", ip);
+    }
+}
+
+#[test]
+fn test_check_addrs_keeps_public_addresses() {
+    let mut filter = HostFilter::default();
+    filter.block_private_addresses();
+    assert_eq!(
+        filter.check_addrs(addrs(&["127.0.0.1", "93.184.216.34"])).unwrap(),
+        addrs(&["93.184.216.34"])
+    );
+    assert!(matches!(
+        filter.check_addrs(addrs(&["169.254.169.254"])).unwrap_err().kind(),
+        ErrorKind::HostBlocked(addr) if addr == "169.254.169.254"
+    ));
+}
+
+#[test]
+fn test_check_host_patterns() {
+    let mut filter = HostFilter::default();
+    filter.allow_hosts(&["*.example.com", "example.com"]);
+    filter.block_hosts(&["internal.example.com"]);
+    assert!(filter.check_host(&Host::Domain("api.example.com")).is_ok());
+    assert!(filter.check_host(&Host::Domain("Example.com")).is_ok());
+    assert!(filter.check_host(&Host::Domain("internal.example.com")).is_err());
+    assert!(filter.check_host(&Host::Domain("example.org")).is_err());
+
+    let filter = HostFilter::default();
+    assert!(filter.check_host(&Host::Ipv4("127.0.0.1".parse().unwrap())).is_ok());
+    assert_eq!(
+        filter.check_addrs(addrs(&["127.0.0.1"])).unwrap(),
+        addrs(&["127.0.0.1"])
+    );
+}
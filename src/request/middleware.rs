@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use crate::error::Result;
+use crate::parsing::Response;
+use crate::request::body::Boxed;
+use crate::request::PreparedRequest;
+
+/// A layer wrapping the sending of the requests of a [`Session`](crate::Session).
+///
+/// Middlewares are added with [`Session::with_middleware`](crate::Session::with_middleware). Each one receives the
+/// request and `next`, which runs the rest of the chain and then sends the request. A middleware can change the
+/// request before calling `next`, inspect or retry the result, or return a response without calling `next` at
+/// all, for instance one obtained with [`PreparedRequest::send_on`].
+///
+/// Redirections are followed within `next`, the middlewares see the original request and the final response.
+pub trait Middleware: Send + Sync {
+    /// Handle a request, calling `next` to send it.
+    fn handle<'a>(
+        &self,
+        req: PreparedRequest<Boxed<'a>>,
+        next: &dyn Fn(PreparedRequest<Boxed<'a>>) -> Result<Response>,
+    ) -> Result<Response>;
+}
+
+/// Run `req` through `middlewares` and send it.
+pub(crate) fn run<'a>(middlewares: &[Arc<dyn Middleware>], mut req: PreparedRequest<Boxed<'a>>) -> Result<Response> {
+    match middlewares.split_first() {
+        Some((first, rest)) => first.handle(req, &|req| run(rest, req)),
+        None => req.send(),
+    }
+}
@@ -1,30 +1,44 @@
 use std::convert::{From, TryInto};
-use std::io::{prelude::*, BufWriter};
+use std::io::{self, prelude::*, BufWriter};
+use std::mem;
 use std::str;
+use std::thread;
+use std::time::Duration;
 
 #[cfg(feature = "compress")]
 use http::header::ACCEPT_ENCODING;
 use http::{
-    header::{HeaderValue, IntoHeaderName, HOST},
-    HeaderMap, Method, StatusCode, Version,
+    header::{HeaderValue, IntoHeaderName, AUTHORIZATION, COOKIE, EXPECT, HOST},
+    Extensions, HeaderMap, Method, StatusCode, Version,
 };
 use url::Url;
 
 use crate::error::{Error, ErrorKind, InvalidResponseKind, Result};
-use crate::parsing::{parse_response, Response};
+use crate::parsing::{parse_response, RedirectHop, Response};
+use crate::pool::Pool;
+use crate::proto::{self, Transport};
 use crate::streams::{BaseStream, ConnectInfo};
 
 /// Contains types to describe request bodies
 pub mod body;
 mod builder;
+mod download;
+mod forwarded;
+mod host_filter;
+mod middleware;
 pub mod proxy;
 mod session;
 mod settings;
 
 use body::{Body, BodyKind};
 pub use builder::{RequestBuilder, RequestInspector};
-pub use session::Session;
-pub(crate) use settings::BaseSettings;
+pub use forwarded::ForwardedParams;
+pub(crate) use host_filter::HostFilter;
+pub use middleware::Middleware;
+pub use session::{Pages, Session};
+#[cfg(feature = "socket2")]
+pub(crate) use settings::SocketCallback;
+pub(crate) use settings::{BaseSettings, StatusPredicate};
 
 fn header_insert<H, V>(headers: &mut HeaderMap, header: H, value: V) -> Result
 where
@@ -59,6 +73,17 @@ where
     Ok(())
 }
 
+/// Form of the target written in the request line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestTarget {
+    /// The path and query of the URL, e.g. `GET /path?q=1 HTTP/1.1`.
+    Origin,
+    /// The complete URL, e.g. `GET http://example.com/path?q=1 HTTP/1.1`.
+    Absolute,
+    /// A single asterisk, as used by `OPTIONS * HTTP/1.1`.
+    Asterisk,
+}
+
 /// Represents a request that's ready to be sent. You can inspect this object for information about the request.
 #[derive(Debug)]
 pub struct PreparedRequest<B> {
@@ -66,6 +91,7 @@ pub struct PreparedRequest<B> {
     method: Method,
     body: B,
     pub(crate) base_settings: BaseSettings,
+    extensions: Extensions,
 }
 
 #[cfg(test)]
@@ -79,6 +105,7 @@ impl PreparedRequest<body::Empty> {
             method,
             body: body::Empty,
             base_settings: BaseSettings::default(),
+            extensions: Extensions::new(),
         }
     }
 }
@@ -91,37 +118,26 @@ impl<B> PreparedRequest<B> {
 
     #[cfg(feature = "compress")]
     fn set_compression(&mut self) -> Result {
+        // An `Accept-Encoding` header set explicitly by the user is sent as is.
         if self.base_settings.allow_compression {
-            header_insert(&mut self.base_settings.headers, ACCEPT_ENCODING, "gzip, deflate")?;
+            header_insert_if_missing(&mut self.base_settings.headers, ACCEPT_ENCODING, "gzip, deflate")?;
         }
         Ok(())
     }
 
     fn base_redirect_url(&self, location: &str, previous_url: &Url) -> Result<Url> {
-        match Url::parse(location) {
-            Ok(url) => Ok(url),
-            Err(url::ParseError::RelativeUrlWithoutBase) => {
-                let joined_url = previous_url
-                    .join(location)
-                    .map_err(|_| InvalidResponseKind::RedirectionUrl)?;
-
-                Ok(joined_url)
-            }
-            Err(_) => Err(InvalidResponseKind::RedirectionUrl.into()),
+        // Joining handles every form a Location can take: absolute URLs replace the previous URL,
+        // while scheme-relative, absolute paths and relative paths are resolved against it.
+        let url = previous_url
+            .join(location)
+            .map_err(|_| InvalidResponseKind::RedirectionUrl)?;
+        // Locations such as `file:` or `javascript:` URLs must never be followed.
+        let allowed = &self.base_settings.allowed_redirect_schemes;
+        if !allowed.iter().any(|scheme| scheme.eq_ignore_ascii_case(url.scheme())) {
+            debug!("refusing to follow redirection to {}", url);
+            return Err(InvalidResponseKind::RedirectionUrl.into());
         }
-    }
-
-    fn write_headers<W>(&self, writer: &mut W) -> Result
-    where
-        W: Write,
-    {
-        for (key, value) in self.base_settings.headers.iter() {
-            write!(writer, "{}: ", key.as_str())?;
-            writer.write_all(value.as_bytes())?;
-            write!(writer, "\r\n")?;
-        }
-        write!(writer, "\r\n")?;
-        Ok(())
+        Ok(url)
     }
 
     /// Get the URL of this request.
@@ -143,6 +159,24 @@ impl<B> PreparedRequest<B> {
     pub fn headers(&self) -> &HeaderMap {
         &self.base_settings.headers
     }
+
+    /// Get a mutable reference to the headers of this request.
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.base_settings.headers
+    }
+
+    /// Get the extensions of this request, they are handed over to its response.
+    ///
+    /// Extensions cannot be cloned, so they are moved to the first response: sending this request again
+    /// produces a response without them, unless they are set again with `extensions_mut`.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Get a mutable reference to the extensions of this request.
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
 }
 
 impl<B: Body> PreparedRequest<B> {
@@ -153,39 +187,41 @@ impl<B: Body> PreparedRequest<B> {
         let mut writer = BufWriter::new(writer);
         let version = Version::HTTP_11;
 
-        if proxy.is_some() && url.scheme() == "http" {
-            debug!("{} {} {:?}", self.method.as_str(), url, version);
-
-            write!(writer, "{} {} {:?}\r\n", self.method.as_str(), url, version)?;
-        } else if let Some(query) = url.query() {
-            debug!("{} {}?{} {:?}", self.method.as_str(), url.path(), query, version);
-
-            write!(
-                writer,
-                "{} {}?{} {:?}\r\n",
-                self.method.as_str(),
-                url.path(),
-                query,
-                version,
-            )?;
-        } else {
-            debug!("{} {} {:?}", self.method.as_str(), url.path(), version);
+        let target = match self.base_settings.request_target {
+            Some(target) => target,
+            None if proxy.is_some() && url.scheme() == "http" => RequestTarget::Absolute,
+            None => RequestTarget::Origin,
+        };
 
-            write!(writer, "{} {} {:?}\r\n", self.method.as_str(), url.path(), version)?;
-        }
+        let target = match target {
+            RequestTarget::Origin => match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_string(),
+            },
+            RequestTarget::Absolute => url.to_string(),
+            RequestTarget::Asterisk => "*".to_string(),
+        };
 
-        self.write_headers(&mut writer)?;
+        debug!("{} {} {:?}", self.method.as_str(), target, version);
+
+        let headers = self.base_settings.headers.iter();
+        let headers = headers.map(|(key, value)| (key.as_str(), value.as_bytes()));
+        proto::write_head(&mut IoTransport(&mut writer), self.method.as_str(), &target, headers)?;
+        if self.base_settings.flush_headers_early || self.expects_continue()? {
+            // Let the server start working on the request, or refuse it, while the body is produced.
+            writer.flush()?;
+        }
 
         match self.body.kind()? {
             BodyKind::Empty => (),
             BodyKind::KnownLength(len) => {
                 debug!("writing out body of length {}", len);
-                self.body.write(&mut writer)?;
+                self.body.write(&mut writer).map_err(Error::from_io)?;
             }
             BodyKind::Chunked => {
                 debug!("writing out chunked body");
                 let mut writer = body::ChunkedWriter(&mut writer);
-                self.body.write(&mut writer)?;
+                self.body.write(&mut writer).map_err(Error::from_io)?;
                 writer.close()?;
             }
         }
@@ -195,11 +231,183 @@ impl<B: Body> PreparedRequest<B> {
         Ok(())
     }
 
+    /// Check if the request has a body and asks the server to approve it with `Expect: 100-continue`.
+    fn expects_continue(&mut self) -> Result<bool> {
+        let expect = match self.base_settings.headers.get(EXPECT) {
+            Some(expect) => expect.as_bytes().eq_ignore_ascii_case(b"100-continue"),
+            None => false,
+        };
+        Ok(expect && !matches!(self.body.kind()?, BodyKind::Empty))
+    }
+
+    /// Send this request over the given stream and wait for the result.
+    ///
+    /// The stream must already be connected to the server, no connection is opened and proxy
+    /// settings are ignored. Since the stream cannot be reused to reach another URL, redirections
+    /// are not followed. This is mostly useful to test request and response handling without a
+    /// real socket.
+    pub fn send_on<S>(&mut self, stream: S) -> Result<Response>
+    where
+        S: Read + Write + Send + 'static,
+    {
+        let mut stream = BaseStream::custom(stream);
+        self.write_to(&mut stream)?;
+        let resp = parse_response(stream, self)?;
+        self.finish(resp)
+    }
+
+    /// Write this request to `writer` as it would be sent on a connection to its server.
+    ///
+    /// No connection is opened and proxy settings are ignored, the bytes can be sent over any transport and
+    /// the response read back with [`send_on`](Self::send_on) or parsed separately.
+    pub fn write_to<W: Write>(&mut self, writer: W) -> Result {
+        let url = self.url.clone();
+        match &self.base_settings.host_header {
+            Some(host) => {
+                self.base_settings.headers.insert(HOST, host.clone());
+            }
+            None => set_host(&mut self.base_settings.headers, &url)?,
+        }
+        self.write_request(writer, &url, None)
+    }
+
+    /// Write `bytes` verbatim on a new connection to the server of this request and read the response.
+    pub(crate) fn send_raw(&mut self, bytes: &[u8]) -> Result<Response> {
+        let url = self.url.clone();
+        let proxy = self.base_settings.proxy_settings.for_url(&url).cloned();
+        let (mut stream, _) = self.connect(&url, proxy.as_ref(), false)?;
+        // The bytes may leave the connection in any state.
+        stream.disable_reuse();
+        stream.write_all(bytes)?;
+        stream.flush()?;
+        let resp = parse_response(stream, self)?;
+        self.finish(resp)
+    }
+
+    /// Check the status of the final response and hand it the extensions of this request.
+    ///
+    /// A response rejected by the predicate set with `expect_status` is an error.
+    fn finish(&mut self, mut resp: Response) -> Result<Response> {
+        if let Some(expect) = &self.base_settings.expect_status {
+            if !(expect.0)(resp.status()) {
+                return Err(ErrorKind::StatusCode(resp.status()).into());
+            }
+        }
+        resp.set_extensions(mem::take(&mut self.extensions));
+        Ok(resp)
+    }
+
+    /// Open a connection to send this request to `url`, returns it with whether it was an idle pooled connection.
+    fn connect(&self, url: &Url, proxy: Option<&Url>, reuse: bool) -> Result<(BaseStream, bool)> {
+        // The target is checked even through a proxy, its addresses are only known when connecting to it directly.
+        // It is checked before taking an idle connection from the pool, which may have been opened without a filter.
+        self.base_settings
+            .host_filter
+            .check_host(&url.host().ok_or(ErrorKind::InvalidUrlHost)?)?;
+
+        let info = ConnectInfo {
+            url,
+            proxy,
+            base_settings: &self.base_settings,
+        };
+        let mut retries = 0;
+        let (mut stream, reused) = loop {
+            let res = match &self.base_settings.pool {
+                Some(pool) => Pool::connect(pool, &info, reuse),
+                None => BaseStream::connect(&info).map(|stream| (stream, false)),
+            };
+            match res {
+                // Nothing was sent yet, so opening the connection can be retried whatever the request is.
+                Err(err) if retries < self.base_settings.connect_retries && matches!(err.kind(), ErrorKind::Io(_)) => {
+                    let delay = retry_delay(retries);
+                    debug!("failed to connect: {}, retrying in {:?}", err, delay);
+                    thread::sleep(delay);
+                    retries += 1;
+                }
+                res => break res?,
+            }
+        };
+        stream.set_cancel_token(self.base_settings.cancel_token.clone());
+        Ok((stream, reused))
+    }
+
+    /// Write this request on the stream and read the head of the response.
+    fn exchange(&mut self, mut stream: BaseStream, url: &Url, proxy: Option<&Url>) -> Result<Response> {
+        // A server may answer before reading the whole body, e.g. with `413 Payload Too Large`. The body stops
+        // being written once such a response is noticed, TLS records cannot be told apart from a response so
+        // only plain connections are watched.
+        let watch = url.scheme() == "http" && proxy.map_or(true, |proxy| proxy.scheme() == "http");
+        // The body of a request expecting `100 Continue` is held back until the server answers or the wait
+        // times out, since many servers never send the interim response.
+        let expect_continue = match watch && self.expects_continue()? {
+            true => Some(self.base_settings.expect_continue_timeout),
+            false => None,
+        };
+        let mut writer = EarlyResponseWriter {
+            stream: &mut stream,
+            watch,
+            expect_continue,
+            until_check: 0,
+            stopped: false,
+        };
+        let res = self.write_request(&mut writer, url, proxy);
+        let stopped = writer.stopped;
+        match res {
+            Ok(()) => parse_response(stream, self),
+            // The server may also have closed its side after responding.
+            Err(err) if stopped || is_closed_connection(&err) => {
+                debug!("server responded before the request was written");
+                stream.disable_reuse();
+                parse_response(stream, self).map_err(|_| err)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
     /// Send this request and wait for the result.
+    ///
+    /// The middlewares registered on the session are called first, in the order they were added. The
+    /// extensions of this request are moved to the response, see [`extensions`](Self::extensions).
     pub fn send(&mut self) -> Result<Response> {
+        if self.base_settings.middlewares.0.is_empty() {
+            return self.send_direct();
+        }
+
+        let middlewares = mem::take(&mut self.base_settings.middlewares.0);
+        let mut base_settings = self.base_settings.clone();
+        self.base_settings.middlewares.0 = middlewares.clone();
+        // The middlewares get a request without them, so sending it with `send` skips the rest of the chain.
+        base_settings.middlewares.0.clear();
+        let req = PreparedRequest {
+            url: self.url.clone(),
+            method: self.method.clone(),
+            body: body::Boxed::borrowed(&mut self.body),
+            base_settings,
+            extensions: mem::take(&mut self.extensions),
+        };
+        middleware::run(&middlewares, req)
+    }
+
+    fn send_direct(&mut self) -> Result<Response> {
+        // The headers are changed for each hop, they are put back for the next time this request is sent.
+        let headers = self.base_settings.headers.clone();
+        let res = self.send_redirected();
+        self.base_settings.headers = headers;
+        res
+    }
+
+    fn send_redirected(&mut self) -> Result<Response> {
         let mut url = self.url.clone();
 
         let mut redirections = 0;
+        let mut history = Vec::new();
+
+        // Cookies set explicitly by the user take precedence over the cookie jar.
+        let cookie_jar = if self.base_settings.headers.contains_key(COOKIE) {
+            None
+        } else {
+            self.base_settings.cookie_jar.clone()
+        };
 
         loop {
             // If a proxy is set and the url is using http, we must connect to the proxy and send
@@ -212,20 +420,61 @@ impl<B: Body> PreparedRequest<B> {
             let proxy = self.base_settings.proxy_settings.for_url(&url).cloned();
 
             // If there is a proxy and the protocol is HTTP, the Host header will be the proxy's host name.
-            match (url.scheme(), &proxy) {
-                ("http", Some(proxy)) => set_host(&mut self.base_settings.headers, proxy)?,
-                _ => set_host(&mut self.base_settings.headers, &url)?,
+            // An explicit Host header set by the user takes precedence as long as the request stays on its host.
+            let host_header = self
+                .base_settings
+                .host_header
+                .as_ref()
+                .filter(|_| same_host(&url, &self.url));
+            match (host_header, url.scheme(), &proxy) {
+                (Some(host), _, _) => {
+                    self.base_settings.headers.insert(HOST, host.clone());
+                }
+                (None, "http", Some(proxy)) => set_host(&mut self.base_settings.headers, proxy)?,
+                (None, _, _) => set_host(&mut self.base_settings.headers, &url)?,
             };
 
-            let info = ConnectInfo {
-                url: &url,
-                proxy: proxy.as_ref(),
-                base_settings: &self.base_settings,
+            if let Some(jar) = &cookie_jar {
+                match jar.header_value(&url) {
+                    Some(cookies) => header_insert(&mut self.base_settings.headers, COOKIE, cookies)?,
+                    None => {
+                        self.base_settings.headers.remove(COOKIE);
+                    }
+                }
+            }
+
+            if let Some(limiter) = &self.base_settings.rate_limiter {
+                limiter.wait(&url);
+            }
+
+            if let Some(token) = &self.base_settings.cancel_token {
+                if token.is_cancelled() {
+                    return Err(ErrorKind::Cancelled.into());
+                }
+            }
+
+            let (stream, reused) = self.connect(&url, proxy.as_ref(), true)?;
+            let recorder = stream.recorder();
+            let mut resp = match self.exchange(stream, &url, proxy.as_ref()) {
+                // The server may have closed an idle connection just before it was reused, in which case the
+                // request is sent again once on a new connection.
+                Err(err)
+                    if reused
+                        && is_closed_connection(&err)
+                        && recorder.metrics().bytes_received() == 0
+                        && self.body.is_replayable() =>
+                {
+                    debug!("idle connection was closed by the server, retrying on a new connection");
+                    let (stream, _) = self.connect(&url, proxy.as_ref(), false)?;
+                    self.exchange(stream, &url, proxy.as_ref())?
+                }
+                res => res?,
             };
-            let mut stream = BaseStream::connect(&info)?;
+            resp.set_url(url.clone());
 
-            self.write_request(&mut stream, &url, proxy.as_ref())?;
-            let resp = parse_response(stream, self)?;
+            if let Some(jar) = &cookie_jar {
+                jar.store(&url, resp.headers());
+            }
 
             debug!("status code {}", resp.status().as_u16());
 
@@ -237,8 +486,20 @@ impl<B: Body> PreparedRequest<B> {
                     | StatusCode::TEMPORARY_REDIRECT
                     | StatusCode::PERMANENT_REDIRECT
             );
-            if !self.base_settings.follow_redirects || !is_redirect {
-                return Ok(resp);
+            // A body streamed from a reader or a channel was consumed by this request and cannot be sent again,
+            // the redirection is returned as is.
+            let replayable = self.body.is_replayable();
+            if is_redirect && !replayable {
+                debug!("not following redirection, the body cannot be sent again");
+            }
+            let meta_refresh = if replayable && self.base_settings.follow_meta_refresh && self.method == Method::GET {
+                resp.meta_refresh()?
+            } else {
+                None
+            };
+            if (!self.base_settings.follow_redirects || !is_redirect || !replayable) && meta_refresh.is_none() {
+                resp.set_redirect_history(history);
+                return self.finish(resp);
             }
 
             redirections += 1;
@@ -247,19 +508,117 @@ impl<B: Body> PreparedRequest<B> {
             }
 
             // Handle redirect
-            let location = resp
-                .headers()
-                .get(http::header::LOCATION)
-                .ok_or(InvalidResponseKind::LocationHeader)?;
-            let location = location.to_str().map_err(|_| InvalidResponseKind::LocationHeader)?;
+            let location = match &meta_refresh {
+                Some(target) => target.as_str(),
+                None => {
+                    let location = resp
+                        .headers()
+                        .get(http::header::LOCATION)
+                        .ok_or(InvalidResponseKind::LocationHeader)?;
+                    // Some servers send raw UTF-8 in the Location header, which `HeaderValue::to_str` rejects.
+                    // The URL parser percent-encodes those bytes while keeping existing escapes untouched.
+                    str::from_utf8(location.as_bytes()).map_err(|_| InvalidResponseKind::LocationHeader)?
+                }
+            };
 
-            url = self.base_redirect_url(location, &url)?;
+            let next_url = self.base_redirect_url(location, &url)?;
+            if !same_host(&next_url, &url) {
+                debug!("redirected to another host, removing credentials");
+                remove_credentials(&mut self.base_settings.headers);
+            }
+            history.push(RedirectHop::new(url, resp.status()));
+            url = next_url;
 
             debug!("redirected to {} giving url {}", location, url);
         }
     }
 }
 
+/// Adapter writing the output of the `proto` layer to a `Write`.
+pub(crate) struct IoTransport<W>(pub W);
+
+impl<W: Write> Transport for IoTransport<W> {
+    type Error = io::Error;
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.0.write_all(buf)
+    }
+}
+
+/// Number of bytes written between two checks for an early response.
+const EARLY_RESPONSE_CHECK_INTERVAL: usize = 64 * 1024;
+
+/// Writer which fails once the server has started sending a response, when `watch` is set.
+struct EarlyResponseWriter<'a> {
+    stream: &'a mut BaseStream,
+    watch: bool,
+    // Time to wait for the server once the head of the request is flushed.
+    expect_continue: Option<Duration>,
+    // Bytes left to write before the next check, the first write and the first write after a flush are checked.
+    until_check: usize,
+    stopped: bool,
+}
+
+impl Write for EarlyResponseWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.watch && !self.stopped && self.until_check == 0 {
+            self.until_check = EARLY_RESPONSE_CHECK_INTERVAL;
+            self.stopped = self.stream.has_early_response()?;
+        }
+        if self.stopped {
+            return Err(io::Error::other("server responded before the request was written"));
+        }
+        let n = self.stream.write(buf)?;
+        self.until_check = self.until_check.saturating_sub(n);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()?;
+        self.until_check = 0;
+        // The first flush sends the head of the request.
+        if let Some(timeout) = self.expect_continue.take() {
+            if self.stream.wait_for_response(timeout) {
+                self.stopped = true;
+                return Err(io::Error::other("server responded before the request was written"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Delay before the retry following `retries` earlier ones, it starts at 100 milliseconds and doubles each time.
+pub(crate) fn retry_delay(retries: u32) -> Duration {
+    Duration::from_millis(100 << retries.min(6))
+}
+
+/// Check if an error is the one a connection closed by the server produces before a response is received.
+pub(crate) fn is_closed_connection(err: &Error) -> bool {
+    match err.kind() {
+        ErrorKind::Io(err) => matches!(
+            err.kind(),
+            io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::UnexpectedEof
+        ),
+        _ => false,
+    }
+}
+
+/// Check if two URLs point to the same host and port.
+fn same_host(a: &Url, b: &Url) -> bool {
+    a.host() == b.host() && a.port_or_known_default() == b.port_or_known_default()
+}
+
+/// Remove the headers carrying credentials, which are not sent to another host than the one they were set for.
+///
+/// Cookies of the session's cookie jar are still sent, according to their own scope.
+fn remove_credentials(headers: &mut HeaderMap) {
+    headers.remove(AUTHORIZATION);
+    headers.remove(COOKIE);
+}
+
 fn set_host(headers: &mut HeaderMap, url: &Url) -> Result {
     let host = url.host_str().ok_or(ErrorKind::InvalidUrlHost)?;
     if let Some(port) = url.port() {
@@ -272,13 +631,17 @@ fn set_host(headers: &mut HeaderMap, url: &Url) -> Result {
 
 #[cfg(test)]
 mod test {
+    use std::io::{self, Cursor, Read, Write};
+    use std::sync::{Arc, Mutex};
+
     use http::header::{HeaderMap, HeaderValue, USER_AGENT};
     use http::Method;
     use url::Url;
 
     use super::BaseSettings;
-    use super::{header_append, header_insert, header_insert_if_missing, PreparedRequest};
-    use crate::body::Empty;
+    use super::{header_append, header_insert, header_insert_if_missing, set_host, PreparedRequest, RequestTarget};
+    use crate::body::{Body, BodyKind, Empty};
+    use crate::error::{ErrorKind, InvalidResponseKind};
 
     #[test]
     fn test_header_insert_exists() {
@@ -330,6 +693,7 @@ mod test {
             url: Url::parse("http://reddit.com/r/rust").unwrap(),
             body: Empty,
             base_settings: BaseSettings::default(),
+            extensions: http::Extensions::new(),
         };
 
         let proxy = Url::parse("http://proxy:3128").unwrap();
@@ -342,6 +706,51 @@ mod test {
         assert_eq!(lines[0], "GET http://reddit.com/r/rust HTTP/1.1");
     }
 
+    fn request_line(req: &mut PreparedRequest<Empty>, proxy: Option<&str>) -> String {
+        let proxy = proxy.map(|proxy| Url::parse(proxy).unwrap());
+        let mut buf: Vec<u8> = vec![];
+        req.write_request(&mut buf, &req.url.clone(), proxy.as_ref()).unwrap();
+
+        let text = String::from_utf8(buf).unwrap();
+        text.split("\r\n").next().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_request_target_default() {
+        let mut req = PreparedRequest::new(Method::GET, "http://example.com/a/b?q=1");
+        assert_eq!(request_line(&mut req, None), "GET /a/b?q=1 HTTP/1.1");
+        assert_eq!(
+            request_line(&mut req, Some("http://proxy:3128")),
+            "GET http://example.com/a/b?q=1 HTTP/1.1"
+        );
+    }
+
+    #[test]
+    fn test_request_target_origin() {
+        let mut req = PreparedRequest::new(Method::GET, "http://example.com/a/b?q=1");
+        req.base_settings.request_target = Some(RequestTarget::Origin);
+        assert_eq!(request_line(&mut req, None), "GET /a/b?q=1 HTTP/1.1");
+        assert_eq!(
+            request_line(&mut req, Some("http://proxy:3128")),
+            "GET /a/b?q=1 HTTP/1.1"
+        );
+    }
+
+    #[test]
+    fn test_request_target_absolute() {
+        let mut req = PreparedRequest::new(Method::GET, "http://example.com/a/b");
+        req.base_settings.request_target = Some(RequestTarget::Absolute);
+        assert_eq!(request_line(&mut req, None), "GET http://example.com/a/b HTTP/1.1");
+    }
+
+    #[test]
+    fn test_request_target_asterisk() {
+        let mut req = crate::options("http://example.com/")
+            .request_target(RequestTarget::Asterisk)
+            .prepare();
+        assert_eq!(request_line(&mut req, None), "OPTIONS * HTTP/1.1");
+    }
+
     #[test]
     fn test_http_url_with_https_proxy() {
         let mut req = PreparedRequest {
@@ -349,6 +758,7 @@ mod test {
             url: Url::parse("http://reddit.com/r/rust").unwrap(),
             body: Empty,
             base_settings: BaseSettings::default(),
+            extensions: http::Extensions::new(),
         };
 
         let proxy = Url::parse("http://proxy:3128").unwrap();
@@ -360,4 +770,591 @@ mod test {
 
         assert_eq!(lines[0], "GET http://reddit.com/r/rust HTTP/1.1");
     }
+
+    #[cfg(feature = "compress")]
+    fn split_request(buf: &[u8]) -> (String, &[u8]) {
+        let pos = buf.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        (String::from_utf8(buf[..pos].to_vec()).unwrap(), &buf[pos + 4..])
+    }
+
+    #[cfg(feature = "compress")]
+    fn gunzip(data: &[u8]) -> Vec<u8> {
+        use std::io::Read;
+
+        let mut out = Vec::new();
+        flate2::read::GzDecoder::new(data).read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_gzip_body_known_length() {
+        let payload = "hello world ".repeat(100);
+        let mut req = crate::post("http://reddit.com/r/rust")
+            .text(&payload)
+            .gzip_body()
+            .prepare();
+
+        let mut buf: Vec<u8> = vec![];
+        req.write_request(&mut buf, &req.url.clone(), None).unwrap();
+
+        let (head, body) = split_request(&buf);
+        assert!(head.contains("\r\ncontent-encoding: gzip"));
+        assert!(head.contains(&format!("\r\ncontent-length: {}", body.len())));
+        assert!(body.len() < payload.len());
+        assert_eq!(gunzip(body), payload.as_bytes());
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_gzip_body_chunked() {
+        use std::io::{BufReader, Read, Result as IoResult, Write};
+
+        use crate::body::{Body, BodyKind};
+        use crate::parsing::chunked_reader::ChunkedReader;
+
+        struct Streamed;
+
+        impl Body for Streamed {
+            fn kind(&mut self) -> IoResult<BodyKind> {
+                Ok(BodyKind::Chunked)
+            }
+
+            fn write<W: Write>(&mut self, mut writer: W) -> IoResult<()> {
+                for _ in 0..100 {
+                    writer.write_all(b"hello world ")?;
+                }
+                Ok(())
+            }
+        }
+
+        let mut req = crate::post("http://reddit.com/r/rust")
+            .body(Streamed)
+            .gzip_body()
+            .prepare();
+
+        let mut buf: Vec<u8> = vec![];
+        req.write_request(&mut buf, &req.url.clone(), None).unwrap();
+
+        let (head, body) = split_request(&buf);
+        assert!(head.contains("\r\ncontent-encoding: gzip"));
+        assert!(head.contains("\r\ntransfer-encoding: chunked"));
+
+        let mut compressed = Vec::new();
+        ChunkedReader::new(BufReader::new(body))
+            .read_to_end(&mut compressed)
+            .unwrap();
+        assert_eq!(gunzip(&compressed), "hello world ".repeat(100).as_bytes());
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_gzip_body_if_larger() {
+        let payload = "hello world ".repeat(100);
+        let mut req = crate::post("http://reddit.com/r/rust")
+            .text(&payload)
+            .gzip_body_if_larger(1000)
+            .prepare();
+        let mut buf: Vec<u8> = vec![];
+        req.write_request(&mut buf, &req.url.clone(), None).unwrap();
+
+        let (head, body) = split_request(&buf);
+        assert!(head.contains("\r\ncontent-encoding: gzip"));
+        assert!(head.contains(&format!("\r\ncontent-length: {}", body.len())));
+        assert_eq!(gunzip(body), payload.as_bytes());
+
+        let mut req = crate::post("http://reddit.com/r/rust")
+            .text(&payload)
+            .gzip_body_if_larger(payload.len())
+            .prepare();
+        let mut buf: Vec<u8> = vec![];
+        req.write_request(&mut buf, &req.url.clone(), None).unwrap();
+
+        let (head, body) = split_request(&buf);
+        assert!(!head.contains("content-encoding"));
+        assert!(head.contains(&format!("\r\ncontent-length: {}", payload.len())));
+        assert_eq!(body, payload.as_bytes());
+    }
+
+    #[test]
+    #[cfg(feature = "compress")]
+    fn test_gzip_body_replaced_or_empty() {
+        // The body set after `gzip_body` replaces the compressed one, it is sent as it is.
+        let mut req = crate::post("http://reddit.com/r/rust")
+            .text("hello")
+            .gzip_body()
+            .text("plain")
+            .prepare();
+        let mut buf: Vec<u8> = vec![];
+        req.write_request(&mut buf, &req.url.clone(), None).unwrap();
+
+        let (head, body) = split_request(&buf);
+        assert!(!head.contains("content-encoding"));
+        assert_eq!(body, b"plain");
+
+        let mut req = crate::post("http://reddit.com/r/rust").gzip_body().prepare();
+        let mut buf: Vec<u8> = vec![];
+        req.write_request(&mut buf, &req.url.clone(), None).unwrap();
+
+        let (head, body) = split_request(&buf);
+        assert!(!head.contains("content-encoding"));
+        assert!(body.is_empty());
+    }
+
+    fn redirect_url(location: &str) -> String {
+        let req = PreparedRequest::new(Method::GET, "http://example.com/a/b?q=1");
+        req.base_redirect_url(location, &req.url).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_redirect_url_rejects_other_schemes() {
+        let req = PreparedRequest::new(Method::GET, "http://example.com/a/b");
+        for location in &["file:///etc/passwd", "javascript:alert(1)", "ftp://example.com/x"] {
+            let err = req.base_redirect_url(location, &req.url).unwrap_err();
+            assert!(matches!(
+                err.kind(),
+                ErrorKind::InvalidResponse(InvalidResponseKind::RedirectionUrl)
+            ));
+        }
+
+        let mut req = PreparedRequest::new(Method::GET, "https://example.com/");
+        req.base_settings.allowed_redirect_schemes = vec!["https".to_string()];
+        assert!(req.base_redirect_url("http://example.com/", &req.url).is_err());
+        assert!(req.base_redirect_url("/other", &req.url).is_ok());
+    }
+
+    #[test]
+    fn test_redirect_url_absolute() {
+        assert_eq!(redirect_url("https://other.com/x"), "https://other.com/x");
+    }
+
+    #[test]
+    fn test_redirect_url_absolute_path() {
+        assert_eq!(redirect_url("/new/path"), "http://example.com/new/path");
+    }
+
+    #[test]
+    fn test_redirect_url_relative_path() {
+        assert_eq!(redirect_url("c"), "http://example.com/a/c");
+        assert_eq!(redirect_url("../c"), "http://example.com/c");
+    }
+
+    #[test]
+    fn test_redirect_url_scheme_relative() {
+        assert_eq!(redirect_url("//other.com/x"), "http://other.com/x");
+    }
+
+    #[test]
+    fn test_redirect_url_keeps_percent_escapes() {
+        assert_eq!(redirect_url("/with%20space"), "http://example.com/with%20space");
+        assert_eq!(redirect_url("http://other.com/a%2Fb"), "http://other.com/a%2Fb");
+    }
+
+    #[test]
+    fn test_redirect_url_encodes_raw_utf8() {
+        assert_eq!(redirect_url("/café"), "http://example.com/caf%C3%A9");
+    }
+
+    #[test]
+    fn test_redirect_url_query_only() {
+        assert_eq!(redirect_url("?p=2"), "http://example.com/a/b?p=2");
+    }
+
+    struct CannedStream {
+        response: Cursor<&'static [u8]>,
+        written: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Read for CannedStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.response.read(buf)
+        }
+    }
+
+    impl Write for CannedStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn canned_stream(response: &'static [u8]) -> (CannedStream, Arc<Mutex<Vec<u8>>>) {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let stream = CannedStream {
+            response: Cursor::new(response),
+            written: written.clone(),
+        };
+        (stream, written)
+    }
+
+    #[test]
+    fn test_send_on() {
+        let (stream, written) = canned_stream(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nX-Test: yes\r\n\r\nhello");
+        let mut req = PreparedRequest::new(Method::GET, "http://example.com/path?q=1");
+        let resp = req.send_on(stream).unwrap();
+
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(resp.headers()["x-test"], "yes");
+        assert_eq!(resp.text().unwrap(), "hello");
+
+        let written = written.lock().unwrap();
+        let text = std::str::from_utf8(&written).unwrap();
+        assert!(text.starts_with("GET /path?q=1 HTTP/1.1\r\n"));
+        assert!(text.contains("\r\nhost: example.com\r\n"));
+    }
+
+    #[test]
+    fn test_write_to() {
+        let mut req = crate::RequestBuilder::new(Method::POST, "http://example.com:8080/path?q=1")
+            .text("hello")
+            .prepare();
+        let mut buf = Vec::new();
+        req.write_to(&mut buf).unwrap();
+
+        let text = std::str::from_utf8(&buf).unwrap();
+        assert!(text.starts_with("POST /path?q=1 HTTP/1.1\r\n"));
+        assert!(text.contains("\r\nhost: example.com:8080\r\n"));
+        assert!(text.contains("\r\ncontent-length: 5\r\n"));
+        assert!(text.ends_with("\r\n\r\nhello"));
+    }
+
+    #[test]
+    fn test_expect_status() {
+        let send = |response: &'static [u8]| {
+            let (stream, _) = canned_stream(response);
+            crate::RequestBuilder::new(Method::GET, "http://example.com/")
+                .expect_status(|status| status.is_success() || status.is_redirection())
+                .prepare()
+                .send_on(stream)
+        };
+
+        let resp = send(b"HTTP/1.1 302 Found\r\nLocation: /other\r\nContent-Length: 0\r\n\r\n").unwrap();
+        assert_eq!(resp.status(), http::StatusCode::FOUND);
+        assert!(send(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").is_ok());
+
+        let err = send(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::StatusCode(http::StatusCode::NOT_FOUND)));
+    }
+
+    #[test]
+    fn test_send_on_does_not_follow_redirects() {
+        let (stream, _) = canned_stream(b"HTTP/1.1 302 Found\r\nLocation: /other\r\nContent-Length: 0\r\n\r\n");
+        let mut req = PreparedRequest::new(Method::GET, "http://example.com/");
+        let resp = req.send_on(stream).unwrap();
+
+        assert_eq!(resp.status(), http::StatusCode::FOUND);
+        assert_eq!(resp.headers()["location"], "/other");
+        assert!(resp.redirect_history().is_empty());
+    }
+
+    #[test]
+    fn test_send_on_invalid_response() {
+        let (stream, _) = canned_stream(b"not http at all\r\n\r\n");
+        let mut req = PreparedRequest::new(Method::GET, "http://example.com/");
+        assert!(req.send_on(stream).is_err());
+    }
+
+    #[test]
+    fn test_transport_wire_output() {
+        let make_request = || {
+            crate::post("http://example.com/submit?id=3")
+                .header("X-Custom", "value")
+                .text("payload")
+                .prepare()
+        };
+
+        let mut expected: Vec<u8> = vec![];
+        let mut req = make_request();
+        set_host(&mut req.base_settings.headers, &req.url.clone()).unwrap();
+        req.write_request(&mut expected, &req.url.clone(), None).unwrap();
+
+        let (stream, written) = canned_stream(b"HTTP/1.1 204 No Content\r\n\r\n");
+        make_request().send_on(stream).unwrap();
+
+        assert_eq!(*written.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_send_on_metrics() {
+        const RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+
+        let (stream, written) = canned_stream(RESPONSE);
+        let mut req = PreparedRequest::new(Method::GET, "http://example.com/");
+        let resp = req.send_on(stream).unwrap();
+
+        let metrics = resp.metrics();
+        assert_eq!(metrics.bytes_sent(), written.lock().unwrap().len() as u64);
+        assert!(metrics.ttfb().is_some());
+        assert_eq!(metrics.total(), None);
+
+        let (_, _, mut reader) = resp.split();
+        io::copy(&mut reader, &mut io::sink()).unwrap();
+
+        let metrics = reader.metrics();
+        assert_eq!(metrics.bytes_received(), RESPONSE.len() as u64);
+        assert!(metrics.total().is_some());
+        assert_eq!(metrics.dns(), None);
+        assert_eq!(metrics.connect(), None);
+        assert_eq!(metrics.tls(), None);
+    }
+
+    fn send_sized(body: &'static [u8], len: u64) -> (crate::Result<crate::Response>, String) {
+        let (stream, written) = canned_stream(b"HTTP/1.1 204 No Content\r\n\r\n");
+        let res = crate::RequestBuilder::new(Method::POST, "http://example.com/")
+            .body_reader_sized(body, len)
+            .prepare()
+            .send_on(stream);
+        let written = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+        (res, written)
+    }
+
+    #[test]
+    fn test_cow_bodies() {
+        use std::borrow::Cow;
+
+        fn send<B: Body>(builder: crate::RequestBuilder<B>) -> String {
+            let (stream, written) = canned_stream(b"HTTP/1.1 204 No Content\r\n\r\n");
+            builder.prepare().send_on(stream).unwrap();
+            let written = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+            written
+        }
+
+        let data = b"borrowed bytes".to_vec();
+        let written = send(crate::post("http://example.com/").bytes(Cow::Borrowed(&data[..])));
+        assert!(written.contains("\r\ncontent-length: 14\r\n"));
+        assert!(written.ends_with("\r\n\r\nborrowed bytes"));
+
+        let written = send(crate::post("http://example.com/").bytes(Cow::<[u8]>::Owned(b"owned".to_vec())));
+        assert!(written.ends_with("\r\n\r\nowned"));
+
+        let text = String::from("borrowed text");
+        let written = send(crate::post("http://example.com/").text(Cow::Borrowed(text.as_str())));
+        assert!(written.contains("\r\ncontent-type: text/plain; charset=utf-8\r\n"));
+        assert!(written.ends_with("\r\n\r\nborrowed text"));
+
+        let written = send(crate::post("http://example.com/").text(Cow::<str>::Owned("owned text".into())));
+        assert!(written.ends_with("\r\n\r\nowned text"));
+    }
+
+    #[test]
+    fn test_extensions() {
+        #[derive(Debug, PartialEq)]
+        struct RequestId(u64);
+
+        let (stream, written) = canned_stream(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+        let mut builder = crate::get("http://example.com/").extension(RequestId(42));
+        assert_eq!(builder.inspect().extensions().get(), Some(&RequestId(42)));
+        let mut req = builder.prepare();
+        let resp = req.send_on(stream).unwrap();
+
+        assert_eq!(resp.extensions().get(), Some(&RequestId(42)));
+        // They were moved to the response.
+        assert_eq!(req.extensions().get::<RequestId>(), None);
+        // Extensions are never sent.
+        assert!(!String::from_utf8_lossy(&written.lock().unwrap()).contains("42"));
+        let resp = resp.into_http().unwrap();
+        assert_eq!(resp.extensions().get(), Some(&RequestId(42)));
+    }
+
+    #[test]
+    fn test_buffer_body() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        tx.send(b"hello ".to_vec()).unwrap();
+        tx.send(b"world".to_vec()).unwrap();
+        drop(tx);
+
+        let (stream, written) = canned_stream(b"HTTP/1.1 204 No Content\r\n\r\n");
+        crate::RequestBuilder::new(Method::POST, "http://example.com/")
+            .body_channel(rx)
+            .buffer_body()
+            .unwrap()
+            .prepare()
+            .send_on(stream)
+            .unwrap();
+
+        let written = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+        assert!(written.contains("\r\ncontent-length: 11\r\n"));
+        assert!(written.contains("\r\ncontent-type: application/octet-stream\r\n"));
+        assert!(!written.contains("transfer-encoding"));
+        assert!(written.ends_with("\r\n\r\nhello world"));
+    }
+
+    #[test]
+    fn test_body_reader_sized() {
+        let (res, written) = send_sized(b"hello", 5);
+        assert!(res.is_ok());
+        assert!(written.contains("\r\ncontent-length: 5\r\n"));
+        assert!(!written.contains("transfer-encoding"));
+        assert!(written.ends_with("\r\n\r\nhello"));
+    }
+
+    #[test]
+    fn test_body_reader_sized_short() {
+        let (res, _) = send_sized(b"hel", 5);
+        match res.unwrap_err().kind() {
+            crate::ErrorKind::BodyLengthMismatch { expected, actual } => {
+                assert_eq!(*expected, 5);
+                assert_eq!(*actual, 3);
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_body_reader_sized_long() {
+        let (res, _) = send_sized(b"hello world", 5);
+        match res.unwrap_err().kind() {
+            crate::ErrorKind::BodyLengthMismatch { expected, actual } => {
+                assert_eq!(*expected, 5);
+                assert_eq!(*actual, 6);
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_body_channel() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let producer = std::thread::spawn(move || {
+            for chunk in ["hello", "", " ", "world"] {
+                tx.send(chunk.as_bytes().to_vec()).unwrap();
+            }
+        });
+
+        let (stream, written) = canned_stream(b"HTTP/1.1 204 No Content\r\n\r\n");
+        crate::RequestBuilder::new(Method::POST, "http://example.com/")
+            .body_channel(rx)
+            .prepare()
+            .send_on(stream)
+            .unwrap();
+        producer.join().unwrap();
+
+        let written = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+        assert!(written.contains("\r\ntransfer-encoding: chunked\r\n"));
+        assert!(written.ends_with("\r\n\r\n5\r\nhello\r\n1\r\n \r\n5\r\nworld\r\n0\r\n\r\n"));
+    }
+
+    /// A body recording what was written to the transport when it started being written.
+    struct ProbeBody {
+        written: Arc<Mutex<Vec<u8>>>,
+        seen: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Body for ProbeBody {
+        fn kind(&mut self) -> io::Result<BodyKind> {
+            Ok(BodyKind::KnownLength(4))
+        }
+
+        fn write<W: Write>(&mut self, mut writer: W) -> io::Result<()> {
+            *self.seen.lock().unwrap() = self.written.lock().unwrap().clone();
+            writer.write_all(b"body")
+        }
+    }
+
+    #[test]
+    fn test_flush_headers_early() {
+        for &early in &[false, true] {
+            let (stream, written) = canned_stream(b"HTTP/1.1 204 No Content\r\n\r\n");
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            let body = ProbeBody {
+                written: written.clone(),
+                seen: seen.clone(),
+            };
+            let mut builder = crate::RequestBuilder::new(Method::POST, "http://example.com/").body(body);
+            if early {
+                builder = builder.flush_headers_early();
+            }
+            builder.prepare().send_on(stream).unwrap();
+
+            let written = written.lock().unwrap();
+            let seen = seen.lock().unwrap();
+            assert!(written.ends_with(b"\r\n\r\nbody"));
+            if early {
+                // The whole head was on the transport before the body was produced.
+                assert_eq!(&seen[..], &written[..written.len() - 4]);
+            } else {
+                assert!(seen.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_file_path_upload() {
+        let path = std::env::temp_dir().join(format!("attohttpc-upload-{}.txt", std::process::id()));
+        std::fs::write(&path, "hello file").unwrap();
+
+        let (stream, written) = canned_stream(b"HTTP/1.1 204 No Content\r\n\r\n");
+        crate::RequestBuilder::new(Method::PUT, "http://example.com/upload")
+            .file_path(&path)
+            .unwrap()
+            .prepare()
+            .send_on(stream)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let written = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+        assert!(written.contains("\r\ncontent-length: 10\r\n"));
+        if cfg!(feature = "mime") {
+            assert!(written.contains("\r\ncontent-type: text/plain\r\n"));
+        } else {
+            assert!(written.contains("\r\ncontent-type: application/octet-stream\r\n"));
+        }
+        assert!(written.ends_with("\r\n\r\nhello file"));
+    }
+
+    #[test]
+    fn test_file_path_missing() {
+        let res = crate::RequestBuilder::new(Method::PUT, "http://example.com/upload").file_path("/does/not/exist");
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_middleware() {
+        use crate::{body::Boxed, Middleware, Response, Result};
+
+        struct InjectHeader;
+
+        impl Middleware for InjectHeader {
+            fn handle<'a>(
+                &self,
+                mut req: PreparedRequest<Boxed<'a>>,
+                next: &dyn Fn(PreparedRequest<Boxed<'a>>) -> Result<Response>,
+            ) -> Result<Response> {
+                req.headers_mut().insert("x-injected", HeaderValue::from_static("yes"));
+                next(req)
+            }
+        }
+
+        struct Canned(Arc<Mutex<Vec<u8>>>);
+
+        impl Middleware for Canned {
+            fn handle<'a>(
+                &self,
+                mut req: PreparedRequest<Boxed<'a>>,
+                _next: &dyn Fn(PreparedRequest<Boxed<'a>>) -> Result<Response>,
+            ) -> Result<Response> {
+                let (stream, written) = canned_stream(b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\ncached");
+                let resp = req.send_on(stream);
+                *self.0.lock().unwrap() = written.lock().unwrap().clone();
+                resp
+            }
+        }
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let mut sess = crate::Session::new();
+        sess.with_middleware(InjectHeader);
+        sess.with_middleware(Canned(written.clone()));
+
+        // The canned response is returned without connecting to the unresolvable host.
+        let resp = sess.post("http://example.invalid/").text("hello").send().unwrap();
+        assert_eq!(resp.text().unwrap(), "cached");
+
+        let written = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+        assert!(written.starts_with("POST / HTTP/1.1\r\n"));
+        assert!(written.contains("\r\nx-injected: yes\r\n"));
+        assert!(written.ends_with("\r\n\r\nhello"));
+    }
 }
@@ -1,31 +1,109 @@
+use std::collections::HashSet;
 use std::convert::TryInto;
+#[cfg(feature = "socket2")]
+use std::io;
+use std::sync::Arc;
 use std::time::Duration;
 
-use http::header::{HeaderValue, IntoHeaderName};
-use http::Method;
+use http::header::{HeaderValue, IntoHeaderName, HOST};
+use http::{Method, StatusCode};
+#[cfg(feature = "socket2")]
+use socket2::Socket;
+use url::Url;
 
 #[cfg(feature = "charsets")]
 use crate::charsets::Charset;
-use crate::error::{Error, Result};
+use crate::cookies::CookieJar;
+use crate::error::{Error, ErrorKind, InvalidResponseKind, Result};
+use crate::parsing::Response;
+use crate::pool::Pool;
+use crate::rate_limit::RateLimiter;
 use crate::request::proxy::ProxySettings;
-use crate::request::{header_append, header_insert, BaseSettings, RequestBuilder};
-use crate::tls::Certificate;
+#[cfg(feature = "socket2")]
+use crate::request::SocketCallback;
+use crate::request::{
+    body, header_append, header_insert, remove_credentials, same_host, BaseSettings, Middleware, RequestBuilder,
+    StatusPredicate,
+};
+use crate::skip_debug::SkipDebug;
+#[cfg(feature = "tls")]
+use crate::tls::TlsConnector;
+use crate::tls::{Certificate, TlsVersion};
 
 /// `Session` is a type that can carry settings over multiple requests. The settings applied to the
 /// `Session` are applied to every request created from this `Session`.
-#[derive(Debug, Default)]
+///
+/// A `Session` also keeps a pool of connections, which are reused by the requests it creates
+/// instead of opening a new connection each time, and a cookie jar once enabled with
+/// [`cookie_store`](Session::cookie_store), which stores the cookies set by responses and sends them
+/// back with the following requests. Cloning a `Session`
+/// is cheap and the clones share the same pool and cookie jar, so the recommended pattern is to
+/// create a single `Session` when the application starts and to clone it wherever requests are
+/// made, including in other threads.
+///
+/// A connection is returned to the pool once the body of its response has been read entirely, or
+/// discarded with [`Response::drain`](crate::Response::drain). When a response is dropped before its
+/// body is read, a small remainder is read to reuse the connection, otherwise it is closed.
+///
+/// A connection is only reused by requests with the same connection settings, such as TLS options or socket
+/// callbacks, and connections made with a custom TLS connector are never reused. Connections idle for more than
+/// 90 seconds are closed.
+///
+/// Idle connections which the server closed are discarded before being reused. If the server closes a connection
+/// just as a request is sent on it, the request is sent again once on a new connection, unless its body was
+/// streamed from a reader and cannot be replayed.
+///
+/// Settings changed on a clone only apply to that clone.
+#[derive(Clone, Debug)]
 pub struct Session {
     base_settings: BaseSettings,
 }
 
+impl Default for Session {
+    fn default() -> Self {
+        Session::new()
+    }
+}
+
 impl Session {
-    /// Create a new `Session` with default settings.
+    /// Create a new `Session` with default settings and an empty connection pool.
     pub fn new() -> Session {
         Session {
-            base_settings: BaseSettings::default(),
+            base_settings: BaseSettings {
+                pool: Some(Arc::new(Pool::default())),
+                ..BaseSettings::default()
+            },
         }
     }
 
+    /// Set the URL that the URLs of the requests created from this `Session` are relative to.
+    ///
+    /// URLs are resolved like links in a page: a full URL replaces the base URL, an absolute path such as
+    /// `/v1/users` replaces its path, and a relative path such as `users` replaces its last segment, so the
+    /// base URL should end with a `/`. The query of the base URL is only kept for an empty URL.
+    ///
+    /// # Panics
+    /// This method will panic if the URL is invalid.
+    pub fn base_url<U>(&mut self, url: U)
+    where
+        U: AsRef<str>,
+    {
+        self.try_base_url(url).expect("invalid base url");
+    }
+
+    /// Set the URL that the URLs of the requests created from this `Session` are relative to.
+    ///
+    /// See [`base_url`](Self::base_url), an invalid URL returns
+    /// [`ErrorKind::InvalidBaseUrl`](crate::ErrorKind::InvalidBaseUrl).
+    pub fn try_base_url<U>(&mut self, url: U) -> Result<()>
+    where
+        U: AsRef<str>,
+    {
+        let url = Url::parse(url.as_ref()).map_err(|_| ErrorKind::InvalidBaseUrl)?;
+        self.base_settings.base_url = Some(url);
+        Ok(())
+    }
+
     /// Create a new `RequestBuilder` with the GET method and this Session's settings applied on it.
     pub fn get<U>(&self, base_url: U) -> RequestBuilder
     where
@@ -90,6 +168,75 @@ impl Session {
         RequestBuilder::with_settings(Method::TRACE, base_url, self.base_settings.clone())
     }
 
+    /// Iterate over the pages of a collection, starting with a GET request to `base_url`.
+    ///
+    /// Each response is yielded in turn and the next page is requested from the URL of its `Link` header with
+    /// `rel="next"`, until a response has none or links to a page which was already requested. Relative links
+    /// are resolved against the URL of the response, after redirections. Pagination stops after an error, and
+    /// the iterator can be dropped at any point to stop early.
+    ///
+    /// Like when following redirections, the `Authorization` and `Cookie` headers are not sent to pages on
+    /// another host than the one of `base_url`.
+    pub fn paginate<U>(&self, base_url: U) -> Pages<'_>
+    where
+        U: AsRef<str>,
+    {
+        Pages {
+            session: self,
+            next: Some(RequestBuilder::try_with_settings(
+                Method::GET,
+                base_url,
+                self.base_settings.clone(),
+            )),
+            origin: None,
+            visited: HashSet::new(),
+        }
+    }
+
+    /// Send `bytes` verbatim as the whole request to the server of `url` and parse what it answers.
+    ///
+    /// This is an escape hatch to test how servers handle malformed or unusual requests: no header is added
+    /// and the URL is only used to open the connection, through a proxy if one applies. The connection is not
+    /// taken from the pool and the response is parsed as the response to a GET request, without following
+    /// redirections.
+    pub fn send_raw<U>(&self, url: U, bytes: &[u8]) -> Result<Response>
+    where
+        U: AsRef<str>,
+    {
+        RequestBuilder::try_with_settings(Method::GET, url, self.base_settings.clone())?
+            .try_prepare()?
+            .send_raw(bytes)
+    }
+
+    /// Send a request built with the `http` crate, with this Session's settings applied on it.
+    ///
+    /// The method, URI, headers, extensions and body of the request are used as is: its headers replace the headers
+    /// of the same name set on this `Session` and a `Host` header replaces the one derived from the URI.
+    /// The URI must be absolute. The version of the request is ignored, requests are always sent with
+    /// HTTP/1.1.
+    pub fn execute(&self, request: http::Request<Vec<u8>>) -> Result<Response> {
+        let (mut parts, body) = request.into_parts();
+        let mut base_settings = self.base_settings.clone();
+
+        if let Some(host) = parts.headers.remove(HOST) {
+            base_settings.host_header = Some(host);
+        }
+        for name in parts.headers.keys() {
+            base_settings.headers.remove(name);
+        }
+        for (name, value) in &parts.headers {
+            base_settings.headers.append(name, value.clone());
+        }
+
+        let mut builder = RequestBuilder::try_with_settings(parts.method, parts.uri.to_string(), base_settings)?;
+        builder.set_extensions(parts.extensions);
+        if body.is_empty() {
+            builder.send()
+        } else {
+            builder.body(body::Bytes(body)).send()
+        }
+    }
+
     //
     // Settings
     //
@@ -152,6 +299,21 @@ impl Session {
         Ok(())
     }
 
+    /// Override the `Host` header sent with every `Request` of this `Session`.
+    ///
+    /// By default the `Host` header is derived from the URL. The connection is still made to the
+    /// URL's address, which is useful to test virtual host routing. The override only applies while
+    /// a request stays on the host of its URL, redirections to another host get their own `Host`.
+    ///
+    /// The `Host` header cannot be omitted: requests are always sent as HTTP/1.1, which requires it, and
+    /// servers answer a request without it with `400 Bad Request`.
+    ///
+    /// Fails if the value is not a valid header value.
+    pub fn host_header(&mut self, host: &str) -> Result<()> {
+        self.base_settings.host_header = Some(HeaderValue::from_str(host)?);
+        Ok(())
+    }
+
     /// Set the maximum number of redirections this `Request` can perform.
     ///
     /// The default is 5.
@@ -161,11 +323,88 @@ impl Session {
 
     /// Sets if this `Request` should follow redirects, 3xx codes.
     ///
-    /// This value defaults to true.
+    /// This value defaults to true. Redirections are never followed by requests whose body is streamed
+    /// from a reader or a channel, since it cannot be sent again.
     pub fn follow_redirects(&mut self, follow_redirects: bool) {
         self.base_settings.follow_redirects = follow_redirects;
     }
 
+    /// Follow the zero-delay `<meta http-equiv="refresh">` tags of the successful HTML responses to GET requests.
+    ///
+    /// Some pages redirect this way instead of with a 3xx status. The target is fetched like a redirection: it
+    /// counts toward `max_redirections`, its scheme must be allowed and it is recorded in the redirection history.
+    /// The body of HTML responses is read into memory to be searched. This is disabled by default.
+    pub fn follow_meta_refresh(&mut self) {
+        self.base_settings.follow_meta_refresh = true;
+    }
+
+    /// Sets the URL schemes redirections of this `Session` may lead to.
+    ///
+    /// By default, only redirections to `http` and `https` URLs are followed, others fail with
+    /// [`InvalidResponseKind::RedirectionUrl`](crate::InvalidResponseKind::RedirectionUrl) instead of being
+    /// attempted. Passing `&["https"]` refuses redirections from HTTPS to plain HTTP. Schemes other than
+    /// `http` and `https` can be allowed but cannot be connected to.
+    pub fn allowed_redirect_schemes(&mut self, schemes: &[&str]) {
+        self.base_settings.allowed_redirect_schemes = schemes.iter().map(|scheme| scheme.to_string()).collect();
+    }
+
+    /// Sets the `TCP_NODELAY` option of the connections of this `Session`, which disables Nagle's algorithm.
+    ///
+    /// Small writes are then sent immediately instead of being grouped, which lowers the latency of small
+    /// requests at the cost of more packets. The default value is `false`.
+    pub fn tcp_nodelay(&mut self, nodelay: bool) {
+        self.base_settings.tcp_nodelay = nodelay;
+    }
+
+    /// Flush the request line and headers of the requests of this `Session` before writing the body.
+    ///
+    /// The head of a request is buffered with the beginning of its body by default, so a server waiting for the
+    /// headers only receives them once the body starts being produced. With a body which is slow to produce, this
+    /// lets the server start working on the request early. Combine it with [`tcp_nodelay`](Self::tcp_nodelay) to
+    /// send the headers in their own packet without delay.
+    pub fn flush_headers_early(&mut self) {
+        self.base_settings.flush_headers_early = true;
+    }
+
+    /// Set how long to wait for the server to answer the requests of this `Session` sent with
+    /// `Expect: 100-continue`, defaults to 1 second.
+    ///
+    /// The body of such requests is held back until the server sends `100 Continue`, or a final response which
+    /// refuses the request without the body being sent. Many servers ignore the header, so the body is sent
+    /// anyway once this timeout expires. Over TLS, to the server or to a proxy, the body is sent without waiting.
+    pub fn expect_continue_timeout(&mut self, timeout: Duration) {
+        self.base_settings.expect_continue_timeout = timeout;
+    }
+
+    /// Refuse to connect to loopback, private, link-local and unique-local addresses, such as `127.0.0.1`,
+    /// `192.168.0.1`, `169.254.169.254` or `fd00::1`.
+    ///
+    /// This protects servers fetching URLs supplied by their users from reaching internal services. The addresses
+    /// a host resolves to are checked, so a public name pointing to an internal address is refused too, and every
+    /// redirection is checked again. Through a proxy, only hosts which are IP addresses can be checked. Requests
+    /// to blocked addresses fail with [`ErrorKind::HostBlocked`](crate::ErrorKind::HostBlocked).
+    pub fn block_private_addresses(&mut self) {
+        self.base_settings.host_filter.block_private_addresses();
+    }
+
+    /// Only allow connecting to hosts matching one of the given patterns, such as `example.com` or `*.example.com`.
+    ///
+    /// Patterns are matched against the host of the URL of each request and redirection, `*` matches any
+    /// sequence of characters and `?` any single character. Other requests fail with
+    /// [`ErrorKind::HostBlocked`](crate::ErrorKind::HostBlocked).
+    pub fn allowed_hosts(&mut self, patterns: &[&str]) {
+        self.base_settings.host_filter.allow_hosts(patterns);
+    }
+
+    /// Refuse to connect to hosts matching one of the given patterns, such as `internal.example.com` or `10.*`.
+    ///
+    /// Patterns are matched against the host of the URL of each request and redirection, and against the
+    /// addresses it resolves to. `*` matches any sequence of characters and `?` any single character. Blocked
+    /// requests fail with [`ErrorKind::HostBlocked`](crate::ErrorKind::HostBlocked).
+    pub fn blocked_hosts(&mut self, patterns: &[&str]) {
+        self.base_settings.host_filter.block_hosts(patterns);
+    }
+
     /// Sets a connect timeout for this request.
     ///
     /// The default is 30 seconds.
@@ -173,16 +412,27 @@ impl Session {
         self.base_settings.connect_timeout = duration;
     }
 
+    /// Set how many times to retry opening a connection which failed, defaults to 0.
+    ///
+    /// Connections which are refused, time out or cannot be resolved are retried whatever the method of the
+    /// request, since no part of it was sent yet. The delay between attempts starts at 100 milliseconds and
+    /// doubles after each of them. A request which fails once its connection is established is not retried.
+    pub fn connect_retries(&mut self, retries: u32) {
+        self.base_settings.connect_retries = retries;
+    }
+
     /// Sets a read timeout for this request.
     ///
-    /// The default is 30 seconds.
+    /// The default is 30 seconds. It can be overridden for a single request using
+    /// `RequestBuilder::read_timeout` or `RequestBuilder::no_timeout`.
     pub fn read_timeout(&mut self, duration: Duration) {
-        self.base_settings.read_timeout = duration;
+        self.base_settings.read_timeout = Some(duration);
     }
 
     /// Sets a timeout for the whole request.
     ///
-    /// Applies after a TCP connection is established. Defaults to no timeout.
+    /// Applies after a TCP connection is established. Defaults to no timeout. It can be overridden
+    /// for a single request using `RequestBuilder::timeout` or `RequestBuilder::no_timeout`.
     pub fn timeout(&mut self, duration: Duration) {
         self.base_settings.timeout = Some(duration);
     }
@@ -212,6 +462,103 @@ impl Session {
         self.base_settings.allow_compression = allow_compression;
     }
 
+    /// Enforce a minimum interval between the requests of this `Session` to the same host.
+    ///
+    /// Sending a request blocks the calling thread until the interval has elapsed since the previous
+    /// request to that host, redirections included. The spacing is tracked per host and shared with the
+    /// clones of this `Session` made after calling this method, so requests made from several threads are
+    /// spaced out as well.
+    pub fn rate_limit(&mut self, per_host: Duration) {
+        self.base_settings.rate_limiter = Some(Arc::new(RateLimiter::new(per_host)));
+    }
+
+    /// Enable or disable the cookie jar of this `Session`.
+    ///
+    /// This is disabled by default. Once enabled, the cookies set by responses are stored and sent back with
+    /// the following requests, according to their `Domain`, `Path`, `Secure`, `Expires` and `Max-Age`
+    /// attributes. Enabling it creates an empty jar, shared with the clones of this `Session` made afterwards.
+    /// A `Cookie` header set on a request replaces the cookies of the jar.
+    pub fn cookie_store(&mut self, enabled: bool) {
+        self.base_settings.cookie_jar = if enabled {
+            Some(Arc::new(CookieJar::default()))
+        } else {
+            None
+        };
+    }
+
+    /// Set the maximum size of the status line and headers of the response, in bytes.
+    ///
+    /// This value defaults to 64 KiB. A response with a larger head, or with more than 100 headers, is
+    /// rejected with [`InvalidResponseKind::HeadersTooLarge`](crate::InvalidResponseKind::HeadersTooLarge).
+    pub fn max_header_size(&mut self, bytes: usize) {
+        self.base_settings.max_header_size = bytes;
+    }
+
+    /// Set the maximum length of a line read by the line-based readers of the response, in bytes.
+    ///
+    /// This value defaults to 1 MiB. It bounds the memory used by [`Response::ndjson`](crate::Response::ndjson)
+    /// when a server never sends a newline, a longer line fails with
+    /// [`InvalidResponseKind::LineTooLong`](crate::InvalidResponseKind::LineTooLong).
+    ///
+    /// This method only exists when the `json` feature is enabled.
+    #[cfg(feature = "json")]
+    pub fn max_line_size(&mut self, bytes: usize) {
+        self.base_settings.max_line_size = bytes;
+    }
+
+    /// Configure the sockets of the requests of this session before they connect, with options such as
+    /// `SO_RCVBUF`.
+    ///
+    /// The callback is given the raw [`Socket`](crate::Socket) right after it is created, before it is bound
+    /// to a local port and before it connects, which gives access to options attohttpc has no method for. It
+    /// is called once per connection attempt, so possibly several times when a host has several addresses or
+    /// when redirections are followed. An error returned by the callback fails the connection attempt.
+    ///
+    /// This method only exists when the `socket2` feature is enabled.
+    #[cfg(feature = "socket2")]
+    pub fn configure_socket<F>(&mut self, callback: F)
+    where
+        F: Fn(&Socket) -> io::Result<()> + Send + Sync + 'static,
+    {
+        let callback: SocketCallback = Arc::new(callback);
+        self.base_settings.configure_socket = Some(SkipDebug(callback));
+    }
+
+    /// Set a predicate deciding which statuses of the final response are successful.
+    ///
+    /// Sending the request fails with [`ErrorKind::StatusCode`](crate::ErrorKind::StatusCode) when the predicate
+    /// returns false, after redirections are followed. By default any status is returned as a response, use
+    /// [`Response::error_for_status`](crate::Response::error_for_status) to only accept 2xx statuses.
+    pub fn expect_status<F>(&mut self, predicate: F)
+    where
+        F: Fn(StatusCode) -> bool + Send + Sync + 'static,
+    {
+        let predicate: StatusPredicate = Arc::new(predicate);
+        self.base_settings.expect_status = Some(SkipDebug(predicate));
+    }
+
+    /// Add a middleware wrapping the sending of every request of this session.
+    ///
+    /// Middlewares are called in the order they were added, the first one added is the outermost layer and
+    /// sees the request first. See [`Middleware`](crate::Middleware).
+    pub fn with_middleware<M>(&mut self, middleware: M)
+    where
+        M: Middleware + 'static,
+    {
+        self.base_settings.middlewares.0.push(Arc::new(middleware));
+    }
+
+    /// Sets if responses whose framing is ambiguous are rejected.
+    ///
+    /// Such responses announce both a `Content-Length` and `Transfer-Encoding: chunked`, or have `Transfer-Encoding`
+    /// headers which do not end with a single `chunked` coding. This value defaults to false, in which case the
+    /// `Content-Length` header is ignored as the specification requires and bodies with other transfer codings are
+    /// read until the connection closes. The connection is not reused either way, since the server could be trying
+    /// to smuggle a response. Conflicting `Content-Length` headers are always rejected.
+    pub fn strict_framing(&mut self, strict_framing: bool) {
+        self.base_settings.strict_framing = strict_framing;
+    }
+
     /// Sets if this `Request` will accept invalid TLS certificates.
     ///
     /// Accepting invalid certificates implies that invalid hostnames are accepted
@@ -238,8 +585,112 @@ impl Session {
         self.base_settings.accept_invalid_hostnames = accept_invalid_hostnames;
     }
 
+    /// Set the name used for SNI and to validate the TLS certificate of the server for every `Request` of
+    /// this `Session`.
+    ///
+    /// By default the host of the URL is used. When the host is an IP address, no SNI is sent and the
+    /// certificate is validated against the address. Setting a name is useful to connect to a server by its
+    /// address while validating its certificate against its usual name. The name is not used for proxies.
+    ///
+    /// The `rustls` backend cannot validate certificates against IP addresses, a name must be set to connect
+    /// to an IP address with it.
+    pub fn tls_server_name(&mut self, name: impl Into<String>) {
+        self.base_settings.tls_server_name = Some(name.into());
+    }
+
     /// Adds a root certificate that will be trusted.
     pub fn add_root_certificate(&mut self, cert: Certificate) {
         self.base_settings.root_certificates.0.push(cert);
     }
+
+    /// Sets the oldest TLS version accepted for the connections of this `Session`, older versions are rejected.
+    ///
+    /// By default the minimum version of the TLS backend is used.
+    pub fn min_tls_version(&mut self, version: TlsVersion) {
+        self.base_settings.min_tls_version = Some(version);
+    }
+
+    /// Sets the newest TLS version accepted for the connections of this `Session`.
+    ///
+    /// By default the maximum version of the TLS backend is used.
+    pub fn max_tls_version(&mut self, version: TlsVersion) {
+        self.base_settings.max_tls_version = Some(version);
+    }
+
+    /// Sets the protocols advertised with ALPN during the TLS handshakes of this `Session`, in order of preference.
+    ///
+    /// attohttpc only speaks HTTP/1.1, advertising another protocol is only useful to make the server accept
+    /// the connection, the negotiated protocol is available with
+    /// [`Response::alpn_protocol`](crate::Response::alpn_protocol). The protocols are ignored when a
+    /// custom `tls_connector` is set, it advertises its own.
+    pub fn alpn_protocols(&mut self, protocols: &[&str]) {
+        self.base_settings.alpn_protocols = protocols.iter().map(|protocol| protocol.to_string()).collect();
+    }
+
+    /// Use a `native_tls::TlsConnector` configured by the caller for the TLS connections of this `Session`.
+    ///
+    /// The connector supersedes `danger_accept_invalid_certs`, `danger_accept_invalid_hostnames`,
+    /// `add_root_certificate` and the TLS versions, which are ignored when it is set. SNI is sent according to
+    /// the connector as well, including for IP addresses. The server name set with `tls_server_name` is still
+    /// used.
+    ///
+    /// This method only exists when the `tls` feature is enabled.
+    #[cfg(feature = "tls")]
+    pub fn tls_connector(&mut self, connector: TlsConnector) {
+        self.base_settings.tls_connector = Some(connector);
+    }
+}
+
+/// Iterator over the pages of a collection linked with `Link` headers, see [`Session::paginate`].
+#[derive(Debug)]
+pub struct Pages<'a> {
+    session: &'a Session,
+    next: Option<Result<RequestBuilder>>,
+    // URL of the first page, the credentials of the session are only sent to its host.
+    origin: Option<Url>,
+    visited: HashSet<Url>,
+}
+
+impl Iterator for Pages<'_> {
+    type Item = Result<Response>;
+
+    fn next(&mut self) -> Option<Result<Response>> {
+        let mut builder = match self.next.take()? {
+            Ok(builder) => builder,
+            Err(err) => return Some(Err(err)),
+        };
+        let url = builder.inspect().url().clone();
+        let origin = self.origin.get_or_insert_with(|| url.clone());
+        self.visited.insert(url);
+        let resp = match builder.send() {
+            Ok(resp) => resp,
+            Err(err) => return Some(Err(err)),
+        };
+        self.visited.insert(resp.url().clone());
+
+        if let Some(link) = resp.links().get("next") {
+            let next = match resp.url().join(link.uri()) {
+                Ok(next) if self.visited.contains(&next) => {
+                    debug!("stopping pagination, {} was already requested", next);
+                    return Some(Ok(resp));
+                }
+                Ok(next) => {
+                    let mut settings = self.session.base_settings.clone();
+                    if !same_host(&next, origin) {
+                        remove_credentials(&mut settings.headers);
+                    }
+                    RequestBuilder::try_with_settings(Method::GET, next, settings)
+                }
+                Err(_) => Err(InvalidResponseKind::LinkUrl.into()),
+            };
+            self.next = Some(next);
+        }
+        Some(Ok(resp))
+    }
 }
+
+// Sessions are meant to be shared between threads.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync + Clone>() {}
+    assert_send_sync::<Session>();
+};
@@ -1,25 +1,80 @@
+#[cfg(feature = "socket2")]
+use std::io;
+use std::sync::Arc;
 use std::time::Duration;
 
-use http::HeaderMap;
+use http::{HeaderMap, HeaderValue, StatusCode};
+#[cfg(feature = "socket2")]
+use socket2::Socket;
+use url::Url;
 
+use crate::cancel::CancelToken;
 #[cfg(feature = "charsets")]
 use crate::charsets::Charset;
+use crate::cookies::CookieJar;
+use crate::parsing::response::DEFAULT_MAX_HEADER_SIZE;
+#[cfg(feature = "json")]
+use crate::parsing::response_reader::DEFAULT_MAX_LINE_SIZE;
+use crate::pool::Pool;
+use crate::rate_limit::RateLimiter;
 use crate::request::proxy::ProxySettings;
+use crate::request::HostFilter;
+use crate::request::Middleware;
+use crate::request::RequestTarget;
 use crate::skip_debug::SkipDebug;
-use crate::tls::Certificate;
+#[cfg(feature = "tls")]
+use crate::tls::TlsConnector;
+use crate::tls::{Certificate, TlsVersion};
+
+/// Predicate set with `expect_status`, a response with a status for which it returns false is an error.
+pub type StatusPredicate = Arc<dyn Fn(StatusCode) -> bool + Send + Sync>;
+
+/// Callback set with `configure_socket`, it is called on each socket before it connects.
+#[cfg(feature = "socket2")]
+pub type SocketCallback = Arc<dyn Fn(&Socket) -> io::Result<()> + Send + Sync>;
 
 #[derive(Clone, Debug)]
 pub struct BaseSettings {
+    pub base_url: Option<Url>,
     pub headers: HeaderMap,
+    pub host_header: Option<HeaderValue>,
+    pub request_target: Option<RequestTarget>,
     pub max_redirections: u32,
     pub follow_redirects: bool,
+    pub follow_meta_refresh: bool,
+    pub allowed_redirect_schemes: Vec<String>,
     pub connect_timeout: Duration,
-    pub read_timeout: Duration,
+    pub connect_retries: u32,
+    pub read_timeout: Option<Duration>,
     pub timeout: Option<Duration>,
+    pub tcp_nodelay: bool,
+    #[cfg(feature = "socket2")]
+    pub local_port: Option<u16>,
+    #[cfg(feature = "socket2")]
+    pub configure_socket: Option<SkipDebug<SocketCallback>>,
+    pub flush_headers_early: bool,
+    pub expect_continue_timeout: Duration,
     pub proxy_settings: ProxySettings,
+    pub host_filter: HostFilter,
     pub accept_invalid_certs: bool,
     pub accept_invalid_hostnames: bool,
     pub root_certificates: SkipDebug<Vec<Certificate>>,
+    pub tls_server_name: Option<String>,
+    pub min_tls_version: Option<TlsVersion>,
+    pub max_tls_version: Option<TlsVersion>,
+    pub alpn_protocols: Vec<String>,
+    #[cfg(feature = "tls")]
+    pub tls_connector: Option<TlsConnector>,
+    pub pool: Option<Arc<Pool>>,
+    pub cookie_jar: Option<Arc<CookieJar>>,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    pub strict_framing: bool,
+    pub expect_status: Option<SkipDebug<StatusPredicate>>,
+    pub middlewares: SkipDebug<Vec<Arc<dyn Middleware>>>,
+    pub max_header_size: usize,
+    #[cfg(feature = "json")]
+    pub max_line_size: usize,
+    pub cancel_token: Option<CancelToken>,
 
     #[cfg(feature = "charsets")]
     pub default_charset: Option<Charset>,
@@ -30,16 +85,46 @@ pub struct BaseSettings {
 impl Default for BaseSettings {
     fn default() -> Self {
         BaseSettings {
+            base_url: None,
             headers: HeaderMap::new(),
+            host_header: None,
+            request_target: None,
             max_redirections: 5,
             follow_redirects: true,
+            follow_meta_refresh: false,
+            allowed_redirect_schemes: vec!["http".to_string(), "https".to_string()],
             connect_timeout: Duration::from_secs(30),
-            read_timeout: Duration::from_secs(30),
+            connect_retries: 0,
+            read_timeout: Some(Duration::from_secs(30)),
             timeout: None,
+            tcp_nodelay: false,
+            #[cfg(feature = "socket2")]
+            local_port: None,
+            #[cfg(feature = "socket2")]
+            configure_socket: None,
+            flush_headers_early: false,
+            expect_continue_timeout: Duration::from_secs(1),
             proxy_settings: ProxySettings::from_env(),
+            host_filter: HostFilter::default(),
             accept_invalid_certs: false,
             accept_invalid_hostnames: false,
             root_certificates: SkipDebug(Vec::new()),
+            tls_server_name: None,
+            min_tls_version: None,
+            max_tls_version: None,
+            alpn_protocols: Vec::new(),
+            #[cfg(feature = "tls")]
+            tls_connector: None,
+            pool: None,
+            cookie_jar: None,
+            rate_limiter: None,
+            strict_framing: false,
+            expect_status: None,
+            middlewares: SkipDebug(Vec::new()),
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+            #[cfg(feature = "json")]
+            max_line_size: DEFAULT_MAX_LINE_SIZE,
+            cancel_token: None,
 
             #[cfg(feature = "charsets")]
             default_charset: None,
@@ -1,19 +1,33 @@
 #[cfg(test)]
 use std::io::Cursor;
 use std::io::{self, Read, Write};
-use std::net::{Shutdown, TcpStream};
-use std::sync::mpsc;
+use std::mem;
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::sync::{mpsc, Arc};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use url::{Host, Url};
 
-use crate::happy;
+use crate::cancel::CancelToken;
+use crate::happy::{self, SocketOptions};
+use crate::metrics::Recorder;
 use crate::parsing::buffers::BufReaderWrite;
 use crate::parsing::response::parse_response_head;
+use crate::pool::{Pool, PoolKey};
 use crate::request::BaseSettings;
-use crate::tls::{TlsHandshaker, TlsStream};
+use crate::skip_debug::SkipDebug;
+use crate::tls::{PeerCertificate, TlsHandshaker};
 use crate::{ErrorKind, Result};
 
+/// A connection over which requests are written and responses are read.
+///
+/// TCP and TLS streams, proxy tunnels and streams provided by the user all implement it, the
+/// protocol code only ever deals with this trait.
+pub trait Transport: Read + Write + Send {}
+
+impl<T> Transport for T where T: Read + Write + Send {}
+
 pub struct ConnectInfo<'a> {
     pub url: &'a Url,
     pub proxy: Option<&'a Url>,
@@ -21,23 +35,184 @@ pub struct ConnectInfo<'a> {
 }
 
 #[derive(Debug)]
-pub enum BaseStream {
-    Plain {
-        stream: TcpStream,
-        timeout: Option<mpsc::Sender<()>>,
-    },
-    Tls {
-        stream: TlsStream<TcpStream>,
-        timeout: Option<mpsc::Sender<()>>,
-    },
-    Tunnel {
-        stream: Box<TlsStream<BufReaderWrite<BaseStream>>>,
-    },
-    #[cfg(test)]
-    Mock(Cursor<Vec<u8>>),
+pub struct BaseStream {
+    transport: SkipDebug<Box<dyn Transport>>,
+    // Handle on the underlying socket, used to apply the timeouts of each request made on this stream.
+    socket: Option<TcpStream>,
+    timeout: Option<mpsc::Sender<()>>,
+    recorder: Arc<Recorder>,
+    pool: Option<(Arc<Pool>, PoolKey)>,
+    cancel_token: Option<CancelToken>,
+    alpn_protocol: Option<String>,
+    peer_certificate: Option<PeerCertificate>,
 }
 
 impl BaseStream {
+    fn new<T>(
+        transport: T,
+        socket: Option<TcpStream>,
+        timeout: Option<mpsc::Sender<()>>,
+        recorder: Arc<Recorder>,
+    ) -> BaseStream
+    where
+        T: Transport + 'static,
+    {
+        BaseStream {
+            transport: SkipDebug(Box::new(transport)),
+            socket,
+            timeout,
+            recorder,
+            pool: None,
+            cancel_token: None,
+            alpn_protocol: None,
+            peer_certificate: None,
+        }
+    }
+
+    /// Get the recorder which tracks the byte counts and timings of this stream.
+    pub fn recorder(&self) -> Arc<Recorder> {
+        self.recorder.clone()
+    }
+
+    /// Get the address of the peer this stream is connected to, the proxy if one is used.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.socket.as_ref().and_then(|socket| socket.peer_addr().ok())
+    }
+
+    /// Get the protocol negotiated with ALPN during the TLS handshake, if any.
+    pub fn alpn_protocol(&self) -> Option<&str> {
+        self.alpn_protocol.as_deref()
+    }
+
+    /// Get the certificate presented by the remote host during the TLS handshake, if any.
+    pub fn peer_certificate(&self) -> Option<&PeerCertificate> {
+        self.peer_certificate.as_ref()
+    }
+
+    /// Make the reads and writes on this stream fail once the given token is cancelled.
+    pub fn set_cancel_token(&mut self, cancel_token: Option<CancelToken>) {
+        self.cancel_token = cancel_token;
+    }
+
+    /// Mark this stream as belonging to the given pool, it will be returned to it once its response is read.
+    pub fn set_pool(&mut self, pool: Arc<Pool>, key: PoolKey) {
+        self.pool = Some((pool, key));
+    }
+
+    /// Check if this stream will be returned to a pool once its response is read.
+    pub fn is_pooled(&self) -> bool {
+        self.pool.is_some()
+    }
+
+    /// Prevent this stream from being returned to its pool, because the server or the client will close it.
+    pub fn disable_reuse(&mut self) {
+        self.pool = None;
+    }
+
+    /// Return this stream to its pool if it has one, or close it.
+    ///
+    /// The response must have been read entirely, so that the next response starts at the beginning of the stream.
+    pub fn release(mut self) {
+        if let Some((pool, key)) = self.pool.take() {
+            // Stop the timeout of the previous request, the stream is idle.
+            if let Some(timeout) = self.timeout.take() {
+                let _ = timeout.send(());
+            }
+            pool.put(key, self);
+        }
+    }
+
+    /// Check if an idle stream can still be used, which is not the case if the server closed it or sent data
+    /// that no request asked for.
+    ///
+    /// The check is a non-blocking read of the socket, streams provided by the user are assumed to be alive.
+    pub fn is_alive(&self) -> bool {
+        let socket = match &self.socket {
+            Some(socket) => socket,
+            None => return true,
+        };
+        if socket.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let alive = match socket.peek(&mut [0]) {
+            Err(err) => err.kind() == io::ErrorKind::WouldBlock,
+            Ok(_) => false,
+        };
+        socket.set_nonblocking(false).is_ok() && alive
+    }
+
+    /// Check if the server already started sending a final response, without blocking.
+    ///
+    /// Interim `1xx` responses are skipped, a final response received after them is counted. Streams provided by
+    /// the user are never checked. An error is returned if the socket cannot be made blocking again.
+    pub fn has_early_response(&self) -> io::Result<bool> {
+        let socket = match &self.socket {
+            Some(socket) => socket,
+            None => return Ok(false),
+        };
+        socket.set_nonblocking(true)?;
+        let mut buf = [0; 1024];
+        let res = socket.peek(&mut buf);
+        socket.set_nonblocking(false)?;
+        match res {
+            Ok(n) => Ok(starts_final_response(&buf[..n])),
+            // Other errors are returned by the next write.
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Wait up to `timeout` for the server to start a final response, returns true if one was received.
+    ///
+    /// Interim `1xx` responses such as `100 Continue` end the wait as well. Nothing is consumed from the
+    /// stream, the response is read as usual afterwards.
+    pub fn wait_for_response(&self, timeout: Duration) -> bool {
+        let socket = match &self.socket {
+            Some(socket) => socket,
+            None => return false,
+        };
+        let read_timeout = match socket.read_timeout() {
+            Ok(read_timeout) => read_timeout,
+            Err(_) => return false,
+        };
+        let deadline = Instant::now() + timeout;
+        // The status code starts after `HTTP/1.1 `.
+        let mut head = [0; 10];
+        let received = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::from_secs(0) || socket.set_read_timeout(Some(remaining)).is_err() {
+                break false;
+            }
+            match socket.peek(&mut head) {
+                Ok(n) if n == head.len() => break head.starts_with(b"HTTP/") && head[9] != b'1',
+                // The connection was closed, sending the rest of the request reports it.
+                Ok(0) => break false,
+                // Wait for the rest of the status line.
+                Ok(_) => thread::sleep(Duration::from_millis(1)),
+                Err(_) => break false,
+            }
+        };
+        socket.set_read_timeout(read_timeout).is_ok() && received
+    }
+
+    /// Set the read timeout of the socket, streams provided by the user are left untouched.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match &self.socket {
+            Some(socket) => socket.set_read_timeout(timeout),
+            None => Ok(()),
+        }
+    }
+
+    /// Prepare an idle stream taken from a pool to be used by a new request.
+    pub fn reuse(&mut self, base_settings: &BaseSettings) -> Result {
+        if let Some(socket) = &self.socket {
+            socket.set_read_timeout(base_settings.read_timeout)?;
+            socket.set_nodelay(base_settings.tcp_nodelay)?;
+            self.timeout = start_timeout(socket, base_settings.timeout)?;
+        }
+        self.recorder = Arc::new(Recorder::new());
+        Ok(())
+    }
+
     pub fn connect(info: &ConnectInfo) -> Result<BaseStream> {
         let connect_url = info.proxy.unwrap_or(info.url);
 
@@ -46,10 +221,11 @@ impl BaseStream {
 
         debug!("trying to connect to {}:{}", host, port);
 
+        let recorder = Arc::new(Recorder::new());
         let stream = match connect_url.scheme() {
-            "http" => BaseStream::connect_tcp(&host, port, info)
-                .map(|(stream, timeout)| BaseStream::Plain { stream, timeout }),
-            "https" => BaseStream::connect_tls(&host, port, info),
+            "http" => BaseStream::connect_tcp(&host, port, info, &recorder)
+                .map(|(stream, socket, timeout)| BaseStream::new(stream, Some(socket), timeout, recorder)),
+            "https" => BaseStream::connect_tls(&host, port, info, recorder),
             _ => Err(ErrorKind::InvalidBaseUrl.into()),
         }?;
 
@@ -69,6 +245,10 @@ impl BaseStream {
         base_settings: &BaseSettings,
     ) -> Result<BaseStream> {
         let remote_host = remote_url.host_str().ok_or(ErrorKind::InvalidUrlHost)?;
+        let remote_name = server_name(
+            &remote_url.host().ok_or(ErrorKind::InvalidUrlHost)?,
+            base_settings.tls_server_name.as_deref(),
+        );
         let remote_port = remote_url.port_or_known_default().ok_or(ErrorKind::InvalidUrlPort)?;
         let proxy_host = proxy_url.host_str().ok_or(ErrorKind::InvalidUrlHost)?;
         let proxy_port = proxy_url.port_or_known_default().ok_or(ErrorKind::InvalidUrlPort)?;
@@ -78,13 +258,19 @@ impl BaseStream {
             remote_host, remote_port, proxy_host, proxy_port,
         );
 
+        // The bytes exchanged with the proxy are not counted, only those sent to the remote host are.
+        let recorder = mem::replace(&mut stream.recorder, Arc::new(Recorder::new()));
+        // The timeout is owned by the outer stream, which will be the one returned to a pool.
+        let socket = stream.socket.take();
+        let timeout = stream.timeout.take();
+
         write!(stream, "CONNECT {}:{} HTTP/1.1\r\n", remote_host, remote_port)?;
         write!(stream, "Host: {}:{}\r\n", proxy_host, proxy_port)?;
         write!(stream, "Connection: close\r\n")?;
         write!(stream, "\r\n")?;
 
         let mut stream = BufReaderWrite::new(stream);
-        let (status, _) = parse_response_head(&mut stream)?;
+        let (_, status, _) = parse_response_head(&mut stream, base_settings.max_header_size)?;
 
         if !status.is_success() {
             // Error initializaing tunnel, get status code and up to 10 KiB of data from the body.
@@ -99,85 +285,134 @@ impl BaseStream {
 
         let mut handshaker = TlsHandshaker::new();
         apply_base_settings(&mut handshaker, base_settings)?;
-        let stream = handshaker.handshake(remote_host, stream)?;
+        let stream = handshaker.handshake(&remote_name, stream)?;
+        recorder.tls_done();
 
-        Ok(BaseStream::Tunnel {
-            stream: Box::new(stream),
-        })
+        let alpn_protocol = stream.alpn_protocol();
+        let peer_certificate = stream.peer_certificate();
+        let mut stream = BaseStream::new(stream, socket, timeout, recorder);
+        stream.alpn_protocol = alpn_protocol;
+        stream.peer_certificate = peer_certificate;
+        Ok(stream)
+    }
+
+    fn connect_tcp(
+        host: &Host<&str>,
+        port: u16,
+        info: &ConnectInfo,
+        recorder: &Recorder,
+    ) -> Result<(TcpStream, TcpStream, Option<mpsc::Sender<()>>)> {
+        let mut addrs = happy::resolve(host, port)?;
+        recorder.resolved();
+        if info.proxy.is_none() {
+            addrs = info.base_settings.host_filter.check_addrs(addrs)?;
+        }
+        let options = SocketOptions {
+            #[cfg(feature = "socket2")]
+            local_port: info.base_settings.local_port,
+            #[cfg(feature = "socket2")]
+            configure: info
+                .base_settings
+                .configure_socket
+                .as_ref()
+                .map(|callback| callback.0.clone()),
+        };
+        let stream = happy::connect(&addrs, &options, info.base_settings.connect_timeout).map_err(|err| {
+            #[cfg(feature = "socket2")]
+            if let Some(port) = options.local_port.filter(|_| err.kind() == io::ErrorKind::AddrInUse) {
+                return ErrorKind::LocalPortInUse(port).into();
+            }
+            crate::Error::from(err)
+        })?;
+        recorder.connected();
+        stream.set_read_timeout(info.base_settings.read_timeout)?;
+        stream.set_nodelay(info.base_settings.tcp_nodelay)?;
+        let socket = stream.try_clone()?;
+        let timeout = start_timeout(&socket, info.base_settings.timeout)?;
+        Ok((stream, socket, timeout))
     }
 
-    fn connect_tcp(host: &Host<&str>, port: u16, info: &ConnectInfo) -> Result<(TcpStream, Option<mpsc::Sender<()>>)> {
-        let stream = happy::connect(host, port, info.base_settings.connect_timeout)?;
-        stream.set_read_timeout(Some(info.base_settings.read_timeout))?;
-        let timeout = info
-            .base_settings
-            .timeout
-            .map(|timeout| -> Result<mpsc::Sender<()>> {
-                let stream = stream.try_clone()?;
-                let (tx, rx) = mpsc::channel();
-                thread::spawn(move || {
-                    if let Err(mpsc::RecvTimeoutError::Timeout) = rx.recv_timeout(timeout) {
-                        drop(rx);
-                        let _ = stream.shutdown(Shutdown::Both);
-                    }
-                });
-                Ok(tx)
-            })
-            .transpose()?;
-        Ok((stream, timeout))
-    }
-
-    fn connect_tls(host: &Host<&str>, port: u16, info: &ConnectInfo) -> Result<BaseStream> {
-        let (stream, timeout) = BaseStream::connect_tcp(host, port, info)?;
+    fn connect_tls(host: &Host<&str>, port: u16, info: &ConnectInfo, recorder: Arc<Recorder>) -> Result<BaseStream> {
+        let (stream, socket, timeout) = BaseStream::connect_tcp(host, port, info, &recorder)?;
         let mut handshaker = TlsHandshaker::new();
         apply_base_settings(&mut handshaker, info.base_settings)?;
-        let stream = handshaker.handshake(&host.to_string(), stream)?;
-        Ok(BaseStream::Tls { stream, timeout })
+        // The server name override is meant for the remote host, not for a proxy.
+        let name = match info.proxy {
+            Some(_) => server_name(host, None),
+            None => server_name(host, info.base_settings.tls_server_name.as_deref()),
+        };
+        let stream = handshaker.handshake(&name, stream)?;
+        recorder.tls_done();
+        // Through a proxy, this is the connection to the proxy and its protocol is not the one of the remote host.
+        let alpn_protocol = stream.alpn_protocol();
+        let peer_certificate = stream.peer_certificate();
+        let mut stream = BaseStream::new(stream, Some(socket), timeout, recorder);
+        if info.proxy.is_none() {
+            stream.alpn_protocol = alpn_protocol;
+            stream.peer_certificate = peer_certificate;
+        }
+        Ok(stream)
+    }
+
+    pub fn custom<T>(transport: T) -> BaseStream
+    where
+        T: Transport + 'static,
+    {
+        BaseStream::new(transport, None, None, Arc::new(Recorder::new()))
     }
 
     #[cfg(test)]
     pub fn mock(bytes: Vec<u8>) -> BaseStream {
-        BaseStream::Mock(Cursor::new(bytes))
+        BaseStream::new(Cursor::new(bytes), None, None, Arc::new(Recorder::new()))
     }
 }
 
 impl Read for BaseStream {
     #[inline]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        match self {
-            BaseStream::Plain { stream, timeout } => read_timeout(stream, buf, timeout),
-            BaseStream::Tls { stream, timeout } => read_timeout(stream, buf, timeout),
-            BaseStream::Tunnel { stream } => stream.read(buf),
-            #[cfg(test)]
-            BaseStream::Mock(s) => s.read(buf),
+        if let Some(token) = &self.cancel_token {
+            token.check_io()?;
         }
+        let n = read_timeout(&mut self.transport.0, buf, &self.timeout)?;
+        self.recorder.add_received(n);
+        Ok(n)
     }
 }
 
 impl Write for BaseStream {
     #[inline]
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        match self {
-            BaseStream::Plain { stream, .. } => stream.write(buf),
-            BaseStream::Tls { stream, .. } => stream.write(buf),
-            BaseStream::Tunnel { stream } => stream.write(buf),
-            #[cfg(test)]
-            _ => Ok(0),
+        if let Some(token) = &self.cancel_token {
+            token.check_io()?;
         }
+        let n = self.transport.0.write(buf)?;
+        self.recorder.add_sent(n);
+        Ok(n)
     }
 
     #[inline]
     fn flush(&mut self) -> io::Result<()> {
-        match self {
-            BaseStream::Plain { stream, .. } => stream.flush(),
-            BaseStream::Tls { stream, .. } => stream.flush(),
-            BaseStream::Tunnel { stream } => stream.flush(),
-            #[cfg(test)]
-            _ => Ok(()),
-        }
+        self.transport.0.flush()
     }
 }
 
+/// Shut the socket down once the timeout expires, unless the returned sender is used or dropped before.
+fn start_timeout(socket: &TcpStream, timeout: Option<Duration>) -> Result<Option<mpsc::Sender<()>>> {
+    timeout
+        .map(|timeout| -> Result<mpsc::Sender<()>> {
+            let socket = socket.try_clone()?;
+            let (tx, rx) = mpsc::channel();
+            thread::spawn(move || {
+                if let Err(mpsc::RecvTimeoutError::Timeout) = rx.recv_timeout(timeout) {
+                    drop(rx);
+                    let _ = socket.shutdown(Shutdown::Both);
+                }
+            });
+            Ok(tx)
+        })
+        .transpose()
+}
+
 fn read_timeout(stream: &mut impl Read, buf: &mut [u8], timeout: &Option<mpsc::Sender<()>>) -> io::Result<usize> {
     let read = stream.read(buf)?;
 
@@ -190,11 +425,67 @@ fn read_timeout(stream: &mut impl Read, buf: &mut [u8], timeout: &Option<mpsc::S
     Ok(read)
 }
 
+/// Get the name used for SNI and certificate validation, IPv6 addresses are returned without brackets.
+fn server_name(host: &Host<&str>, server_name: Option<&str>) -> String {
+    match (server_name, host) {
+        (Some(name), _) => name.to_string(),
+        (None, Host::Domain(domain)) => domain.to_string(),
+        (None, Host::Ipv4(ip)) => ip.to_string(),
+        (None, Host::Ipv6(ip)) => ip.to_string(),
+    }
+}
+
 fn apply_base_settings(handshaker: &mut TlsHandshaker, base_settings: &BaseSettings) -> Result<()> {
     handshaker.danger_accept_invalid_certs(base_settings.accept_invalid_certs);
     handshaker.danger_accept_invalid_hostnames(base_settings.accept_invalid_hostnames);
+    handshaker.set_versions(base_settings.min_tls_version, base_settings.max_tls_version);
+    handshaker.set_alpn_protocols(&base_settings.alpn_protocols);
     for cert in &base_settings.root_certificates.0 {
         handshaker.add_root_certificate(cert.clone())?;
     }
+    #[cfg(feature = "tls")]
+    if let Some(connector) = &base_settings.tls_connector {
+        handshaker.set_connector(connector.clone());
+    }
     Ok(())
 }
+
+/// Check if data received from a server starts a final response, after the interim responses it starts with.
+fn starts_final_response(mut data: &[u8]) -> bool {
+    loop {
+        // The status code starts after `HTTP/1.1 `.
+        if data.len() < 10 || !data.starts_with(b"HTTP/") {
+            return false;
+        }
+        if data[9] != b'1' {
+            return true;
+        }
+        match data.windows(4).position(|w| w == b"\r\n\r\n") {
+            Some(end) => data = &data[end + 4..],
+            None => return false,
+        }
+    }
+}
+
+#[test]
+fn test_starts_final_response() {
+    assert!(!starts_final_response(b""));
+    assert!(!starts_final_response(b"HTTP/1.1 "));
+    assert!(starts_final_response(b"HTTP/1.1 413 Payload Too Large\r\n"));
+    assert!(!starts_final_response(b"HTTP/1.1 100 Continue\r\n\r\n"));
+    assert!(!starts_final_response(b"HTTP/1.1 100 Continue\r\n"));
+    assert!(starts_final_response(
+        b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 103 Early Hints\r\nLink: </a>\r\n\r\nHTTP/1.1 413"
+    ));
+}
+
+#[test]
+fn test_server_name() {
+    let ipv6: Host<&str> = Host::Ipv6("::1".parse().unwrap());
+    assert_eq!(server_name(&ipv6, None), "::1");
+    assert_eq!(server_name(&Host::Domain("example.com"), None), "example.com");
+    assert_eq!(
+        server_name(&Host::Domain("10.0.0.1"), Some("example.com")),
+        "example.com"
+    );
+}
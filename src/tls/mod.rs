@@ -6,6 +6,7 @@ mod rustls_impl;
 
 #[cfg(all(not(feature = "tls"), not(feature = "tls-rustls")))]
 mod no_tls_impl;
+mod peer_certificate;
 
 #[cfg(feature = "tls")]
 pub use native_tls_impl::*;
@@ -15,3 +16,20 @@ pub use rustls_impl::*;
 
 #[cfg(all(not(feature = "tls"), not(feature = "tls-rustls")))]
 pub use no_tls_impl::*;
+pub(crate) use peer_certificate::days_since_epoch;
+pub use peer_certificate::PeerCertificate;
+
+/// A version of the TLS protocol, used to restrict the versions accepted for a connection.
+///
+/// Versions are ordered from the oldest to the newest. The `rustls` backend only supports TLS 1.2 and 1.3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TlsVersion {
+    /// TLS 1.0, deprecated by RFC 8996.
+    Tls1_0,
+    /// TLS 1.1, deprecated by RFC 8996.
+    Tls1_1,
+    /// TLS 1.2.
+    Tls1_2,
+    /// TLS 1.3.
+    Tls1_3,
+}
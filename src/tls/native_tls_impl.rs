@@ -1,24 +1,39 @@
 use std::fmt;
 use std::io;
 use std::io::prelude::*;
+use std::net::IpAddr;
 
 use native_tls::HandshakeError;
 
+use super::{PeerCertificate, TlsVersion};
 use crate::Result;
 
 pub type Certificate = native_tls::Certificate;
+pub type TlsConnector = native_tls::TlsConnector;
+
+/// Get the DER encoding of a certificate, used to tell apart connections trusting different roots.
+pub fn certificate_der(cert: &Certificate) -> Vec<u8> {
+    cert.to_der().unwrap_or_default()
+}
 
 pub struct TlsHandshaker {
     inner: native_tls::TlsConnectorBuilder,
+    connector: Option<TlsConnector>,
 }
 
 impl TlsHandshaker {
     pub fn new() -> TlsHandshaker {
         TlsHandshaker {
             inner: native_tls::TlsConnector::builder(),
+            connector: None,
         }
     }
 
+    /// Use a connector built by the user instead of the settings of this handshaker.
+    pub fn set_connector(&mut self, connector: TlsConnector) {
+        self.connector = Some(connector);
+    }
+
     pub fn danger_accept_invalid_certs(&mut self, accept_invalid_certs: bool) {
         self.inner.danger_accept_invalid_certs(accept_invalid_certs);
     }
@@ -32,12 +47,43 @@ impl TlsHandshaker {
         Ok(())
     }
 
-    pub fn handshake<S>(&self, domain: &str, stream: S) -> Result<TlsStream<S>>
+    pub fn set_alpn_protocols(&mut self, protocols: &[String]) {
+        let protocols: Vec<&str> = protocols.iter().map(String::as_str).collect();
+        self.inner.request_alpns(&protocols);
+    }
+
+    pub fn set_versions(&mut self, min: Option<TlsVersion>, max: Option<TlsVersion>) {
+        fn protocol(version: TlsVersion) -> native_tls::Protocol {
+            match version {
+                TlsVersion::Tls1_0 => native_tls::Protocol::Tlsv10,
+                TlsVersion::Tls1_1 => native_tls::Protocol::Tlsv11,
+                TlsVersion::Tls1_2 => native_tls::Protocol::Tlsv12,
+                TlsVersion::Tls1_3 => native_tls::Protocol::Tlsv13,
+            }
+        }
+
+        // Unset versions keep the defaults of native-tls, which does not accept versions older than TLS 1.2.
+        if let Some(min) = min {
+            self.inner.min_protocol_version(Some(protocol(min)));
+        }
+        if let Some(max) = max {
+            self.inner.max_protocol_version(Some(protocol(max)));
+        }
+    }
+
+    pub fn handshake<S>(&mut self, domain: &str, stream: S) -> Result<TlsStream<S>>
     where
         S: Read + Write,
     {
-        let connector = self.inner.build()?;
-        let stream = match connector.connect(&domain, stream) {
+        let connector = match self.connector.take() {
+            Some(connector) => connector,
+            None => {
+                // SNI does not allow IP addresses, the certificate is still validated against the address.
+                self.inner.use_sni(domain.parse::<IpAddr>().is_err());
+                self.inner.build()?
+            }
+        };
+        let stream = match connector.connect(domain, stream) {
             Ok(stream) => stream,
             Err(HandshakeError::Failure(err)) => return Err(err.into()),
             Err(HandshakeError::WouldBlock(mut stream)) => loop {
@@ -59,6 +105,21 @@ where
     inner: native_tls::TlsStream<S>,
 }
 
+impl<S> TlsStream<S>
+where
+    S: Read + Write,
+{
+    pub fn alpn_protocol(&self) -> Option<String> {
+        let protocol = self.inner.negotiated_alpn().ok()??;
+        String::from_utf8(protocol).ok()
+    }
+
+    pub fn peer_certificate(&self) -> Option<PeerCertificate> {
+        let cert = self.inner.peer_certificate().ok()??;
+        cert.to_der().ok().map(PeerCertificate::from_der)
+    }
+}
+
 impl<S> Read for TlsStream<S>
 where
     S: Read + Write,
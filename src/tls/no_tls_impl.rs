@@ -3,9 +3,17 @@ use std::io;
 use std::io::prelude::*;
 use std::marker::PhantomData;
 
+use super::{PeerCertificate, TlsVersion};
 use crate::{ErrorKind, Result};
 
-pub type Certificate = ();
+/// No root certificate can be created when TLS is disabled.
+#[derive(Clone, Debug)]
+pub struct Certificate(());
+
+/// Get the DER encoding of a certificate, used to tell apart connections trusting different roots.
+pub fn certificate_der(_cert: &Certificate) -> Vec<u8> {
+    Vec::new()
+}
 
 pub struct TlsHandshaker {}
 
@@ -22,7 +30,11 @@ impl TlsHandshaker {
         Ok(())
     }
 
-    pub fn handshake<S>(&self, _domain: &str, _stream: S) -> Result<TlsStream<S>>
+    pub fn set_alpn_protocols(&mut self, _protocols: &[String]) {}
+
+    pub fn set_versions(&mut self, _min: Option<TlsVersion>, _max: Option<TlsVersion>) {}
+
+    pub fn handshake<S>(&mut self, _domain: &str, _stream: S) -> Result<TlsStream<S>>
     where
         S: Read + Write,
     {
@@ -37,6 +49,19 @@ where
     dummy: PhantomData<S>,
 }
 
+impl<S> TlsStream<S>
+where
+    S: Read + Write,
+{
+    pub fn alpn_protocol(&self) -> Option<String> {
+        None
+    }
+
+    pub fn peer_certificate(&self) -> Option<PeerCertificate> {
+        None
+    }
+}
+
 impl<S> Read for TlsStream<S>
 where
     S: Read + Write,
@@ -0,0 +1,167 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The certificate presented by the server during the TLS handshake.
+///
+/// See [`Response::peer_certificate`](crate::Response::peer_certificate). The validity window is read from the
+/// certificate itself, it lets callers enforce their own policy, such as rejecting certificates which expire
+/// soon. Neither backend exposes the OCSP response stapled by the server.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PeerCertificate {
+    der: Vec<u8>,
+}
+
+impl PeerCertificate {
+    #[cfg_attr(not(any(feature = "tls", feature = "tls-rustls")), allow(dead_code))]
+    pub(crate) fn from_der(der: Vec<u8>) -> PeerCertificate {
+        PeerCertificate { der }
+    }
+
+    /// Get the DER encoding of this certificate.
+    #[inline]
+    pub fn der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// Get the start of the validity window of this certificate, `None` if it cannot be parsed.
+    pub fn not_before(&self) -> Option<SystemTime> {
+        self.validity().map(|(not_before, _)| not_before)
+    }
+
+    /// Get the end of the validity window of this certificate, `None` if it cannot be parsed.
+    pub fn not_after(&self) -> Option<SystemTime> {
+        self.validity().map(|(_, not_after)| not_after)
+    }
+
+    fn validity(&self) -> Option<(SystemTime, SystemTime)> {
+        let (_, certificate, _) = read_tlv(&self.der, SEQUENCE)?;
+        let (_, mut tbs, _) = read_tlv(certificate, SEQUENCE)?;
+        // The version is optional and tagged explicitly.
+        if tbs.first() == Some(&0xa0) {
+            tbs = read_tlv(tbs, 0xa0)?.2;
+        }
+        let (_, _, rest) = read_tlv(tbs, INTEGER)?;
+        let (_, _, rest) = read_tlv(rest, SEQUENCE)?;
+        let (_, _, rest) = read_tlv(rest, SEQUENCE)?;
+        let (_, validity, _) = read_tlv(rest, SEQUENCE)?;
+
+        let (tag, not_before, rest) = read_any_tlv(validity)?;
+        let not_before = parse_time(tag, not_before)?;
+        let (tag, not_after, _) = read_any_tlv(rest)?;
+        let not_after = parse_time(tag, not_after)?;
+        Some((not_before, not_after))
+    }
+}
+
+impl std::fmt::Debug for PeerCertificate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PeerCertificate")
+            .field("not_before", &self.not_before())
+            .field("not_after", &self.not_after())
+            .finish()
+    }
+}
+
+const INTEGER: u8 = 0x02;
+const SEQUENCE: u8 = 0x30;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+
+/// Read a DER element, returns its tag, its content and the rest of the input.
+fn read_any_tlv(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, input) = input.split_first()?;
+    let (&first, input) = input.split_first()?;
+    let (len, input) = if first < 0x80 {
+        (first as usize, input)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || input.len() < count {
+            return None;
+        }
+        let len = input[..count].iter().fold(0, |len, &b| (len << 8) | b as usize);
+        (len, &input[count..])
+    };
+    if input.len() < len {
+        return None;
+    }
+    Some((tag, &input[..len], &input[len..]))
+}
+
+fn read_tlv(input: &[u8], expected: u8) -> Option<(u8, &[u8], &[u8])> {
+    read_any_tlv(input).filter(|(tag, _, _)| *tag == expected)
+}
+
+/// Parse an `UTCTime` or a `GeneralizedTime`, which are always in UTC in certificates.
+fn parse_time(tag: u8, value: &[u8]) -> Option<SystemTime> {
+    let value = value.strip_suffix(b"Z")?;
+    if !value.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let digits = |range: std::ops::Range<usize>| -> Option<u64> {
+        value
+            .get(range)?
+            .iter()
+            .try_fold(0, |n, &d| Some(n * 10 + u64::from(d - b'0')))
+    };
+
+    let (year, rest) = match (tag, value.len()) {
+        // Two-digit years from 50 are in the 20th century.
+        (UTC_TIME, 12) => match digits(0..2)? {
+            year if year >= 50 => (1900 + year, 2),
+            year => (2000 + year, 2),
+        },
+        (GENERALIZED_TIME, 14) => (digits(0..4)?, 4),
+        _ => return None,
+    };
+    let month = digits(rest..rest + 2)?;
+    let day = digits(rest + 2..rest + 4)?;
+    let hours = digits(rest + 4..rest + 6)?;
+    let minutes = digits(rest + 6..rest + 8)?;
+    let seconds = digits(rest + 8..rest + 10)?;
+    if year < 1970 || !(1..=12).contains(&month) || !(1..=31).contains(&day) || hours > 23 || minutes > 59 {
+        return None;
+    }
+
+    let secs = days_since_epoch(year, month, day) * 86400 + hours * 3600 + minutes * 60 + seconds;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Count the days from 1970-01-01 to a date of the proleptic Gregorian calendar.
+pub(crate) fn days_since_epoch(year: u64, month: u64, day: u64) -> u64 {
+    // Years start in March so that the leap day is the last day of the year.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[test]
+fn test_parse_time() {
+    let secs = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap().as_secs();
+    assert_eq!(parse_time(UTC_TIME, b"700101000000Z").map(secs), Some(0));
+    assert_eq!(parse_time(UTC_TIME, b"200229123456Z").map(secs), Some(1_582_979_696));
+    assert_eq!(
+        parse_time(GENERALIZED_TIME, b"20500101000000Z").map(secs),
+        Some(2_524_608_000)
+    );
+    assert_eq!(parse_time(UTC_TIME, b"200229123456"), None);
+    assert_eq!(parse_time(UTC_TIME, b"201329123456Z"), None);
+    assert_eq!(parse_time(GENERALIZED_TIME, b"200229123456Z"), None);
+}
+
+#[test]
+fn test_validity() {
+    // Minimal structure of a certificate, up to its validity.
+    let mut tbs = vec![0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x01, 0x01, 0x30, 0x00, 0x30, 0x00];
+    tbs.extend_from_slice(b"\x30\x1e\x17\x0d200101000000Z\x17\x0d300101000000Z");
+    let mut cert = vec![0x30, tbs.len() as u8];
+    cert.extend_from_slice(&tbs);
+    let mut der = vec![0x30, 0x81, cert.len() as u8];
+    der.extend_from_slice(&cert);
+
+    let cert = PeerCertificate::from_der(der);
+    assert_eq!(cert.not_before(), Some(UNIX_EPOCH + Duration::from_secs(1_577_836_800)));
+    assert_eq!(cert.not_after(), Some(UNIX_EPOCH + Duration::from_secs(1_893_456_000)));
+    assert_eq!(PeerCertificate::from_der(vec![0x30, 0x05]).not_after(), None);
+}
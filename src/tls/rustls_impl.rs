@@ -4,15 +4,22 @@ use std::io::prelude::*;
 use std::sync::Arc;
 
 use rustls::{
-    ClientConfig, ClientSession, ServerCertVerified, ServerCertVerifier, Session, StreamOwned, WebPKIVerifier,
+    ClientConfig, ClientSession, ProtocolVersion, ServerCertVerified, ServerCertVerifier, Session, StreamOwned,
+    WebPKIVerifier,
 };
 use webpki::DNSNameRef;
 use webpki_roots::TLS_SERVER_ROOTS;
 
+use super::{PeerCertificate, TlsVersion};
 use crate::Result;
 
 pub type Certificate = rustls::Certificate;
 
+/// Get the DER encoding of a certificate, used to tell apart connections trusting different roots.
+pub fn certificate_der(cert: &Certificate) -> Vec<u8> {
+    cert.0.clone()
+}
+
 pub struct TlsHandshaker {
     inner: ClientConfig,
     accept_invalid_certs: bool,
@@ -56,10 +63,31 @@ impl TlsHandshaker {
         Ok(())
     }
 
-    pub fn handshake<S>(&self, domain: &str, mut stream: S) -> Result<TlsStream<S>>
+    pub fn set_alpn_protocols(&mut self, protocols: &[String]) {
+        let protocols: Vec<Vec<u8>> = protocols.iter().map(|protocol| protocol.as_bytes().to_vec()).collect();
+        self.inner.set_protocols(&protocols);
+    }
+
+    pub fn set_versions(&mut self, min: Option<TlsVersion>, max: Option<TlsVersion>) {
+        let range = min.unwrap_or(TlsVersion::Tls1_0)..=max.unwrap_or(TlsVersion::Tls1_3);
+        self.inner.versions.retain(|version| match version {
+            ProtocolVersion::TLSv1_2 => range.contains(&TlsVersion::Tls1_2),
+            ProtocolVersion::TLSv1_3 => range.contains(&TlsVersion::Tls1_3),
+            _ => false,
+        });
+    }
+
+    pub fn handshake<S>(&mut self, domain: &str, mut stream: S) -> Result<TlsStream<S>>
     where
         S: Read + Write,
     {
+        if self.inner.versions.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "rustls supports none of the allowed TLS versions",
+            )
+            .into());
+        }
         let domain = DNSNameRef::try_from_ascii_str(domain)?;
         let config = Arc::new(self.inner.clone());
         let mut session = ClientSession::new(&config, domain);
@@ -87,6 +115,16 @@ impl<S> TlsStream<S>
 where
     S: Read + Write,
 {
+    pub fn alpn_protocol(&self) -> Option<String> {
+        let protocol = self.inner.sess.get_alpn_protocol()?;
+        String::from_utf8(protocol.to_vec()).ok()
+    }
+
+    pub fn peer_certificate(&self) -> Option<PeerCertificate> {
+        let certs = self.inner.sess.get_peer_certificates()?;
+        certs.first().map(|cert| PeerCertificate::from_der(cert.0.clone()))
+    }
+
     fn handle_close_notify(&mut self, res: io::Result<usize>) -> io::Result<usize> {
         match res {
             Err(err) if err.kind() == io::ErrorKind::ConnectionAborted => {
@@ -0,0 +1,77 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use attohttpc::{CancelToken, ErrorKind};
+
+/// Start a server sending a chunked body of 100 chunks of one byte, one chunk every 20 ms.
+fn start_slow_body_server() -> u16 {
+    let listener = TcpListener::bind("localhost:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+            line.clear();
+        }
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n")
+            .unwrap();
+        for _ in 0..100 {
+            if stream.write_all(b"1\r\na\r\n").is_err() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        let _ = stream.write_all(b"0\r\n\r\n");
+    });
+    port
+}
+
+#[test]
+fn test_cancel_while_reading_body() {
+    let port = start_slow_body_server();
+    let token = CancelToken::new();
+
+    let mut resp = attohttpc::get(format!("http://localhost:{}", port))
+        .cancel_token(token.clone())
+        .send()
+        .unwrap();
+
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        token.cancel();
+        Instant::now()
+    });
+
+    let mut buf = [0; 16];
+    let err = loop {
+        match resp.read(&mut buf) {
+            Ok(0) => panic!("the body was read entirely"),
+            Ok(_) => (),
+            Err(err) => break err,
+        }
+    };
+    let aborted = Instant::now();
+    let cancelled = canceller.join().unwrap();
+
+    // The read aborts with the next chunk at the latest.
+    assert!(aborted.duration_since(cancelled) < Duration::from_millis(500));
+    let inner = err.into_inner().unwrap().downcast::<attohttpc::Error>().unwrap();
+    assert!(matches!(inner.kind(), ErrorKind::Cancelled));
+}
+
+#[test]
+fn test_cancel_before_send() {
+    let port = start_slow_body_server();
+    let token = CancelToken::new();
+    token.cancel();
+
+    let err = attohttpc::get(format!("http://localhost:{}", port))
+        .cancel_token(token)
+        .send()
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::Cancelled));
+}
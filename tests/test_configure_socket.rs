@@ -0,0 +1,60 @@
+#![cfg(feature = "socket2")]
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use attohttpc::ErrorKind;
+
+/// Start a server answering every request with a short body.
+fn start_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                line.clear();
+            }
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+        }
+    });
+    port
+}
+
+#[test]
+fn test_configure_socket_recv_buffer() {
+    let port = start_server();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}", port))
+        .configure_socket(move |socket| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            socket.set_recv_buffer_size(64 * 1024)?;
+            assert!(socket.recv_buffer_size()? > 0);
+            Ok(())
+        })
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "hello");
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_configure_socket_error() {
+    let port = start_server();
+
+    let err = attohttpc::get(format!("http://127.0.0.1:{}", port))
+        .configure_socket(|_| Err(io::Error::new(io::ErrorKind::PermissionDenied, "denied")))
+        .send()
+        .unwrap_err();
+    match err.kind() {
+        ErrorKind::Io(err) => assert_eq!(err.kind(), io::ErrorKind::PermissionDenied),
+        err => panic!("unexpected error {:?}", err),
+    }
+}
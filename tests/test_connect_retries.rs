@@ -0,0 +1,115 @@
+#![cfg(feature = "socket2")]
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// Start a server echoing the body of every request, returns its port and a counter of the connections it
+/// accepted. With `hang_up`, connections are closed as soon as the head of the request is read.
+fn start_server(hang_up: bool) -> (u16, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let counter = connections.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            let mut len = 0;
+            while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    len = value.trim().parse().unwrap();
+                }
+                line.clear();
+            }
+            if hang_up {
+                continue;
+            }
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).unwrap();
+            let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", len);
+            let _ = stream.write_all(&body);
+        }
+    });
+    (port, connections)
+}
+
+/// Make a callback refusing the first `refused` attempts to open a connection, returns it with a counter of the
+/// attempts.
+fn refuse_first(refused: usize) -> (impl Fn(&attohttpc::Socket) -> io::Result<()>, Arc<AtomicUsize>) {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let counter = attempts.clone();
+    let callback = move |_: &attohttpc::Socket| {
+        if counter.fetch_add(1, Ordering::SeqCst) < refused {
+            return Err(io::Error::new(io::ErrorKind::ConnectionRefused, "refused"));
+        }
+        Ok(())
+    };
+    (callback, attempts)
+}
+
+#[test]
+fn test_connect_retries_get() {
+    let (port, connections) = start_server(false);
+    let (callback, attempts) = refuse_first(1);
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}", port))
+        .configure_socket(callback)
+        .connect_retries(2)
+        .send()
+        .unwrap();
+    assert!(resp.is_success());
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_connect_retries_post() {
+    let (port, connections) = start_server(false);
+    let (callback, attempts) = refuse_first(2);
+
+    // The body cannot be sent again, but nothing was sent when the connection failed.
+    let resp = attohttpc::post(format!("http://127.0.0.1:{}", port))
+        .body_reader_sized(io::Cursor::new("hello"), 5)
+        .configure_socket(callback)
+        .connect_retries(2)
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "hello");
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_connect_retries_exhausted() {
+    let (port, connections) = start_server(false);
+    let (callback, attempts) = refuse_first(3);
+
+    let err = attohttpc::get(format!("http://127.0.0.1:{}", port))
+        .configure_socket(callback)
+        .connect_retries(2)
+        .send()
+        .unwrap_err();
+    match err.kind() {
+        attohttpc::ErrorKind::Io(err) => assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused),
+        err => panic!("unexpected error {:?}", err),
+    }
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    assert_eq!(connections.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_connect_retries_not_after_connecting() {
+    let (port, connections) = start_server(true);
+
+    let res = attohttpc::post(format!("http://127.0.0.1:{}", port))
+        .text("hello")
+        .connect_retries(2)
+        .send();
+    assert!(res.is_err());
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,256 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const CONTENT: &[u8] = b"0123456789abcdefghij";
+
+type Handler = fn(&[String]) -> Vec<u8>;
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("attohttpc-{}-{}", std::process::id(), name));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn read_request_head(stream: &TcpStream) -> Vec<String> {
+    let mut reader = BufReader::new(stream);
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end().to_string();
+        if line.is_empty() {
+            return lines;
+        }
+        lines.push(line.to_ascii_lowercase());
+    }
+}
+
+/// Start a server which answers each connection with the response produced by the matching handler.
+fn start_server(handlers: Vec<Handler>) -> (u16, JoinHandle<()>) {
+    let listener = TcpListener::bind("localhost:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let thread = thread::spawn(move || {
+        for handler in handlers {
+            let (mut stream, _) = listener.accept().unwrap();
+            let head = read_request_head(&stream);
+            stream.write_all(&handler(&head)).unwrap();
+        }
+    });
+    (port, thread)
+}
+
+fn full_response(head: &[String]) -> Vec<u8> {
+    assert!(!head.iter().any(|l| l.starts_with("range:")));
+    let mut resp = format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nETag: \"v1\"\r\n\r\n",
+        CONTENT.len()
+    )
+    .into_bytes();
+    resp.extend(CONTENT);
+    resp
+}
+
+#[test]
+fn test_download_interrupted_then_resumed() {
+    fn interrupted(head: &[String]) -> Vec<u8> {
+        let mut resp = full_response(head);
+        resp.truncate(resp.len() - 12);
+        resp
+    }
+
+    fn resumed(head: &[String]) -> Vec<u8> {
+        assert!(head.contains(&"range: bytes=8-".to_string()));
+        assert!(head.contains(&"if-range: \"v1\"".to_string()));
+        assert!(!head.iter().any(|l| l.starts_with("accept-encoding:")));
+        let mut resp = b"HTTP/1.1 206 Partial Content\r\n\
+            Content-Range: bytes 8-19/20\r\nContent-Length: 12\r\nETag: \"v1\"\r\n\r\n"
+            .to_vec();
+        resp.extend(&CONTENT[8..]);
+        resp
+    }
+
+    let (port, thread) = start_server(vec![interrupted, resumed]);
+    let path = temp_path("resumed");
+
+    let len = attohttpc::download_resumable(format!("http://localhost:{}", port), &path).unwrap();
+    thread.join().unwrap();
+
+    assert_eq!(len, CONTENT.len() as u64);
+    assert_eq!(fs::read(&path).unwrap(), CONTENT);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_download_encoded_range_rejected() {
+    fn interrupted(head: &[String]) -> Vec<u8> {
+        let mut resp = full_response(head);
+        resp.truncate(resp.len() - 12);
+        resp
+    }
+
+    fn encoded(_: &[String]) -> Vec<u8> {
+        b"HTTP/1.1 206 Partial Content\r\n\
+            Content-Range: bytes 8-19/20\r\nContent-Length: 12\r\nContent-Encoding: gzip\r\nETag: \"v1\"\r\n\r\n\
+            XXXXXXXXXXXX"
+            .to_vec()
+    }
+
+    let (port, thread) = start_server(vec![interrupted, encoded]);
+    let path = temp_path("encoded");
+
+    let err = attohttpc::download_resumable(format!("http://localhost:{}", port), &path).unwrap_err();
+    thread.join().unwrap();
+
+    assert!(matches!(
+        err.kind(),
+        attohttpc::ErrorKind::InvalidResponse(attohttpc::InvalidResponseKind::EncodedRange)
+    ));
+    assert_eq!(fs::read(&path).unwrap(), &CONTENT[..8]);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_download_refused_not_retried() {
+    let port = TcpListener::bind("localhost:0").unwrap().local_addr().unwrap().port();
+    let path = temp_path("refused");
+
+    let start = Instant::now();
+    let err = attohttpc::download_resumable(format!("http://localhost:{}", port), &path).unwrap_err();
+    match err.kind() {
+        attohttpc::ErrorKind::Io(err) => assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused),
+        err => panic!("unexpected error {:?}", err),
+    }
+    // A retry would first wait for 100 milliseconds.
+    assert!(start.elapsed() < Duration::from_millis(100));
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn test_download_restarts_when_range_ignored() {
+    let (port, thread) = start_server(vec![|head| {
+        assert!(head.contains(&"range: bytes=5-".to_string()));
+        let mut resp = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", CONTENT.len()).into_bytes();
+        resp.extend(CONTENT);
+        resp
+    }]);
+    let path = temp_path("ignored");
+    fs::write(&path, b"XXXXX").unwrap();
+
+    let len = attohttpc::download_resumable(format!("http://localhost:{}", port), &path).unwrap();
+    thread.join().unwrap();
+
+    assert_eq!(len, CONTENT.len() as u64);
+    assert_eq!(fs::read(&path).unwrap(), CONTENT);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_download_restarts_when_resource_changed() {
+    fn interrupted(head: &[String]) -> Vec<u8> {
+        let mut resp = full_response(head);
+        resp.truncate(resp.len() - 10);
+        resp
+    }
+
+    fn changed(head: &[String]) -> Vec<u8> {
+        assert!(head.contains(&"range: bytes=10-".to_string()));
+        let mut resp = b"HTTP/1.1 206 Partial Content\r\n\
+            Content-Range: bytes 10-19/20\r\nContent-Length: 10\r\nETag: \"v2\"\r\n\r\n"
+            .to_vec();
+        resp.extend(b"CHANGED!!!");
+        resp
+    }
+
+    let (port, thread) = start_server(vec![interrupted, changed, full_response]);
+    let path = temp_path("changed");
+
+    let len = attohttpc::download_resumable(format!("http://localhost:{}", port), &path).unwrap();
+    thread.join().unwrap();
+
+    assert_eq!(len, CONTENT.len() as u64);
+    assert_eq!(fs::read(&path).unwrap(), CONTENT);
+    fs::remove_file(&path).unwrap();
+}
+
+/// Answer `HEAD` requests with the length of the resource and range requests with the requested part.
+fn range_response(head: &[String]) -> Vec<u8> {
+    if head[0].starts_with("head ") {
+        return format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\nETag: \"v1\"\r\n\r\n",
+            CONTENT.len()
+        )
+        .into_bytes();
+    }
+
+    let range = match head.iter().find_map(|l| l.strip_prefix("range: bytes=")) {
+        Some(range) => range,
+        None => return full_response(head),
+    };
+    assert!(head.contains(&"if-range: \"v1\"".to_string()));
+    let (first, last) = range.split_once('-').unwrap();
+    let (first, last): (usize, usize) = (first.parse().unwrap(), last.parse().unwrap());
+    let mut resp = format!(
+        "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\n\r\n",
+        first,
+        last,
+        CONTENT.len(),
+        last - first + 1
+    )
+    .into_bytes();
+    resp.extend(&CONTENT[first..=last]);
+    resp
+}
+
+#[test]
+fn test_download_parallel() {
+    let (port, thread) = start_server(vec![range_response; 5]);
+    let parallel_path = temp_path("parallel");
+    let single_path = temp_path("parallel-single");
+
+    let len = attohttpc::download_parallel(format!("http://localhost:{}", port), &parallel_path, 3).unwrap();
+    attohttpc::download_resumable(format!("http://localhost:{}", port), &single_path).unwrap();
+    thread.join().unwrap();
+
+    assert_eq!(len, CONTENT.len() as u64);
+    assert_eq!(fs::read(&parallel_path).unwrap(), fs::read(&single_path).unwrap());
+    assert_eq!(fs::read(&parallel_path).unwrap(), CONTENT);
+    fs::remove_file(&parallel_path).unwrap();
+    fs::remove_file(&single_path).unwrap();
+}
+
+#[test]
+fn test_download_parallel_uneven_parts() {
+    // 20 bytes in parts of 4 bytes only make 5 parts.
+    let (port, thread) = start_server(vec![range_response; 6]);
+    let path = temp_path("parallel-uneven");
+
+    let len = attohttpc::download_parallel(format!("http://localhost:{}", port), &path, 6).unwrap();
+    thread.join().unwrap();
+
+    assert_eq!(len, CONTENT.len() as u64);
+    assert_eq!(fs::read(&path).unwrap(), CONTENT);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_download_parallel_without_ranges() {
+    fn head_response(head: &[String]) -> Vec<u8> {
+        assert!(head[0].starts_with("head "));
+        format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", CONTENT.len()).into_bytes()
+    }
+
+    let (port, thread) = start_server(vec![head_response, full_response]);
+    let path = temp_path("parallel-sequential");
+    fs::write(&path, b"previous content which is longer").unwrap();
+
+    let len = attohttpc::download_parallel(format!("http://localhost:{}", port), &path, 3).unwrap();
+    thread.join().unwrap();
+
+    assert_eq!(len, CONTENT.len() as u64);
+    assert_eq!(fs::read(&path).unwrap(), CONTENT);
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,64 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+
+/// Start a server writing `interim` once it read the head of the request and answering `413 Payload Too Large`
+/// after reading the first bytes of the body, then reading and discarding the rest of it. Returns its port and a
+/// receiver of the number of body bytes it read.
+fn start_early_response_server(interim: &'static [u8]) -> (u16, mpsc::Receiver<u64>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+            line.clear();
+        }
+        stream.write_all(interim).unwrap();
+
+        let mut chunk = [0; 1024];
+        reader.read_exact(&mut chunk).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 413 Payload Too Large\r\nContent-Length: 8\r\n\r\ntoo big!")
+            .unwrap();
+
+        let rest = io::copy(&mut reader, &mut io::sink()).unwrap_or(0);
+        tx.send(chunk.len() as u64 + rest).unwrap();
+    });
+    (port, rx)
+}
+
+#[test]
+fn test_early_response_stops_body() {
+    let (port, read) = start_early_response_server(b"");
+    let len = 256 * 1024 * 1024;
+
+    let resp = attohttpc::post(format!("http://127.0.0.1:{}", port))
+        .body_reader_sized(io::repeat(b'x').take(len), len)
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), attohttpc::StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(resp.text().unwrap(), "too big!");
+
+    // The rest of the body is not sent once the response is noticed.
+    assert!(read.recv().unwrap() < len);
+}
+
+#[test]
+fn test_early_response_after_continue() {
+    let (port, read) = start_early_response_server(b"HTTP/1.1 100 Continue\r\n\r\n");
+    let len = 256 * 1024 * 1024;
+
+    // The interim response is still buffered when the final response arrives behind it.
+    let resp = attohttpc::post(format!("http://127.0.0.1:{}", port))
+        .header("Expect", "100-continue")
+        .body_reader_sized(io::repeat(b'x').take(len), len)
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), attohttpc::StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(resp.text().unwrap(), "too big!");
+    assert!(read.recv().unwrap() < len);
+}
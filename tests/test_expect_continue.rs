@@ -0,0 +1,86 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Start a server writing `answer` once it read the head of a request, then reading the body until the client
+/// closes the connection when `answer` is a final response, or reading a body of 5 bytes and answering
+/// `200 OK` otherwise. Returns its port and a receiver of the body bytes it read.
+fn start_server(answer: &'static [u8]) -> (u16, mpsc::Receiver<Vec<u8>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+            line.clear();
+        }
+        stream.write_all(answer).unwrap();
+
+        let mut body = Vec::new();
+        if answer.starts_with(b"HTTP/1.1 1") || answer.is_empty() {
+            body.resize(5, 0);
+            reader.read_exact(&mut body).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+        } else {
+            let _ = reader.read_to_end(&mut body);
+        }
+        tx.send(body).unwrap();
+    });
+    (port, rx)
+}
+
+#[test]
+fn test_expect_continue_ignored() {
+    let (port, body) = start_server(b"");
+
+    let start = Instant::now();
+    let resp = attohttpc::post(format!("http://127.0.0.1:{}", port))
+        .header("Expect", "100-continue")
+        .expect_continue_timeout(Duration::from_millis(200))
+        .text("hello")
+        .send()
+        .unwrap();
+    assert!(resp.is_success());
+    // The body is sent once the timeout expires.
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert_eq!(body.recv().unwrap(), b"hello");
+}
+
+#[test]
+fn test_expect_continue_accepted() {
+    let (port, body) = start_server(b"HTTP/1.1 100 Continue\r\n\r\n");
+
+    let start = Instant::now();
+    let resp = attohttpc::post(format!("http://127.0.0.1:{}", port))
+        .header("Expect", "100-continue")
+        .expect_continue_timeout(Duration::from_secs(10))
+        .text("hello")
+        .send()
+        .unwrap();
+    assert!(resp.is_success());
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(body.recv().unwrap(), b"hello");
+}
+
+#[test]
+fn test_expect_continue_refused() {
+    let (port, body) = start_server(b"HTTP/1.1 417 Expectation Failed\r\nContent-Length: 0\r\n\r\n");
+
+    let resp = attohttpc::post(format!("http://127.0.0.1:{}", port))
+        .header("Expect", "100-continue")
+        .expect_continue_timeout(Duration::from_secs(10))
+        .text("hello")
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), attohttpc::StatusCode::EXPECTATION_FAILED);
+    drop(resp);
+
+    // The body is never sent.
+    assert!(body.recv().unwrap().is_empty());
+}
@@ -0,0 +1,115 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use attohttpc::ErrorKind;
+
+/// Start a server answering every request with `response`, returns its port and a counter of the connections it
+/// accepted.
+fn start_server(response: &'static [u8]) -> (u16, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let counter = connections.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                line.clear();
+            }
+            let _ = stream.write_all(response);
+        }
+    });
+    (port, connections)
+}
+
+fn assert_blocked(res: attohttpc::Result<attohttpc::Response>, host: &str) {
+    match res.unwrap_err().kind() {
+        ErrorKind::HostBlocked(blocked) => assert_eq!(blocked, host),
+        err => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[test]
+fn test_block_private_addresses_loopback() {
+    let (port, connections) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+
+    let res = attohttpc::get(format!("http://127.0.0.1:{}", port))
+        .block_private_addresses()
+        .send();
+    assert_blocked(res, "127.0.0.1");
+    assert_eq!(connections.load(Ordering::SeqCst), 0);
+
+    // The addresses a name resolves to are checked as well.
+    let res = attohttpc::get(format!("http://localhost:{}", port))
+        .block_private_addresses()
+        .send();
+    assert!(matches!(res.unwrap_err().kind(), ErrorKind::HostBlocked(_)));
+    assert_eq!(connections.load(Ordering::SeqCst), 0);
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}", port)).send().unwrap();
+    assert!(resp.is_success());
+}
+
+#[test]
+fn test_block_private_addresses_metadata_service() {
+    let res = attohttpc::get("http://169.254.169.254/latest/meta-data/")
+        .block_private_addresses()
+        .send();
+    assert_blocked(res, "169.254.169.254");
+
+    let mut session = attohttpc::Session::new();
+    session.block_private_addresses();
+    assert_blocked(session.get("http://[fd00::1]/").send(), "fd00::1");
+}
+
+#[test]
+fn test_blocked_host_after_redirection() {
+    let (port, _) = start_server(
+        b"HTTP/1.1 302 Found\r\nLocation: http://169.254.169.254/latest/meta-data/\r\nContent-Length: 0\r\n\r\n",
+    );
+
+    let res = attohttpc::get(format!("http://127.0.0.1:{}", port))
+        .blocked_hosts(&["169.254.*"])
+        .send();
+    assert_blocked(res, "169.254.169.254");
+}
+
+#[test]
+fn test_allowed_hosts() {
+    let (port, _) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}", port))
+        .allowed_hosts(&["127.0.0.1", "*.example.com"])
+        .send()
+        .unwrap();
+    assert!(resp.is_success());
+
+    let res = attohttpc::get(format!("http://localhost:{}", port))
+        .allowed_hosts(&["127.0.0.1", "*.example.com"])
+        .send();
+    assert_blocked(res, "localhost");
+}
+
+#[test]
+fn test_session_blocks_pooled_connection() {
+    let (port, connections) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+    let session = attohttpc::Session::new();
+
+    let resp = session.get(format!("http://127.0.0.1:{}", port)).send().unwrap();
+    assert!(resp.is_success());
+    resp.drain().unwrap();
+
+    // The idle connection to the host is not used by a request which blocks it.
+    let res = session
+        .get(format!("http://127.0.0.1:{}", port))
+        .blocked_hosts(&["127.0.0.1"])
+        .send();
+    assert_blocked(res, "127.0.0.1");
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
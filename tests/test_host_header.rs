@@ -0,0 +1,91 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::thread;
+
+use warp::Filter;
+
+async fn make_server() -> Result<u16, anyhow::Error> {
+    let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+    let incoming = tokio::net::TcpListener::bind(&addr).await?;
+    let local_addr = incoming.local_addr()?;
+
+    let host = warp::header::<String>("host").map(|host: String| host);
+
+    let server = warp::serve(host).serve_incoming(incoming);
+    tokio::spawn(server);
+
+    Ok(local_addr.port())
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn test_host_header_default() -> Result<(), anyhow::Error> {
+    let port = make_server().await?;
+
+    let resp = attohttpc::get(format!("http://localhost:{}", port)).send()?;
+    assert_eq!(resp.text()?, format!("localhost:{}", port));
+
+    Ok(())
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn test_host_header_override() -> Result<(), anyhow::Error> {
+    let port = make_server().await?;
+
+    let resp = attohttpc::get(format!("http://localhost:{}", port))
+        .host_header("vhost.example.com")?
+        .send()?;
+    assert_eq!(resp.text()?, "vhost.example.com");
+
+    Ok(())
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn test_host_header_override_session() -> Result<(), anyhow::Error> {
+    let port = make_server().await?;
+
+    let mut sess = attohttpc::Session::new();
+    sess.host_header("vhost.example.com")?;
+
+    let resp = sess.get(format!("http://localhost:{}", port)).send()?;
+    assert_eq!(resp.text()?, "vhost.example.com");
+
+    Ok(())
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn test_host_header_override_not_sent_to_other_host() -> Result<(), anyhow::Error> {
+    let port = make_server().await?;
+
+    // A server redirecting to the one echoing the `Host` header, which listens on another port.
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let redirect_port = listener.local_addr()?.port();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+            line.clear();
+        }
+        let _ = write!(
+            stream,
+            "HTTP/1.1 302 Found\r\nLocation: http://localhost:{}/\r\nContent-Length: 0\r\n\r\n",
+            port
+        );
+    });
+
+    let resp = attohttpc::get(format!("http://localhost:{}", redirect_port))
+        .host_header("vhost.example.com")?
+        .send()?;
+    assert_eq!(resp.text()?, format!("localhost:{}", port));
+
+    Ok(())
+}
+
+#[test]
+fn test_host_header_invalid() {
+    let res = attohttpc::get("http://localhost").host_header("bad\nhost");
+    assert!(res.is_err());
+
+    let mut sess = attohttpc::Session::new();
+    assert!(sess.host_header("bad\nhost").is_err());
+}
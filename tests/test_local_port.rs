@@ -0,0 +1,77 @@
+#![cfg(feature = "socket2")]
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+
+use attohttpc::ErrorKind;
+
+/// Start a server answering every request with the port the connection came from.
+fn start_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                line.clear();
+            }
+            let peer_port = stream.peer_addr().unwrap().port().to_string();
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                peer_port.len(),
+                peer_port
+            );
+        }
+    });
+    port
+}
+
+/// Find a local port which is currently free.
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+#[test]
+fn test_local_port() {
+    let port = start_server();
+    let local_port = free_port();
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}", port))
+        .local_port(local_port)
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), local_port.to_string());
+}
+
+#[test]
+fn test_local_port_configured_before_bind() {
+    let port = start_server();
+    let local_port = free_port();
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}", port))
+        .local_port(local_port)
+        .configure_socket(|socket| {
+            assert_eq!(socket.local_addr()?.as_socket().map(|addr| addr.port()), Some(0));
+            Ok(())
+        })
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), local_port.to_string());
+}
+
+#[test]
+fn test_local_port_in_use() {
+    let port = start_server();
+    let used = TcpListener::bind("0.0.0.0:0").unwrap();
+    let local_port = used.local_addr().unwrap().port();
+
+    let err = attohttpc::get(format!("http://127.0.0.1:{}", port))
+        .local_port(local_port)
+        .send()
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::LocalPortInUse(p) if *p == local_port));
+}
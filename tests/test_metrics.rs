@@ -0,0 +1,174 @@
+#[cfg(any(feature = "tls", feature = "tls-rustls"))]
+#[allow(dead_code)]
+mod tools;
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread::{self, JoinHandle};
+
+use attohttpc::{Metrics, ResponseReader};
+
+const RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 11\r\nContent-Type: text/plain\r\n\r\nhello world";
+
+/// Start a server which answers a single request and returns the size of the request it received.
+fn start_server() -> (u16, JoinHandle<usize>) {
+    let listener = TcpListener::bind("localhost:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let thread = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(&stream);
+        let mut size = 0;
+        loop {
+            let mut line = String::new();
+            size += reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+        }
+        stream.write_all(RESPONSE).unwrap();
+        size
+    });
+    (port, thread)
+}
+
+fn read_body(reader: &mut ResponseReader) -> String {
+    let mut body = Vec::new();
+    io::copy(reader, &mut body).unwrap();
+    String::from_utf8(body).unwrap()
+}
+
+/// Check that every phase happened, in order.
+fn assert_ordered(metrics: &Metrics, tls: bool) {
+    let dns = metrics.dns().unwrap();
+    let connect = metrics.connect().unwrap();
+    let ttfb = metrics.ttfb().unwrap();
+    let total = metrics.total().unwrap();
+
+    assert!(dns <= connect);
+    match metrics.tls() {
+        Some(handshake) => {
+            assert!(tls);
+            assert!(connect <= handshake);
+            assert!(handshake <= ttfb);
+        }
+        None => {
+            assert!(!tls);
+            assert!(connect <= ttfb);
+        }
+    }
+    assert!(ttfb <= total);
+}
+
+#[test]
+fn test_metrics_byte_counts() -> attohttpc::Result {
+    let (port, server) = start_server();
+
+    let resp = attohttpc::get(format!("http://localhost:{}/some/path", port)).send()?;
+    let (_, _, mut reader) = resp.split();
+    std::io::copy(&mut reader, &mut std::io::sink())?;
+    let request_size = server.join().unwrap();
+
+    let metrics = reader.metrics();
+    assert_eq!(metrics.bytes_sent(), request_size as u64);
+    assert_eq!(metrics.bytes_received(), RESPONSE.len() as u64);
+
+    assert!(metrics.dns().is_some());
+    assert!(metrics.connect().is_some());
+    assert!(metrics.tls().is_none());
+    assert!(metrics.ttfb().is_some());
+    assert!(metrics.total().is_some());
+
+    Ok(())
+}
+
+#[test]
+fn test_metrics_before_body_is_read() -> attohttpc::Result {
+    let (port, server) = start_server();
+
+    let resp = attohttpc::get(format!("http://localhost:{}/", port)).send()?;
+    server.join().unwrap();
+
+    let metrics = resp.metrics();
+    assert!(metrics.bytes_sent() > 0);
+    assert!(metrics.bytes_received() > 0);
+    assert!(metrics.total().is_none());
+
+    assert_eq!(resp.text()?, "hello world");
+
+    Ok(())
+}
+
+#[test]
+fn test_metrics_timings_ordered() -> attohttpc::Result {
+    let (port, server) = start_server();
+
+    let resp = attohttpc::get(format!("http://localhost:{}/", port)).send()?;
+    let (_, _, mut reader) = resp.split();
+    assert_eq!(read_body(&mut reader), "hello world");
+    server.join().unwrap();
+
+    assert_ordered(&reader.metrics(), false);
+
+    Ok(())
+}
+
+#[test]
+fn test_remote_addr() -> attohttpc::Result {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(&stream);
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+        }
+        stream.write_all(RESPONSE).unwrap();
+    });
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}/", addr.port())).send()?;
+    server.join().unwrap();
+
+    assert_eq!(resp.remote_addr(), Some(addr));
+    assert_eq!(resp.text()?, "hello world");
+
+    Ok(())
+}
+
+#[cfg(any(feature = "tls", feature = "tls-rustls"))]
+#[tokio::test(threaded_scheduler)]
+async fn test_metrics_timings_ordered_tls() -> Result<(), anyhow::Error> {
+    let remote_port = tools::start_hello_world_server(true).await?;
+
+    let resp = attohttpc::get(format!("https://localhost:{}", remote_port))
+        .danger_accept_invalid_certs(true)
+        .send()?;
+    let (_, _, mut reader) = resp.split();
+    assert_eq!(read_body(&mut reader), "hello");
+
+    assert_ordered(&reader.metrics(), true);
+
+    Ok(())
+}
+
+#[cfg(any(feature = "tls", feature = "tls-rustls"))]
+#[tokio::test(threaded_scheduler)]
+async fn test_metrics_timings_ordered_tunnel() -> Result<(), anyhow::Error> {
+    use url::Url;
+
+    let remote_port = tools::start_hello_world_server(true).await?;
+    let proxy_port = tools::start_proxy_server(false).await?;
+    let proxy_url = Url::parse(&format!("http://localhost:{}", proxy_port)).unwrap();
+
+    let resp = attohttpc::get(format!("https://localhost:{}", remote_port))
+        .proxy_settings(attohttpc::ProxySettingsBuilder::new().https_proxy(proxy_url).build())
+        .danger_accept_invalid_certs(true)
+        .send()?;
+    let (_, _, mut reader) = resp.split();
+    assert_eq!(read_body(&mut reader), "hello");
+
+    assert_ordered(&reader.metrics(), true);
+
+    Ok(())
+}
@@ -0,0 +1,37 @@
+#![cfg(not(any(feature = "tls", feature = "tls-rustls", feature = "json")))]
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+
+use attohttpc::ErrorKind;
+
+#[test]
+fn test_plain_http_without_tls_and_json() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let mut stream = listener.incoming().next().unwrap().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+            line.clear();
+        }
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+    });
+
+    let resp = attohttpc::get(format!("http://127.0.0.1:{}", port)).send().unwrap();
+    assert!(resp.is_success());
+    assert_eq!(resp.text().unwrap(), "hello");
+}
+
+#[test]
+fn test_https_without_tls() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let err = attohttpc::get(format!("https://127.0.0.1:{}", port))
+        .send()
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::TlsDisabled));
+}
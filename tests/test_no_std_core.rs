@@ -0,0 +1,61 @@
+#![cfg(feature = "no-std-core")]
+
+use attohttpc::proto::{self, Request, Transport};
+
+/// Transport of a constrained target, which can only hold a fixed number of bytes.
+struct FixedBuffer {
+    buf: [u8; 128],
+    len: usize,
+}
+
+#[derive(Debug, PartialEq)]
+struct Full;
+
+impl Transport for FixedBuffer {
+    type Error = Full;
+
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Full> {
+        let end = self.len + buf.len();
+        if end > self.buf.len() {
+            return Err(Full);
+        }
+        self.buf[self.len..end].copy_from_slice(buf);
+        self.len = end;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_no_std_core_request() {
+    let request = Request::new("PUT", "http://[::1]:8080/items/1?v=2#top")
+        .unwrap()
+        .header("Accept", "*/*")
+        .unwrap()
+        .body("{}");
+
+    let mut transport = FixedBuffer { buf: [0; 128], len: 0 };
+    request.write(&mut transport).unwrap();
+    assert_eq!(
+        &transport.buf[..transport.len],
+        &b"PUT /items/1?v=2 HTTP/1.1\r\nhost: [::1]:8080\r\naccept: */*\r\ncontent-length: 2\r\n\r\n{}"[..]
+    );
+
+    // Errors of the transport are returned as they are.
+    let mut transport = FixedBuffer {
+        buf: [0; 128],
+        len: 100,
+    };
+    assert_eq!(request.write(&mut transport), Err(Full));
+}
+
+#[test]
+fn test_no_std_core_chunked_body() {
+    let mut bytes = Vec::new();
+    proto::write_head(&mut bytes, "POST", "/", vec![("transfer-encoding", &b"chunked"[..])]).unwrap();
+    proto::write_chunk(&mut bytes, b"hello").unwrap();
+    proto::write_last_chunk(&mut bytes).unwrap();
+    assert_eq!(
+        bytes,
+        &b"POST / HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n"[..]
+    );
+}
@@ -1,6 +1,6 @@
 use std::net::SocketAddr;
 
-use attohttpc::ErrorKind;
+use attohttpc::{ErrorKind, InvalidResponseKind};
 use warp::Filter;
 
 async fn make_server() -> Result<u16, anyhow::Error> {
@@ -15,8 +15,53 @@ async fn make_server() -> Result<u16, anyhow::Error> {
             .status(http::StatusCode::NOT_MODIFIED)
             .body("")
     });
+    let c = warp::path("hop1").map(|| warp::redirect::redirect(http::Uri::from_static("/hop2")));
+    let d = warp::path("hop2").map(|| warp::redirect::temporary(http::Uri::from_static("/hop3")));
+    let e = warp::path("hop3").map(|| "done");
+    let f = warp::path("encoded").map(|| warp::redirect::redirect(http::Uri::from_static("/echo/a%20b")));
+    let g = warp::path("echo")
+        .and(warp::path::full())
+        .map(|path: warp::path::FullPath| path.as_str().to_string());
+
+    let h = warp::path("file").map(|| {
+        http::Response::builder()
+            .header("Location", "file:///etc/passwd")
+            .status(http::StatusCode::FOUND)
+            .body("")
+    });
+
+    let i = warp::path("meta")
+        .map(|| warp::reply::html("<html><head><meta http-equiv=\"refresh\" content=\"0; url=/hop3\"></head></html>"));
+    let j =
+        warp::path("meta-loop").map(|| warp::reply::html("<meta http-equiv=\"refresh\" content=\"0;url=/meta-loop\">"));
 
-    let server = warp::serve(a.or(b)).serve_incoming(incoming);
+    let k = warp::path("auth")
+        .and(warp::header::optional::<String>("authorization"))
+        .map(|auth: Option<String>| auth.unwrap_or_default());
+    let port = local_addr.port();
+    let l = warp::path("other-host").map(move || {
+        http::Response::builder()
+            .header("Location", format!("http://127.0.0.1:{}/auth", port))
+            .status(http::StatusCode::FOUND)
+            .body("")
+    });
+    let m = warp::path("same-host").map(|| warp::redirect::temporary(http::Uri::from_static("/auth")));
+
+    let server = warp::serve(
+        a.or(b)
+            .or(c)
+            .or(d)
+            .or(e)
+            .or(f)
+            .or(g)
+            .or(h)
+            .or(i)
+            .or(j)
+            .or(k)
+            .or(l)
+            .or(m),
+    )
+    .serve_incoming(incoming);
     tokio::spawn(server);
 
     Ok(local_addr.port())
@@ -69,6 +114,46 @@ async fn test_redirection_disallowed() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+#[tokio::test(threaded_scheduler)]
+async fn test_redirection_streamed_body() -> Result<(), anyhow::Error> {
+    let port = make_server().await?;
+
+    // The body cannot be sent again, the redirection is returned instead of being followed.
+    let (tx, rx) = std::sync::mpsc::channel();
+    tx.send(b"hello".to_vec()).unwrap();
+    drop(tx);
+    let resp = attohttpc::post(format!("http://localhost:{}/hop2", port))
+        .body_channel(rx)
+        .send()
+        .unwrap();
+    assert_eq!(resp.status(), http::StatusCode::TEMPORARY_REDIRECT);
+    assert!(resp.redirect_history().is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn test_redirection_credentials() -> Result<(), anyhow::Error> {
+    let port = make_server().await?;
+
+    let resp = attohttpc::get(format!("http://localhost:{}/same-host", port))
+        .header("Authorization", "secret")
+        .send()?;
+    assert_eq!(resp.text()?, "secret");
+
+    // Credentials are not sent to another host.
+    let mut req = attohttpc::get(format!("http://localhost:{}/other-host", port))
+        .header("Authorization", "secret")
+        .prepare();
+    let resp = req.send()?;
+    assert_eq!(resp.url().as_str(), format!("http://127.0.0.1:{}/auth", port));
+    assert_eq!(resp.text()?, "");
+    // They are kept to send the request again.
+    assert_eq!(req.headers()["authorization"], "secret");
+
+    Ok(())
+}
+
 #[tokio::test(threaded_scheduler)]
 async fn test_redirection_not_redirect() -> Result<(), anyhow::Error> {
     let port = make_server().await?;
@@ -80,3 +165,85 @@ async fn test_redirection_not_redirect() -> Result<(), anyhow::Error> {
 
     Ok(())
 }
+
+#[tokio::test(threaded_scheduler)]
+async fn test_redirection_history() -> Result<(), anyhow::Error> {
+    let port = make_server().await?;
+
+    let resp = attohttpc::get(format!("http://localhost:{}/hop1", port)).send()?;
+    let history = resp.redirect_history();
+
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].url().path(), "/hop1");
+    assert_eq!(history[0].status(), http::StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(history[1].url().path(), "/hop2");
+    assert_eq!(history[1].status(), http::StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(resp.text()?, "done");
+
+    Ok(())
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn test_redirection_history_empty() -> Result<(), anyhow::Error> {
+    let port = make_server().await?;
+
+    let resp = attohttpc::get(format!("http://localhost:{}/hop3", port)).send()?;
+    assert!(resp.redirect_history().is_empty());
+
+    Ok(())
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn test_redirection_keeps_percent_escapes() -> Result<(), anyhow::Error> {
+    let port = make_server().await?;
+
+    let resp = attohttpc::get(format!("http://localhost:{}/encoded", port)).send()?;
+    assert_eq!(resp.text()?, "/echo/a%20b");
+
+    Ok(())
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn test_redirection_to_file_scheme() -> Result<(), anyhow::Error> {
+    let port = make_server().await?;
+
+    let err = attohttpc::get(format!("http://localhost:{}/file", port))
+        .send()
+        .unwrap_err();
+    assert!(matches!(
+        err.kind(),
+        ErrorKind::InvalidResponse(InvalidResponseKind::RedirectionUrl)
+    ));
+
+    // Without following redirections, the response is returned as is.
+    let resp = attohttpc::get(format!("http://localhost:{}/file", port))
+        .follow_redirects(false)
+        .send()?;
+    assert_eq!(resp.headers()["location"], "file:///etc/passwd");
+
+    Ok(())
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn test_redirection_meta_refresh() -> Result<(), anyhow::Error> {
+    let port = make_server().await?;
+
+    let resp = attohttpc::get(format!("http://localhost:{}/meta", port))
+        .follow_meta_refresh()
+        .send()?;
+    assert_eq!(resp.redirect_history().len(), 1);
+    assert_eq!(resp.redirect_history()[0].url().path(), "/meta");
+    assert_eq!(resp.text()?, "done");
+
+    // Meta refreshes are only followed when asked to, the page is returned as is otherwise.
+    let resp = attohttpc::get(format!("http://localhost:{}/meta", port)).send()?;
+    assert!(resp.text()?.contains("http-equiv"));
+
+    let err = attohttpc::get(format!("http://localhost:{}/meta-loop", port))
+        .follow_meta_refresh()
+        .send()
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::TooManyRedirections));
+
+    Ok(())
+}
@@ -0,0 +1,692 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use attohttpc::Session;
+
+/// Start a keep-alive server answering every request with `response`, returns its port and a counter of the
+/// connections it accepted.
+fn start_server(response: &'static [u8]) -> (u16, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("localhost:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let counter = connections.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                        return;
+                    }
+                    if line == "\r\n" && stream.write_all(response).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    (port, connections)
+}
+
+/// Start a server which closes each connection after answering a single request, without announcing it.
+fn start_closing_server() -> (u16, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("localhost:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let counter = connections.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(value) = line.strip_prefix("content-length: ") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            reader.read_exact(&mut vec![0; length]).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                .unwrap();
+        }
+    });
+    (port, connections)
+}
+
+/// Start a server which answers the first request of each connection and closes it when the next request arrives.
+///
+/// Unlike a connection closed while it is idle, the client cannot notice it before sending the request.
+fn start_closing_on_request_server() -> (u16, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("localhost:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let counter = connections.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(value) = line.strip_prefix("content-length: ") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            reader.read_exact(&mut vec![0; length]).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                .unwrap();
+            let _ = reader.read_line(&mut String::new());
+        }
+    });
+    (port, connections)
+}
+
+/// Start a server which sets a cookie and answers with the `Cookie` header it received.
+fn start_cookie_server() -> u16 {
+    let listener = TcpListener::bind("localhost:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut cookie = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(value) = line.strip_prefix("cookie: ") {
+                    cookie = value.trim().to_string();
+                }
+            }
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nSet-Cookie: id=42\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+                cookie.len(),
+                cookie
+            );
+        }
+    });
+    port
+}
+
+/// Start a server answering each request with its request line, headers and body.
+fn start_echo_server() -> u16 {
+    let listener = TcpListener::bind("localhost:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut echo = String::new();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(value) = line.strip_prefix("content-length: ") {
+                    length = value.trim().parse().unwrap();
+                }
+                echo.push_str(&line);
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            echo.push_str(std::str::from_utf8(&body).unwrap());
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+                echo.len(),
+                echo
+            );
+        }
+    });
+    port
+}
+
+/// Start a server answering `/items?page=N` with the page number as body, linking to the next page up to the
+/// third one.
+fn start_pages_server() -> u16 {
+    let listener = TcpListener::bind("localhost:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                line.clear();
+            }
+            let page: u32 = request_line
+                .split(' ')
+                .nth(1)
+                .and_then(|target| target.strip_prefix("/items?page="))
+                .unwrap()
+                .parse()
+                .unwrap();
+            let link = match page {
+                1 => format!("Link: <http://localhost:{}/items?page=2>; rel=\"next\"\r\n", port),
+                2 => "Link: </items?page=1>; rel=prev, </items?page=3>; rel=next\r\n".to_string(),
+                _ => String::new(),
+            };
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\n{}Connection: close\r\nContent-Length: 1\r\n\r\n{}",
+                link, page
+            );
+        }
+    });
+    port
+}
+
+#[test]
+fn test_session_reuses_connection() {
+    let (port, connections) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+    let sess = Session::new();
+
+    for _ in 0..3 {
+        let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+        assert_eq!(resp.text().unwrap(), "hello");
+    }
+
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+#[cfg(feature = "socket2")]
+fn test_session_separates_connection_settings() {
+    let (port, connections) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+    let sess = Session::new();
+    let called = Arc::new(AtomicUsize::new(0));
+
+    let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+    assert_eq!(resp.text().unwrap(), "hello");
+
+    // A connection configured differently is not reused by requests which do not configure it.
+    let counter = called.clone();
+    let resp = sess
+        .get(format!("http://localhost:{}", port))
+        .configure_socket(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        })
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "hello");
+    assert_eq!(called.load(Ordering::SeqCst), 1);
+
+    let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+    assert_eq!(resp.text().unwrap(), "hello");
+
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_session_reuses_chunked_connection() {
+    let (port, connections) =
+        start_server(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n");
+    let sess = Session::new();
+
+    for _ in 0..3 {
+        let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+        assert_eq!(resp.text().unwrap(), "hello");
+    }
+
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_session_http10_connection() {
+    let (port, connections) = start_server(b"HTTP/1.0 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+    let sess = Session::new();
+
+    for _ in 0..2 {
+        let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+        assert_eq!(resp.version(), attohttpc::Version::HTTP_10);
+        assert_eq!(resp.text().unwrap(), "hello");
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+    let (port, connections) =
+        start_server(b"HTTP/1.0 200 OK\r\nConnection: Keep-Alive\r\nContent-Length: 5\r\n\r\nhello");
+    for _ in 0..2 {
+        let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+        assert_eq!(resp.text().unwrap(), "hello");
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_session_connection_close() {
+    let (port, connections) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+    let sess = Session::new();
+
+    for _ in 0..2 {
+        let resp = sess
+            .get(format!("http://localhost:{}", port))
+            .header("Connection", "close")
+            .send()
+            .unwrap();
+        assert_eq!(resp.text().unwrap(), "hello");
+    }
+
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_session_shared_across_threads() {
+    let (port, connections) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+    let sess = Session::new();
+
+    let threads: Vec<_> = (0..2)
+        .map(|_| {
+            let sess = sess.clone();
+            thread::spawn(move || {
+                for _ in 0..5 {
+                    let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+                    assert_eq!(resp.text().unwrap(), "hello");
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // Each thread needs at most one connection at a time, every other request reuses one of them.
+    assert!(connections.load(Ordering::SeqCst) <= 2);
+
+    let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+    assert_eq!(resp.text().unwrap(), "hello");
+    assert!(connections.load(Ordering::SeqCst) <= 2);
+}
+
+#[test]
+fn test_session_hammer() {
+    let (port, connections) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+    let sess = Session::new();
+
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let sess = sess.clone();
+            thread::spawn(move || {
+                for _ in 0..25 {
+                    let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+                    assert_eq!(resp.text().unwrap(), "hello");
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    assert!(connections.load(Ordering::SeqCst) <= 8);
+}
+
+#[test]
+fn test_session_cookies_disabled_by_default() {
+    let port = start_cookie_server();
+    let sess = Session::new();
+
+    for _ in 0..2 {
+        let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+        assert_eq!(resp.text().unwrap(), "");
+    }
+}
+
+#[test]
+fn test_session_shares_cookies() {
+    let port = start_cookie_server();
+    let mut sess = Session::new();
+    sess.cookie_store(true);
+
+    let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+    assert_eq!(resp.text().unwrap(), "");
+
+    let clone = sess.clone();
+    let body = thread::spawn(move || {
+        let resp = clone.get(format!("http://localhost:{}", port)).send().unwrap();
+        resp.text().unwrap()
+    })
+    .join()
+    .unwrap();
+    assert_eq!(body, "id=42");
+
+    // A cookie set by the user replaces the ones of the jar.
+    let resp = sess
+        .get(format!("http://localhost:{}", port))
+        .header("Cookie", "user=1")
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "user=1");
+}
+
+#[test]
+fn test_session_drain_reuses_connection() {
+    let (port, connections) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+    let sess = Session::new();
+
+    let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+    resp.drain().unwrap();
+    let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+    assert_eq!(resp.text().unwrap(), "hello");
+
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_session_drain_large_body_closes_connection() {
+    let len = 1024 * 1024;
+    let mut response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", len).into_bytes();
+    response.resize(response.len() + len, b'a');
+    let (port, connections) = start_server(Box::leak(response.into_boxed_slice()));
+    let sess = Session::new();
+
+    let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+    resp.drain().unwrap();
+    let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+    assert_eq!(resp.bytes().unwrap().len(), len);
+
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_session_drop_partially_read_response() {
+    let (port, connections) =
+        start_server(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n");
+    let sess = Session::new();
+
+    let mut resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+    let mut byte = [0; 1];
+    resp.read_exact(&mut byte).unwrap();
+    assert_eq!(&byte, b"h");
+    drop(resp);
+
+    let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+    assert_eq!(resp.text().unwrap(), "hello world");
+
+    // The small remainder was read on drop, making the connection reusable.
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_session_drop_large_remainder_closes_connection() {
+    let len = 1024 * 1024;
+    let mut response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", len).into_bytes();
+    response.resize(response.len() + len, b'a');
+    let (port, connections) = start_server(Box::leak(response.into_boxed_slice()));
+    let sess = Session::new();
+
+    let mut resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+    let mut byte = [0; 1];
+    resp.read_exact(&mut byte).unwrap();
+    drop(resp);
+
+    let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+    assert_eq!(resp.bytes().unwrap().len(), len);
+
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_session_rate_limit() {
+    let (port, _) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+    let mut sess = Session::new();
+    sess.rate_limit(Duration::from_millis(100));
+
+    let start = Instant::now();
+    for _ in 0..3 {
+        let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+        assert_eq!(resp.text().unwrap(), "hello");
+    }
+
+    // The first request is sent right away, the two others wait for their turn.
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+#[test]
+fn test_session_execute() {
+    let port = start_echo_server();
+    let mut sess = Session::new();
+    sess.header("X-Session", "session");
+    sess.header("X-Replaced", "session");
+
+    let request = http::Request::builder()
+        .method("PUT")
+        .uri(format!("http://localhost:{}/items/1?full=true", port))
+        .header("X-Replaced", "first")
+        .header("X-Replaced", "second")
+        .header("Host", "example.com")
+        .body(b"payload".to_vec())
+        .unwrap();
+    let echo = sess.execute(request).unwrap().text().unwrap();
+
+    assert!(echo.starts_with("PUT /items/1?full=true HTTP/1.1\r\n"));
+    assert!(echo.contains("\r\nhost: example.com\r\n"));
+    assert!(echo.contains("\r\nx-session: session\r\n"));
+    assert!(echo.contains("\r\nx-replaced: first\r\nx-replaced: second\r\n"));
+    assert!(!echo.contains("x-replaced: session"));
+    assert!(echo.contains("\r\ncontent-length: 7\r\n"));
+    assert!(echo.ends_with("\r\npayload"));
+}
+
+#[test]
+fn test_session_execute_into_http() {
+    let port = start_echo_server();
+    let sess = Session::new();
+
+    let request = http::Request::get(format!("http://localhost:{}/", port))
+        .body(Vec::new())
+        .unwrap();
+    let response = sess.execute(request).unwrap().into_http().unwrap();
+
+    assert_eq!(response.status(), http::StatusCode::OK);
+    assert_eq!(response.headers()["connection"], "close");
+    assert!(response.body().starts_with(b"GET / HTTP/1.1\r\n"));
+}
+
+#[test]
+fn test_session_discards_closed_idle_connection() {
+    let (port, connections) = start_closing_server();
+    let sess = Session::new();
+
+    let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+    assert_eq!(resp.text().unwrap(), "ok");
+    // Let the server close the pooled connection.
+    thread::sleep(Duration::from_millis(50));
+
+    // The closed connection is noticed before sending the request, even a streamed body can be sent.
+    let resp = sess
+        .post(format!("http://localhost:{}", port))
+        .body_reader_sized(&b"hello"[..], 5)
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "ok");
+
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_session_retries_closed_idle_connection() {
+    let (port, connections) = start_closing_on_request_server();
+    let sess = Session::new();
+
+    let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+    assert_eq!(resp.text().unwrap(), "ok");
+
+    let resp = sess
+        .post(format!("http://localhost:{}", port))
+        .text("hello")
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "ok");
+
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_session_does_not_retry_streamed_body() {
+    let (port, connections) = start_closing_on_request_server();
+    let sess = Session::new();
+
+    let resp = sess.get(format!("http://localhost:{}", port)).send().unwrap();
+    assert_eq!(resp.text().unwrap(), "ok");
+
+    // The body was consumed by the first attempt, it cannot be sent again.
+    let result = sess
+        .post(format!("http://localhost:{}", port))
+        .body_reader_sized(&b"hello"[..], 5)
+        .send();
+    assert!(result.is_err());
+
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+fn url(sess: &Session, path: &str) -> String {
+    sess.get(path).inspect().url().to_string()
+}
+
+#[test]
+fn test_session_base_url() {
+    let mut sess = Session::new();
+    sess.base_url("https://api.example.com/");
+
+    assert_eq!(url(&sess, "/v1/users"), "https://api.example.com/v1/users");
+    assert_eq!(url(&sess, "v1/users?page=2"), "https://api.example.com/v1/users?page=2");
+    assert_eq!(url(&sess, "http://other.example.com/x"), "http://other.example.com/x");
+
+    sess.base_url("https://api.example.com/v1/?key=abc");
+    assert_eq!(url(&sess, "users"), "https://api.example.com/v1/users");
+    // Absolute paths replace the path of the base URL.
+    assert_eq!(url(&sess, "/v2/users"), "https://api.example.com/v2/users");
+    assert_eq!(url(&sess, ""), "https://api.example.com/v1/?key=abc");
+
+    assert!(sess.try_base_url("not a url").is_err());
+}
+
+#[test]
+fn test_session_base_url_request() {
+    let (port, _) = start_server(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+    let mut sess = Session::new();
+    sess.base_url(format!("http://localhost:{}/api/", port));
+
+    let resp = sess.get("items").send().unwrap();
+    assert_eq!(resp.text().unwrap(), "ok");
+}
+
+#[test]
+fn test_session_paginate() {
+    let port = start_pages_server();
+    let sess = Session::new();
+
+    let pages: Vec<String> = sess
+        .paginate(format!("http://localhost:{}/items?page=1", port))
+        .map(|resp| resp.unwrap().text().unwrap())
+        .collect();
+    assert_eq!(pages, ["1", "2", "3"]);
+
+    // Taking fewer pages stops the pagination early.
+    let mut pages = sess.paginate(format!("http://localhost:{}/items?page=2", port));
+    assert_eq!(pages.next().unwrap().unwrap().text().unwrap(), "2");
+    drop(pages);
+
+    let mut pages = sess.paginate("not a url");
+    assert!(pages.next().unwrap().is_err());
+    assert!(pages.next().is_none());
+}
+
+/// Start a server serving pages which answer with their path and the `Authorization` header they received.
+///
+/// `/start` redirects to `/a/1`, which links to `2` relatively to its own URL. `/a/2` links to `/a/3` through
+/// `localhost` while the server is reached through `127.0.0.1`, and `/a/3` links to itself.
+fn start_linked_pages_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut auth = "-".to_string();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                if let Some(value) = line.strip_prefix("authorization: ") {
+                    auth = value.trim().to_string();
+                }
+                line.clear();
+            }
+            let path = request_line.split(' ').nth(1).unwrap().to_string();
+            let head = match path.as_str() {
+                "/start" => "HTTP/1.1 302 Found\r\nLocation: /a/1\r\n".to_string(),
+                "/a/1" => "HTTP/1.1 200 OK\r\nLink: <2>; rel=next\r\n".to_string(),
+                "/a/2" => format!("HTTP/1.1 200 OK\r\nLink: <http://localhost:{}/a/3>; rel=next\r\n", port),
+                _ => "HTTP/1.1 200 OK\r\nLink: </a/3>; rel=next\r\n".to_string(),
+            };
+            let body = format!("{} {}", path, auth);
+            let _ = write!(
+                stream,
+                "{}Connection: close\r\nContent-Length: {}\r\n\r\n{}",
+                head,
+                body.len(),
+                body
+            );
+        }
+    });
+    port
+}
+
+#[test]
+fn test_session_paginate_links() {
+    let port = start_linked_pages_server();
+    let mut sess = Session::new();
+    sess.header("Authorization", "secret");
+
+    let pages: Vec<String> = sess
+        .paginate(format!("http://127.0.0.1:{}/start", port))
+        .map(|resp| resp.unwrap().text().unwrap())
+        .collect();
+    // The pagination stops at the link of the last page to itself.
+    assert_eq!(pages, ["/a/1 secret", "/a/2 secret", "/a/3 -"]);
+}
+
+#[test]
+fn test_session_send_raw() {
+    let port = start_echo_server();
+    let mut sess = Session::new();
+    sess.header("X-Session", "ignored");
+
+    let raw = b"GET /raw?x=1 HTTP/1.1\r\nHost: example.com\r\nX-Odd:  spaced \r\n\r\n";
+    let resp = sess.send_raw(format!("http://localhost:{}/unused", port), raw).unwrap();
+    assert!(resp.is_success());
+    assert_eq!(
+        resp.text().unwrap(),
+        "GET /raw?x=1 HTTP/1.1\r\nHost: example.com\r\nX-Odd:  spaced \r\n"
+    );
+
+    assert!(sess.send_raw("not a url", raw).is_err());
+}
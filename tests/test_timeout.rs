@@ -1,8 +1,37 @@
-use std::io;
+use std::io::{self, BufRead, BufReader, Write};
 use std::net::TcpListener;
-use std::thread;
+use std::thread::{self, JoinHandle};
 use std::time::Duration;
 
+fn start_slow_server(delay: Duration) -> (u16, JoinHandle<()>) {
+    let listener = TcpListener::bind("localhost:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let thread = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+            line.clear();
+        }
+        thread::sleep(delay);
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+    });
+    (port, thread)
+}
+
+fn assert_timed_out(result: attohttpc::Result<attohttpc::Response>) {
+    match result {
+        Err(err) => match err.kind() {
+            attohttpc::ErrorKind::Io(err) => match err.kind() {
+                io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => (),
+                err => panic!("Unexpected I/O error: {:?}", err),
+            },
+            err => panic!("Unexpected error: {:?}", err),
+        },
+        Ok(resp) => panic!("Unexpected response: {:?}", resp),
+    }
+}
+
 #[test]
 fn request_fails_due_to_read_timeout() {
     let listener = TcpListener::bind("localhost:0").unwrap();
@@ -56,3 +85,106 @@ fn request_fails_due_to_timeout() {
 
     thread.join().unwrap();
 }
+
+#[test]
+fn request_timeout_inherited_from_session() {
+    let (port, thread) = start_slow_server(Duration::from_millis(300));
+
+    let mut sess = attohttpc::Session::new();
+    sess.timeout(Duration::from_millis(100));
+
+    assert_timed_out(sess.get(format!("http://localhost:{}", port)).send());
+
+    thread.join().unwrap();
+}
+
+#[test]
+fn request_timeout_overrides_session() {
+    let (port, thread) = start_slow_server(Duration::from_millis(300));
+
+    let mut sess = attohttpc::Session::new();
+    sess.timeout(Duration::from_millis(100));
+    sess.read_timeout(Duration::from_millis(100));
+
+    let resp = sess
+        .get(format!("http://localhost:{}", port))
+        .timeout(Duration::from_secs(5))
+        .read_timeout(Duration::from_secs(5))
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "ok");
+
+    thread.join().unwrap();
+}
+
+#[test]
+fn request_no_timeout_clears_session_timeouts() {
+    let (port, thread) = start_slow_server(Duration::from_millis(300));
+
+    let mut sess = attohttpc::Session::new();
+    sess.timeout(Duration::from_millis(100));
+    sess.read_timeout(Duration::from_millis(100));
+
+    let resp = sess
+        .get(format!("http://localhost:{}", port))
+        .no_timeout()
+        .send()
+        .unwrap();
+    assert_eq!(resp.text().unwrap(), "ok");
+
+    thread.join().unwrap();
+}
+
+/// Start a server answering with a body of `len` bytes sent one byte at a time every `delay`.
+fn start_dribbling_server(len: usize, delay: Duration) -> u16 {
+    let listener = TcpListener::bind("localhost:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+            line.clear();
+        }
+        let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", len);
+        for _ in 0..len {
+            if stream.write_all(b"x").is_err() {
+                break;
+            }
+            thread::sleep(delay);
+        }
+    });
+    port
+}
+
+#[test]
+fn read_exact_timeout_fails_on_slow_body() {
+    let port = start_dribbling_server(20, Duration::from_millis(50));
+    let mut resp = attohttpc::get(format!("http://localhost:{}", port)).send().unwrap();
+
+    let mut buf = [0; 2];
+    resp.read_exact_timeout(&mut buf, Duration::from_secs(5)).unwrap();
+    assert_eq!(&buf, b"xx");
+
+    let mut buf = [0; 10];
+    let err = resp
+        .read_exact_timeout(&mut buf, Duration::from_millis(150))
+        .unwrap_err();
+    match err.kind() {
+        attohttpc::ErrorKind::Io(err) => assert_eq!(err.kind(), io::ErrorKind::TimedOut),
+        err => panic!("Unexpected error: {:?}", err),
+    }
+}
+
+#[test]
+fn read_exact_timeout_fails_on_short_body() {
+    let port = start_dribbling_server(2, Duration::from_millis(0));
+    let mut resp = attohttpc::get(format!("http://localhost:{}", port)).send().unwrap();
+
+    let mut buf = [0; 3];
+    let err = resp.read_exact_timeout(&mut buf, Duration::from_secs(5)).unwrap_err();
+    match err.kind() {
+        attohttpc::ErrorKind::Io(err) => assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof),
+        err => panic!("Unexpected error: {:?}", err),
+    }
+}
@@ -0,0 +1,172 @@
+#![cfg(any(feature = "tls", feature = "tls-rustls"))]
+
+#[allow(dead_code, unused_imports)]
+mod tools;
+
+#[tokio::test(threaded_scheduler)]
+async fn test_ip_host_with_server_name() -> Result<(), anyhow::Error> {
+    let port = tools::start_hello_world_server(true).await?;
+
+    let resp = attohttpc::get(format!("https://127.0.0.1:{}", port))
+        .tls_server_name("localhost")
+        .danger_accept_invalid_certs(true)
+        .send()?;
+
+    assert_eq!(resp.text()?, "hello");
+
+    Ok(())
+}
+
+#[cfg(feature = "tls")]
+#[tokio::test(threaded_scheduler)]
+async fn test_ip_host_without_sni() -> Result<(), anyhow::Error> {
+    let port = tools::start_hello_world_server(true).await?;
+
+    let resp = attohttpc::get(format!("https://127.0.0.1:{}", port))
+        .danger_accept_invalid_certs(true)
+        .send()?;
+
+    assert_eq!(resp.text()?, "hello");
+
+    Ok(())
+}
+
+#[cfg(feature = "tls")]
+#[tokio::test(threaded_scheduler)]
+async fn test_custom_tls_connector() -> Result<(), anyhow::Error> {
+    let port = tools::start_hello_world_server(true).await?;
+    let url = format!("https://localhost:{}", port);
+
+    // The self-signed certificate is rejected by default.
+    assert!(attohttpc::get(&url).send().is_err());
+
+    let connector = native_tls::TlsConnector::builder()
+        .danger_accept_invalid_certs(true)
+        .build()?;
+    let resp = attohttpc::get(&url).tls_connector(connector.clone()).send()?;
+    assert_eq!(resp.text()?, "hello");
+
+    let mut session = attohttpc::Session::new();
+    session.tls_connector(connector);
+    assert_eq!(session.get(&url).send()?.text()?, "hello");
+
+    Ok(())
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn test_min_tls_version_rejects_older_server() -> Result<(), anyhow::Error> {
+    use tokio_rustls::rustls::ProtocolVersion;
+
+    let port = tools::start_hello_world_server_with_versions(&[ProtocolVersion::TLSv1_2]).await?;
+    let url = format!("https://localhost:{}", port);
+
+    let resp = attohttpc::get(&url)
+        .danger_accept_invalid_certs(true)
+        .min_tls_version(attohttpc::TlsVersion::Tls1_2)
+        .send()?;
+    assert_eq!(resp.text()?, "hello");
+
+    let res = attohttpc::get(&url)
+        .danger_accept_invalid_certs(true)
+        .min_tls_version(attohttpc::TlsVersion::Tls1_3)
+        .send();
+    assert!(res.is_err());
+
+    Ok(())
+}
+
+/// Get the versions listed by the `supported_versions` extension of the first `ClientHello` sent to a server.
+#[cfg(feature = "tls")]
+fn client_hello_versions(configure: impl FnOnce(attohttpc::RequestBuilder) -> attohttpc::RequestBuilder) -> Vec<u16> {
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut header = [0; 5];
+        stream.read_exact(&mut header).unwrap();
+        let mut record = vec![0; usize::from(u16::from_be_bytes([header[3], header[4]]))];
+        stream.read_exact(&mut record).unwrap();
+        record
+    });
+    let _ = configure(attohttpc::get(format!("https://127.0.0.1:{}", port))).send();
+    let hello = server.join().unwrap();
+
+    let u16_at = |pos: usize| usize::from(u16::from_be_bytes([hello[pos], hello[pos + 1]]));
+    // Skip the handshake header, the version, the random, the session id, the cipher suites and the compression
+    // methods.
+    let mut pos = 4 + 2 + 32;
+    pos += 1 + usize::from(hello[pos]);
+    pos += 2 + u16_at(pos);
+    pos += 1 + usize::from(hello[pos]);
+    let end = pos + 2 + u16_at(pos);
+    pos += 2;
+    while pos < end {
+        let (kind, len) = (u16_at(pos), u16_at(pos + 2));
+        if kind == 0x2b {
+            let list = &hello[pos + 5..pos + 5 + usize::from(hello[pos + 4])];
+            return list.chunks(2).map(|v| u16::from_be_bytes([v[0], v[1]])).collect();
+        }
+        pos += 4 + len;
+    }
+    panic!("no supported_versions extension");
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn test_default_tls_versions() {
+    const TLS_1_0: u16 = 0x0301;
+    const TLS_1_1: u16 = 0x0302;
+    const TLS_1_3: u16 = 0x0304;
+
+    // Without a minimum version, the default of native-tls is kept and older versions are not offered.
+    let versions = client_hello_versions(|req| req);
+    assert!(!versions.is_empty());
+    assert!(!versions.contains(&TLS_1_0) && !versions.contains(&TLS_1_1));
+
+    let versions = client_hello_versions(|req| req.max_tls_version(attohttpc::TlsVersion::Tls1_3));
+    assert!(!versions.contains(&TLS_1_0) && !versions.contains(&TLS_1_1));
+
+    let versions = client_hello_versions(|req| req.min_tls_version(attohttpc::TlsVersion::Tls1_3));
+    assert_eq!(versions, [TLS_1_3]);
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn test_alpn_protocol() -> Result<(), anyhow::Error> {
+    // The server supports both `h2` and `http/1.1`.
+    let port = tools::start_hello_world_server(true).await?;
+    let url = format!("https://localhost:{}", port);
+
+    let resp = attohttpc::get(&url)
+        .danger_accept_invalid_certs(true)
+        .alpn_protocols(&["http/1.1"])
+        .send()?;
+    assert_eq!(resp.alpn_protocol(), Some("http/1.1"));
+    assert_eq!(resp.text()?, "hello");
+
+    let resp = attohttpc::get(&url).danger_accept_invalid_certs(true).send()?;
+    assert_eq!(resp.alpn_protocol(), None);
+
+    Ok(())
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn test_peer_certificate() -> Result<(), anyhow::Error> {
+    let port = tools::start_hello_world_server(true).await?;
+
+    let resp = attohttpc::get(format!("https://localhost:{}", port))
+        .danger_accept_invalid_certs(true)
+        .send()?;
+    let cert = resp.peer_certificate().expect("no peer certificate");
+    // The certificate is a DER SEQUENCE.
+    assert_eq!(cert.der()[0], 0x30);
+    assert!(cert.not_before().unwrap() < cert.not_after().unwrap());
+
+    let port = tools::start_hello_world_server(false).await?;
+    let resp = attohttpc::get(format!("http://localhost:{}", port)).send()?;
+    assert!(resp.peer_certificate().is_none());
+
+    Ok(())
+}
@@ -5,6 +5,8 @@ use hyper::server::conn::AddrIncoming;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Request, Response, Server};
 
+use tokio_rustls::rustls::ProtocolVersion;
+
 use super::tls::{TlsAcceptor, TlsConfigBuilder};
 
 pub async fn start_hello_world_server(tls: bool) -> Result<u16, hyper::Error> {
@@ -39,3 +41,25 @@ pub async fn start_hello_world_server(tls: bool) -> Result<u16, hyper::Error> {
 
     Ok(addr.port())
 }
+
+/// Start a TLS server answering "hello" which only accepts the given protocol versions.
+pub async fn start_hello_world_server_with_versions(versions: &[ProtocolVersion]) -> Result<u16, hyper::Error> {
+    let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+
+    async fn handler(_: Request<Body>) -> Result<Response<Body>, hyper::Error> {
+        Ok(Response::new(Body::from("hello")))
+    }
+
+    let bound = AddrIncoming::bind(&addr)?;
+    let port = bound.local_addr().port();
+    let make_service = make_service_fn(move |_| async move { Ok::<_, Infallible>(service_fn(handler)) });
+    let conf = TlsConfigBuilder::new()
+        .cert(include_bytes!("cert.pem"))
+        .key(include_bytes!("key.pem"))
+        .versions(versions)
+        .build()
+        .unwrap();
+    tokio::spawn(Server::builder(TlsAcceptor::new(conf, bound)).serve(make_service));
+
+    Ok(port)
+}
@@ -14,7 +14,7 @@ use futures::ready;
 use hyper::server::accept::Accept;
 use hyper::server::conn::{AddrIncoming, AddrStream};
 
-use tokio_rustls::rustls::{NoClientAuth, ServerConfig, TLSError};
+use tokio_rustls::rustls::{NoClientAuth, ProtocolVersion, ServerConfig, TLSError};
 
 /// Represents errors that can occur building the TlsConfig
 #[derive(Debug)]
@@ -52,6 +52,7 @@ pub(crate) struct TlsConfigBuilder {
     cert: Box<dyn Read + Send + Sync>,
     key: Box<dyn Read + Send + Sync>,
     ocsp_resp: Vec<u8>,
+    versions: Vec<ProtocolVersion>,
 }
 
 impl std::fmt::Debug for TlsConfigBuilder {
@@ -67,6 +68,7 @@ impl TlsConfigBuilder {
             key: Box::new(io::empty()),
             cert: Box::new(io::empty()),
             ocsp_resp: Vec::new(),
+            versions: Vec::new(),
         }
     }
 
@@ -82,6 +84,12 @@ impl TlsConfigBuilder {
         self
     }
 
+    /// restricts the Tls protocol versions accepted by the server
+    pub(crate) fn versions(mut self, versions: &[ProtocolVersion]) -> Self {
+        self.versions = versions.to_vec();
+        self
+    }
+
     pub(crate) fn build(mut self) -> Result<ServerConfig, TlsConfigError> {
         let mut cert_rdr = BufReader::new(self.cert);
         let cert = tokio_rustls::rustls::internal::pemfile::certs(&mut cert_rdr)
@@ -118,15 +126,20 @@ impl TlsConfigBuilder {
             .set_single_cert_with_ocsp_and_sct(cert, key, self.ocsp_resp, Vec::new())
             .map_err(TlsConfigError::InvalidKey)?;
         config.set_protocols(&["h2".into(), "http/1.1".into()]);
+        if !self.versions.is_empty() {
+            config.versions = self.versions;
+        }
         Ok(config)
     }
 }
 
+#[allow(dead_code)]
 struct LazyFile {
     path: PathBuf,
     file: Option<File>,
 }
 
+#[allow(dead_code)]
 impl LazyFile {
     fn lazy_read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         if self.file.is_none() {